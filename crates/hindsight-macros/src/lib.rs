@@ -0,0 +1,279 @@
+//! Attribute macros for the Hindsight tracing client.
+//!
+//! Use these through the `hindsight` crate (with its `macros` feature) rather
+//! than depending on this crate directly.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{quote, ToTokens};
+use syn::{parse_macro_input, Expr, FnArg, Ident, ItemFn, LitStr, Pat, ReturnType, Type, TypePath};
+
+/// Wrap a function in a hindsight span
+///
+/// The span is named after the function and records its named arguments as
+/// attributes. If the function returns a `Result`, an `Err` marks the span
+/// as errored with the error's `Display` output, or its `Debug` output when
+/// it has no `Display`. Works on both sync and
+/// async functions; spans started inside become children of this one.
+///
+/// Spans go to the global tracer (see `Tracer::install_global`); without one,
+/// the function runs untraced.
+///
+/// # Options
+///
+/// - `name = "..."`: span name instead of the function name
+/// - `skip(a, b)`: do not record these arguments
+/// - `skip_all`: do not record any arguments
+/// - `rename(a = "user.id")`: record argument `a` under another key
+/// - `tracer = expr`: use this tracer (a `Tracer` or `&Tracer`) instead of
+///   the global one
+///
+/// Arguments whose type implements `IntoAttributeValue` and `Clone` are
+/// recorded as such; others are recorded with their `Debug` output. Skip
+/// arguments that implement neither.
+///
+/// # Example
+/// ```
+/// use hindsight::Tracer;
+///
+/// struct Db;
+///
+/// #[hindsight::instrument(tracer = tracer, skip(tracer, db), rename(id = "user.id"))]
+/// fn load_user(tracer: &Tracer, db: &Db, id: i64) -> Result<String, String> {
+///     Ok(format!("user {}", id))
+/// }
+///
+/// let (tracer, _spans) = Tracer::in_memory();
+/// assert!(load_user(&tracer, &Db, 42).is_ok());
+/// ```
+#[proc_macro_attribute]
+pub fn instrument(args: TokenStream, item: TokenStream) -> TokenStream {
+    let mut options = Options::default();
+    let parser = syn::meta::parser(|meta| options.parse(meta));
+    parse_macro_input!(args with parser);
+
+    let item = parse_macro_input!(item as ItemFn);
+    match expand(options, item) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+#[derive(Default)]
+struct Options {
+    name: Option<LitStr>,
+    skip: Vec<Ident>,
+    skip_all: bool,
+    rename: Vec<(Ident, LitStr)>,
+    tracer: Option<Expr>,
+}
+
+impl Options {
+    fn parse(&mut self, meta: syn::meta::ParseNestedMeta) -> syn::Result<()> {
+        if meta.path.is_ident("name") {
+            self.name = Some(meta.value()?.parse()?);
+        } else if meta.path.is_ident("skip_all") {
+            self.skip_all = true;
+        } else if meta.path.is_ident("skip") {
+            meta.parse_nested_meta(|inner| {
+                self.skip.push(inner.path.require_ident()?.clone());
+                Ok(())
+            })?;
+        } else if meta.path.is_ident("rename") {
+            meta.parse_nested_meta(|inner| {
+                let ident = inner.path.require_ident()?.clone();
+                self.rename.push((ident, inner.value()?.parse()?));
+                Ok(())
+            })?;
+        } else if meta.path.is_ident("tracer") {
+            self.tracer = Some(meta.value()?.parse()?);
+        } else {
+            return Err(meta.error(
+                "unsupported option, expected `name`, `skip`, `skip_all`, `rename` or `tracer`",
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn expand(options: Options, item: ItemFn) -> syn::Result<TokenStream2> {
+    let ItemFn {
+        attrs,
+        vis,
+        sig,
+        block,
+    } = item;
+
+    let span_name = match &options.name {
+        Some(name) => name.value(),
+        None => sig.ident.to_string(),
+    };
+
+    // Arguments recorded as attributes
+    let mut recorded = Vec::new();
+    if !options.skip_all {
+        for input in &sig.inputs {
+            let FnArg::Typed(pat_type) = input else {
+                continue;
+            };
+            let Pat::Ident(pat_ident) = pat_type.pat.as_ref() else {
+                continue;
+            };
+            let ident = &pat_ident.ident;
+            if options.skip.contains(ident) {
+                continue;
+            }
+            let key = options
+                .rename
+                .iter()
+                .find(|(arg, _)| arg == ident)
+                .map(|(_, key)| key.value())
+                .unwrap_or_else(|| ident.to_string());
+            // `IntoAttributeValue` if the type has it, `Debug` otherwise
+            recorded.push(quote! {
+                .with_attribute(#key, {
+                    #[allow(unused_imports)]
+                    use ::hindsight::__macro_support::{RecordDebug as _, RecordValue as _};
+                    (&::hindsight::__macro_support::Arg(&#ident)).attribute_value()
+                })
+            });
+        }
+    }
+    for ident in options
+        .skip
+        .iter()
+        .chain(options.rename.iter().map(|(i, _)| i))
+    {
+        let known = sig.inputs.iter().any(|input| match input {
+            FnArg::Typed(pat_type) => {
+                matches!(pat_type.pat.as_ref(), Pat::Ident(p) if &p.ident == ident)
+            }
+            FnArg::Receiver(_) => false,
+        });
+        if !known {
+            return Err(syn::Error::new(ident.span(), "no argument with this name"));
+        }
+    }
+
+    let tracer = match &options.tracer {
+        // Bound to a local first so a temporary tracer outlives the span
+        Some(expr) => quote! {
+            let __hindsight_tracer_ref = &(#expr);
+            let __hindsight_tracer: ::core::option::Option<&::hindsight::Tracer> =
+                ::core::option::Option::Some(
+                    <_ as ::core::borrow::Borrow<::hindsight::Tracer>>::borrow(
+                        __hindsight_tracer_ref,
+                    ),
+                );
+        },
+        None => quote! {
+            let __hindsight_tracer: ::core::option::Option<&::hindsight::Tracer> =
+                ::hindsight::global_tracer();
+        },
+    };
+
+    let start_span = quote! {
+        #tracer
+        #[allow(unused_mut)]
+        let mut __hindsight_span = __hindsight_tracer
+            .map(|tracer| tracer.span(#span_name) #(#recorded)* .start());
+    };
+
+    let record_error = if returns_result(&sig.output) {
+        quote! {
+            if let ::core::result::Result::Err(e) = &__hindsight_result {
+                if let ::core::option::Option::Some(span) = __hindsight_span.as_mut() {
+                    #[allow(unused_imports)]
+                    use ::hindsight::__macro_support::{ErrorDebug as _, ErrorDisplay as _};
+                    span.set_error((&::hindsight::__macro_support::Arg(e)).error_message());
+                }
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    let end_span = quote! {
+        if let ::core::option::Option::Some(span) = __hindsight_span {
+            span.end();
+        }
+    };
+
+    let body = if sig.asyncness.is_some() {
+        // Give the async block's `?` and `return` something to infer from
+        let fake_return = match &sig.output {
+            ReturnType::Type(_, ty) if !contains_impl_trait(ty) => quote! {
+                #[allow(unreachable_code)]
+                if false {
+                    let __hindsight_fake_return: #ty = loop {};
+                    return __hindsight_fake_return;
+                }
+            },
+            _ => quote! {},
+        };
+        let stmts = &block.stmts;
+        quote! {
+            #start_span
+            let __hindsight_result = ::hindsight::__macro_support::instrument(
+                __hindsight_span.as_ref(),
+                async move {
+                    #fake_return
+                    #(#stmts)*
+                },
+            )
+            .await;
+            #record_error
+            #end_span
+            __hindsight_result
+        }
+    } else {
+        let closure_output = match &sig.output {
+            ReturnType::Type(arrow, ty) if !contains_impl_trait(ty) => quote! { #arrow #ty },
+            _ => quote! {},
+        };
+        quote! {
+            #start_span
+            let __hindsight_result = {
+                let _hindsight_guard = __hindsight_span.as_ref().map(|span| span.enter());
+                // `FnOnce`, so the body can return borrows of `&mut` arguments
+                ::hindsight::__macro_support::call_once(move || #closure_output #block)
+            };
+            #record_error
+            #end_span
+            __hindsight_result
+        }
+    };
+
+    Ok(quote! {
+        #(#attrs)*
+        #vis #sig {
+            #body
+        }
+    })
+}
+
+/// Whether the return type is spelled `Result<..>` (or a path ending in it)
+fn returns_result(output: &ReturnType) -> bool {
+    let ReturnType::Type(_, ty) = output else {
+        return false;
+    };
+    match ty.as_ref() {
+        Type::Path(TypePath { path, .. }) => path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "Result"),
+        _ => false,
+    }
+}
+
+/// Whether `impl Trait` appears anywhere in the type (it can't be named in a `let`)
+fn contains_impl_trait(ty: &Type) -> bool {
+    fn walk(tokens: TokenStream2) -> bool {
+        tokens.into_iter().any(|token| match token {
+            proc_macro2::TokenTree::Ident(ident) => ident == "impl",
+            proc_macro2::TokenTree::Group(group) => walk(group.stream()),
+            _ => false,
+        })
+    }
+    walk(ty.to_token_stream())
+}
@@ -3,11 +3,17 @@
 //! This crate defines the core types for W3C Trace Context and span representation.
 
 pub mod events;
+pub mod local;
 pub mod service;
 pub mod span;
+pub mod span_file;
 pub mod trace_context;
+#[cfg(feature = "upgrade")]
+pub mod upgrade;
 
 pub use events::*;
+pub use local::*;
 pub use service::*;
 pub use span::*;
+pub use span_file::*;
 pub use trace_context::*;
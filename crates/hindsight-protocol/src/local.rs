@@ -0,0 +1,35 @@
+use std::path::PathBuf;
+
+/// File name of the server's Unix socket in [`runtime_dir`]
+pub const UNIX_SOCKET_NAME: &str = "hindsight.sock";
+
+/// File name of the shared-memory rendezvous socket in [`runtime_dir`]
+pub const SHM_SOCKET_NAME: &str = "shm.sock";
+
+/// Directory where a local server advertises its endpoints
+///
+/// `$XDG_RUNTIME_DIR/hindsight`, or `None` if `XDG_RUNTIME_DIR` is unset.
+/// The directory is private to the user, which is what keeps other users
+/// off local endpoints.
+pub fn runtime_dir() -> Option<PathBuf> {
+    std::env::var_os("XDG_RUNTIME_DIR")
+        .filter(|dir| !dir.is_empty())
+        .map(|dir| PathBuf::from(dir).join("hindsight"))
+}
+
+/// Well-known path of the local server's Unix socket
+///
+/// The socket speaks everything the main port does (web UI, `Upgrade:
+/// rapace`, raw rapace).
+pub fn unix_socket_path() -> Option<PathBuf> {
+    runtime_dir().map(|dir| dir.join(UNIX_SOCKET_NAME))
+}
+
+/// Well-known path of the local server's shared-memory rendezvous socket
+///
+/// A client connects here, sends the path of a shared-memory segment it
+/// created (one line), and waits for `ok` once the server has mapped it.
+/// Rapace then runs over the segment.
+pub fn shm_socket_path() -> Option<PathBuf> {
+    runtime_dir().map(|dir| dir.join(SHM_SOCKET_NAME))
+}
@@ -1,5 +1,6 @@
 use facet::Facet;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
+#[cfg(not(all(target_arch = "wasm32", feature = "js")))]
 use std::time::{SystemTime, UNIX_EPOCH};
 
 use crate::trace_context::{SpanId, TraceId};
@@ -9,6 +10,7 @@ use crate::trace_context::{SpanId, TraceId};
 pub struct Timestamp(pub u64);
 
 impl Timestamp {
+    #[cfg(not(all(target_arch = "wasm32", feature = "js")))]
     pub fn now() -> Self {
         let nanos = SystemTime::now()
             .duration_since(UNIX_EPOCH)
@@ -16,6 +18,20 @@ impl Timestamp {
             .as_nanos() as u64;
         Self(nanos)
     }
+
+    /// In browsers `SystemTime` is unavailable; `performance.timeOrigin +
+    /// performance.now()` gives sub-millisecond wall-clock time instead
+    #[cfg(all(target_arch = "wasm32", feature = "js"))]
+    pub fn now() -> Self {
+        use wasm_bindgen::JsCast;
+
+        let millis = js_sys::Reflect::get(&js_sys::global(), &"performance".into())
+            .ok()
+            .and_then(|performance| performance.dyn_into::<web_sys::Performance>().ok())
+            .map(|performance| performance.time_origin() + performance.now())
+            .unwrap_or_else(js_sys::Date::now);
+        Self((millis * 1_000_000.0) as u64)
+    }
 }
 
 /// Span represents a single operation in a trace
@@ -35,13 +51,24 @@ pub struct Span {
 
 impl Span {
     /// Calculate span duration in nanoseconds
+    ///
+    /// Zero if the span claims to end before it starts.
     pub fn duration_nanos(&self) -> Option<u64> {
-        self.end_time.map(|end| end.0 - self.start_time.0)
+        self.end_time
+            .map(|end| end.0.saturating_sub(self.start_time.0))
+    }
+
+    /// Whether this is an in-flight record of a span that hasn't ended yet
+    ///
+    /// Clients with heartbeats enabled send these when a span starts and
+    /// periodically while it runs; the finished span replaces them.
+    pub fn is_running(&self) -> bool {
+        self.end_time.is_none()
     }
 }
 
 /// Attribute value
-#[derive(Clone, Debug, Facet)]
+#[derive(Clone, Debug, PartialEq, Facet)]
 #[repr(u8)]
 pub enum AttributeValue {
     String(String),
@@ -78,6 +105,11 @@ pub struct Trace {
 
 impl Trace {
     /// Build a trace from a flat list of spans
+    ///
+    /// The root is the span without a parent. If the root lives in a process
+    /// that doesn't report here, it is the earliest span whose parent is
+    /// missing, as in [`SpanNode::from_spans`]; None only if `spans` is
+    /// empty.
     pub fn from_spans(mut spans: Vec<Span>) -> Option<Self> {
         if spans.is_empty() {
             return None;
@@ -87,8 +119,17 @@ impl Trace {
 
         let trace_id = spans[0].trace_id;
 
-        // Find root span (one with no parent)
-        let root_span = spans.iter().find(|s| s.parent_span_id.is_none())?;
+        let ids: HashSet<SpanId> = spans.iter().map(|span| span.span_id).collect();
+        let root_span = spans
+            .iter()
+            .find(|s| s.parent_span_id.is_none())
+            .or_else(|| {
+                spans.iter().find(|s| {
+                    s.parent_span_id
+                        .is_some_and(|parent| !ids.contains(&parent))
+                })
+            })
+            .unwrap_or(&spans[0]);
 
         let root_span_id = root_span.span_id;
         let start_time = root_span.start_time;
@@ -104,6 +145,11 @@ impl Trace {
         })
     }
 
+    /// Whether any span in the trace is still in flight
+    pub fn is_running(&self) -> bool {
+        self.spans.iter().any(Span::is_running)
+    }
+
     /// Get children of a given span
     pub fn children(&self, span_id: SpanId) -> Vec<&Span> {
         self.spans
@@ -159,6 +205,70 @@ impl Trace {
     }
 }
 
+/// A span with its children, as trace views show it
+#[derive(Clone, Debug)]
+pub struct SpanNode {
+    pub span: Span,
+    /// Child spans, by start time
+    pub children: Vec<SpanNode>,
+    /// Nesting level; roots are at 0
+    pub depth: usize,
+}
+
+impl SpanNode {
+    /// Arrange a trace's spans into trees
+    pub fn from_trace(trace: &Trace) -> Vec<SpanNode> {
+        Self::from_spans(&trace.spans)
+    }
+
+    /// Arrange spans of one trace into trees, roots first by start time
+    ///
+    /// Spans whose parent is missing (e.g. it lives in a process that hasn't
+    /// reported yet) are shown as roots.
+    pub fn from_spans(spans: &[Span]) -> Vec<SpanNode> {
+        let ids: HashSet<SpanId> = spans.iter().map(|span| span.span_id).collect();
+        let mut children: HashMap<SpanId, Vec<&Span>> = HashMap::new();
+        let mut roots = Vec::new();
+        for span in spans {
+            match span.parent_span_id {
+                Some(parent) if parent != span.span_id && ids.contains(&parent) => {
+                    children.entry(parent).or_default().push(span)
+                }
+                _ => roots.push(span),
+            }
+        }
+
+        fn build(span: &Span, children: &HashMap<SpanId, Vec<&Span>>, depth: usize) -> SpanNode {
+            let mut child_spans = children.get(&span.span_id).cloned().unwrap_or_default();
+            child_spans.sort_by_key(|child| child.start_time.0);
+            SpanNode {
+                span: span.clone(),
+                children: child_spans
+                    .into_iter()
+                    .map(|child| build(child, children, depth + 1))
+                    .collect(),
+                depth,
+            }
+        }
+
+        roots.sort_by_key(|root| root.start_time.0);
+        roots
+            .into_iter()
+            .map(|root| build(root, &children, 0))
+            .collect()
+    }
+
+    /// This node and its descendants in display order, as
+    /// `(span, depth, has_children)`
+    pub fn flatten(&self) -> Vec<(Span, usize, bool)> {
+        let mut result = vec![(self.span.clone(), self.depth, !self.children.is_empty())];
+        for child in &self.children {
+            result.extend(child.flatten());
+        }
+        result
+    }
+}
+
 /// Type of trace based on framework detection
 #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Facet)]
 #[repr(u8)]
@@ -198,6 +308,8 @@ pub struct TraceSummary {
     pub duration_nanos: Option<u64>,
     pub span_count: usize,
     pub has_errors: bool,
+    /// Some span is still in flight
+    pub is_running: bool,
     pub trace_type: TraceType,
 }
 
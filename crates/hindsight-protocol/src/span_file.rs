@@ -0,0 +1,43 @@
+use facet::Facet;
+
+use crate::span::*;
+
+/// Current span file format version
+pub const SPAN_FILE_VERSION: u32 = 1;
+
+/// One line of a span file
+///
+/// Span files are JSON Lines: one record per line, each holding a finished
+/// span with its original IDs and timestamps. Clients write them when no
+/// server is reachable; the server imports them later.
+#[derive(Clone, Debug, Facet)]
+pub struct SpanFileRecord {
+    pub version: u32,
+    pub span: Span,
+}
+
+/// Encode a span as one span file line (without the trailing newline)
+pub fn encode_span_line(span: &Span) -> String {
+    facet_json::to_string(&SpanFileRecord {
+        version: SPAN_FILE_VERSION,
+        span: span.clone(),
+    })
+}
+
+/// Decode one span file line
+pub fn decode_span_line(line: &str) -> Result<Span, SpanFileError> {
+    let record: SpanFileRecord =
+        facet_json::from_str(line).map_err(|e| SpanFileError::InvalidRecord(e.to_string()))?;
+    if record.version != SPAN_FILE_VERSION {
+        return Err(SpanFileError::UnsupportedVersion(record.version));
+    }
+    Ok(record.span)
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SpanFileError {
+    #[error("invalid span record: {0}")]
+    InvalidRecord(String),
+    #[error("unsupported span file version {0}")]
+    UnsupportedVersion(u32),
+}
@@ -98,6 +98,10 @@ pub struct TraceContext {
     pub span_id: SpanId,
     pub parent_span_id: Option<SpanId>,
     pub flags: u8,
+    /// Vendor-specific W3C tracestate header, passed through unchanged
+    pub tracestate: Option<String>,
+    /// W3C baggage (`key=value` pairs), passed through unchanged
+    pub baggage: Option<String>,
 }
 
 impl TraceContext {
@@ -108,6 +112,8 @@ impl TraceContext {
             span_id: SpanId::new(),
             parent_span_id: None,
             flags: 0x01, // Sampled
+            tracestate: None,
+            baggage: None,
         }
     }
 
@@ -118,6 +124,8 @@ impl TraceContext {
             span_id: SpanId::new(),
             parent_span_id: Some(self.span_id),
             flags: self.flags,
+            tracestate: self.tracestate.clone(),
+            baggage: self.baggage.clone(),
         }
     }
 
@@ -141,9 +149,24 @@ impl TraceContext {
             span_id,
             parent_span_id: None,
             flags,
+            tracestate: None,
+            baggage: None,
         })
     }
 
+    /// Parse from W3C traceparent and (optional) tracestate headers
+    pub fn from_headers(
+        traceparent: &str,
+        tracestate: Option<&str>,
+    ) -> Result<Self, TraceContextError> {
+        let mut context = Self::from_traceparent(traceparent)?;
+        context.tracestate = tracestate
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(str::to_string);
+        Ok(context)
+    }
+
     /// Format as W3C traceparent header
     pub fn to_traceparent(&self) -> String {
         format!(
@@ -0,0 +1,248 @@
+//! The `Upgrade: rapace` handshake, shared by everything that connects to a
+//! server's HTTP port (`upgrade` feature)
+
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf};
+
+/// Give up on responses with headers larger than this
+const MAX_RESPONSE_HEAD: usize = 16 * 1024;
+
+/// Why an `Upgrade: rapace` handshake failed
+#[derive(Debug, thiserror::Error)]
+pub enum UpgradeError {
+    #[error("failed to send upgrade request: {0}")]
+    Send(#[source] io::Error),
+    #[error("failed to read upgrade response: {0}")]
+    Receive(#[source] io::Error),
+    #[error("server closed the connection during the upgrade handshake")]
+    Closed,
+    #[error("HTTP upgrade response too large")]
+    TooLarge,
+    #[error("malformed HTTP upgrade response: {0}")]
+    Malformed(#[from] httparse::Error),
+    #[error("HTTP upgrade rejected: {code} {reason} (check the authorization header)")]
+    Unauthorized { code: u16, reason: String },
+    #[error("HTTP upgrade failed: {code} {reason}")]
+    Refused { code: u16, reason: String },
+    #[error("server did not agree to Upgrade: rapace (got {0:?})")]
+    WrongProtocol(Option<String>),
+    #[error("server did not agree to Connection: Upgrade (got {0:?})")]
+    NotUpgraded(String),
+}
+
+/// Perform the `Upgrade: rapace` handshake over an open connection
+///
+/// `host` is sent as the `Host` header and should include the port;
+/// `authorization` is sent verbatim as the `Authorization` header. Succeeds
+/// only on a `101` response that echoes `Upgrade: rapace`, returning the
+/// connection ready for rapace.
+pub async fn http_upgrade<S>(
+    mut stream: S,
+    host: &str,
+    authorization: Option<&str>,
+) -> Result<Rewind<S>, UpgradeError>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut request = format!(
+        "GET / HTTP/1.1\r\n\
+         Host: {}\r\n\
+         Upgrade: rapace\r\n\
+         Connection: Upgrade\r\n",
+        host
+    );
+    if let Some(authorization) = authorization {
+        request.push_str(&format!("Authorization: {}\r\n", authorization));
+    }
+    request.push_str("\r\n");
+
+    stream
+        .write_all(request.as_bytes())
+        .await
+        .map_err(UpgradeError::Send)?;
+
+    let mut buffer = Vec::with_capacity(1024);
+    loop {
+        let mut chunk = [0u8; 1024];
+        let n = stream
+            .read(&mut chunk)
+            .await
+            .map_err(UpgradeError::Receive)?;
+        if n == 0 {
+            return Err(UpgradeError::Closed);
+        }
+        buffer.extend_from_slice(&chunk[..n]);
+
+        if let Some(head_len) = check_response(&buffer)? {
+            // Anything after the headers already belongs to rapace
+            let rest = buffer.split_off(head_len);
+            return Ok(Rewind::new(stream, rest));
+        }
+        if buffer.len() > MAX_RESPONSE_HEAD {
+            return Err(UpgradeError::TooLarge);
+        }
+    }
+}
+
+/// Validate a (possibly partial) upgrade response
+///
+/// Returns the length of the response head once it is complete.
+fn check_response(buffer: &[u8]) -> Result<Option<usize>, UpgradeError> {
+    let mut headers = [httparse::EMPTY_HEADER; 32];
+    let mut response = httparse::Response::new(&mut headers);
+    let head_len = match response.parse(buffer)? {
+        httparse::Status::Complete(head_len) => head_len,
+        httparse::Status::Partial => return Ok(None),
+    };
+
+    let code = response.code.unwrap_or_default();
+    let reason = response.reason.unwrap_or_default().to_string();
+    match code {
+        101 => {}
+        401 | 403 => return Err(UpgradeError::Unauthorized { code, reason }),
+        _ => return Err(UpgradeError::Refused { code, reason }),
+    }
+
+    let header = |name: &str| {
+        response
+            .headers
+            .iter()
+            .find(|header| header.name.eq_ignore_ascii_case(name))
+            .map(|header| String::from_utf8_lossy(header.value).trim().to_string())
+    };
+    let upgrade = header("upgrade");
+    if !upgrade
+        .as_deref()
+        .is_some_and(|upgrade| upgrade.eq_ignore_ascii_case("rapace"))
+    {
+        return Err(UpgradeError::WrongProtocol(upgrade));
+    }
+    let connection = header("connection").unwrap_or_default();
+    if !connection
+        .split(',')
+        .any(|token| token.trim().eq_ignore_ascii_case("upgrade"))
+    {
+        return Err(UpgradeError::NotUpgraded(connection));
+    }
+
+    Ok(Some(head_len))
+}
+
+/// Stream that yields `head` before reading from `inner`
+///
+/// Puts back bytes read past an HTTP head: a server sniffing a connection,
+/// or a client that read rapace frames along with the `101` response.
+pub struct Rewind<S> {
+    head: Vec<u8>,
+    pos: usize,
+    inner: S,
+}
+
+impl<S> Rewind<S> {
+    /// Replay `head`, then read from `inner`
+    pub fn new(inner: S, head: Vec<u8>) -> Self {
+        Self {
+            head,
+            pos: 0,
+            inner,
+        }
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for Rewind<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        if self.pos < self.head.len() {
+            let n = buf.remaining().min(self.head.len() - self.pos);
+            let start = self.pos;
+            buf.put_slice(&self.head[start..start + n]);
+            self.pos += n;
+            return Poll::Ready(Ok(()));
+        }
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for Rewind<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_response() {
+        let ok = b"HTTP/1.1 101 Switching Protocols\r\nupgrade: Rapace\r\nconnection: upgrade\r\n\r\nrest";
+        assert_eq!(check_response(ok).unwrap(), Some(ok.len() - 4));
+
+        assert_eq!(check_response(b"HTTP/1.1 101 Switching").unwrap(), None);
+
+        // A body mentioning 101 is not an upgrade
+        let not_found = b"HTTP/1.1 404 Not Found\r\ncontent-length: 3\r\n\r\n101";
+        assert!(matches!(
+            check_response(not_found),
+            Err(UpgradeError::Refused { code: 404, .. })
+        ));
+
+        let unauthorized = b"HTTP/1.1 401 Unauthorized\r\n\r\n";
+        assert!(matches!(
+            check_response(unauthorized),
+            Err(UpgradeError::Unauthorized { code: 401, .. })
+        ));
+
+        let wrong_protocol =
+            b"HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\r\n";
+        assert!(matches!(
+            check_response(wrong_protocol),
+            Err(UpgradeError::WrongProtocol(Some(_)))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_http_upgrade_keeps_early_bytes() {
+        let (client, mut server) = tokio::io::duplex(1024);
+        let server = tokio::spawn(async move {
+            let mut request = vec![0u8; 1024];
+            let n = server.read(&mut request).await.unwrap();
+            let request = String::from_utf8_lossy(&request[..n]).to_string();
+            server
+                .write_all(
+                    b"HTTP/1.1 101 Switching Protocols\r\nUpgrade: rapace\r\nConnection: Upgrade\r\n\r\nhello",
+                )
+                .await
+                .unwrap();
+            request
+        });
+
+        let mut upgraded = http_upgrade(client, "localhost:1990", Some("Bearer t"))
+            .await
+            .unwrap();
+        let mut early = [0u8; 5];
+        upgraded.read_exact(&mut early).await.unwrap();
+        assert_eq!(&early, b"hello");
+
+        let request = server.await.unwrap();
+        assert!(request.contains("Host: localhost:1990\r\n"));
+        assert!(request.contains("Authorization: Bearer t\r\n"));
+    }
+}
@@ -0,0 +1,217 @@
+//! Reading and checking the start of a connection before picking a protocol
+
+use std::io;
+use std::time::Duration;
+use subtle::ConstantTimeEq;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// Largest HTTP request head read before picking a protocol
+const MAX_HEAD_LEN: usize = 16 * 1024;
+
+/// How long a client may take to send its request head
+const HEAD_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Request lines of the HTTP requests the server answers
+const HTTP_METHODS: &[&[u8]] = &[
+    b"GET ",
+    b"POST ",
+    b"PUT ",
+    b"DELETE ",
+    b"HEAD ",
+    b"OPTIONS ",
+];
+
+/// Query parameter carrying the auth token on WebSocket URLs, since
+/// browsers can't set headers on a WebSocket handshake
+pub(crate) const ACCESS_TOKEN_PARAM: &str = "access_token";
+
+/// Read the start of a connection: the whole request head for HTTP, or
+/// whatever arrives first for raw rapace
+pub(crate) async fn read_head<S: AsyncRead + Unpin>(stream: &mut S) -> io::Result<Vec<u8>> {
+    let mut head = Vec::new();
+    let read = async {
+        let mut buf = [0u8; 1024];
+        loop {
+            let n = stream.read(&mut buf).await?;
+            if n == 0 {
+                break;
+            }
+            head.extend_from_slice(&buf[..n]);
+            if !could_be_http(&head)
+                || head.windows(4).any(|window| window == b"\r\n\r\n")
+                || head.len() >= MAX_HEAD_LEN
+            {
+                break;
+            }
+        }
+        io::Result::Ok(())
+    };
+    tokio::time::timeout(HEAD_TIMEOUT, read)
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "request head timed out"))??;
+    Ok(head)
+}
+
+/// Whether `head` starts an HTTP request
+pub(crate) fn is_http(head: &[u8]) -> bool {
+    HTTP_METHODS.iter().any(|method| head.starts_with(method))
+}
+
+/// Whether `head` starts, or may still turn into, an HTTP request
+fn could_be_http(head: &[u8]) -> bool {
+    HTTP_METHODS
+        .iter()
+        .any(|method| head.starts_with(method) || method.starts_with(head))
+}
+
+/// The parts of an HTTP request head the server looks at
+pub(crate) struct RequestHead {
+    path: String,
+    headers: Vec<(String, String)>,
+}
+
+impl RequestHead {
+    /// Parse a complete request head; None if it is malformed or cut short
+    pub(crate) fn parse(head: &[u8]) -> Option<Self> {
+        let mut headers = [httparse::EMPTY_HEADER; 64];
+        let mut request = httparse::Request::new(&mut headers);
+        match request.parse(head) {
+            Ok(httparse::Status::Complete(_)) => {}
+            _ => return None,
+        }
+        Some(Self {
+            path: request.path?.to_string(),
+            headers: request
+                .headers
+                .iter()
+                .filter_map(|header| {
+                    let value = std::str::from_utf8(header.value).ok()?;
+                    Some((header.name.to_string(), value.trim().to_string()))
+                })
+                .collect(),
+        })
+    }
+
+    /// First value of the header `name` (case-insensitive)
+    pub(crate) fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Whether this is a WebSocket handshake
+    pub(crate) fn is_websocket_upgrade(&self) -> bool {
+        self.header("upgrade")
+            .is_some_and(|upgrade| upgrade.eq_ignore_ascii_case("websocket"))
+    }
+
+    /// Percent-decoded value of the query parameter `name`
+    pub(crate) fn query_param(&self, name: &str) -> Option<String> {
+        let (_, query) = self.path.split_once('?')?;
+        query
+            .split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .find(|(key, _)| *key == name)
+            .and_then(|(_, value)| percent_decode(value))
+    }
+
+    /// Whether the request carries `token`, in an `Authorization: Bearer`
+    /// header or in the [`ACCESS_TOKEN_PARAM`] query parameter
+    pub(crate) fn is_authorized(&self, token: &str) -> bool {
+        is_authorized(self.header("authorization"), token)
+            || self
+                .query_param(ACCESS_TOKEN_PARAM)
+                .is_some_and(|presented| token_matches(&presented, token))
+    }
+}
+
+/// Whether an `Authorization` header value carries `Bearer <token>`
+pub(crate) fn is_authorized(authorization: Option<&str>, token: &str) -> bool {
+    authorization
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|presented| token_matches(presented.trim(), token))
+}
+
+/// Compare tokens in constant time, so timing doesn't reveal how much of a
+/// guess was right
+fn token_matches(presented: &str, token: &str) -> bool {
+    presented.as_bytes().ct_eq(token.as_bytes()).into()
+}
+
+fn percent_decode(value: &str) -> Option<String> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'%' => {
+                let hex = value.get(index + 1..index + 3)?;
+                decoded.push(u8::from_str_radix(hex, 16).ok()?);
+                index += 3;
+            }
+            b'+' => {
+                decoded.push(b' ');
+                index += 1;
+            }
+            byte => {
+                decoded.push(byte);
+                index += 1;
+            }
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(head: &str) -> RequestHead {
+        RequestHead::parse(head.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn test_is_authorized() {
+        assert!(is_authorized(Some("Bearer s3cret"), "s3cret"));
+        assert!(!is_authorized(Some("Bearer s3cre"), "s3cret"));
+        assert!(!is_authorized(Some("Basic s3cret"), "s3cret"));
+        assert!(!is_authorized(None, "s3cret"));
+    }
+
+    #[test]
+    fn test_websocket_token() {
+        let head =
+            parse("GET /?access_token=s3%2Fcret HTTP/1.1\r\nHost: x\r\nUpgrade: WebSocket\r\n\r\n");
+        assert!(head.is_websocket_upgrade());
+        assert!(head.is_authorized("s3/cret"));
+        assert!(!head.is_authorized("s3cret"));
+
+        let head = parse("GET / HTTP/1.1\r\nupgrade: websocket\r\nauthorization: Bearer t\r\n\r\n");
+        assert!(head.is_authorized("t"));
+
+        // Incomplete heads are not trusted
+        assert!(RequestHead::parse(b"GET / HTTP/1.1\r\nAuthorization: Bearer t\r\n").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_read_head() {
+        // Everything up to the end of the head, across several reads
+        let (mut client, mut server) = tokio::io::duplex(64);
+        let long_header = format!("X-Padding: {}\r\n", "a".repeat(2000));
+        let request = format!("GET / HTTP/1.1\r\n{}\r\n", long_header);
+        let writer = tokio::spawn(async move {
+            use tokio::io::AsyncWriteExt;
+            client.write_all(request.as_bytes()).await.unwrap();
+            client
+        });
+        let head = read_head(&mut server).await.unwrap();
+        assert!(head.ends_with(b"\r\n\r\n"));
+        assert!(RequestHead::parse(&head).is_some());
+        drop(writer.await.unwrap());
+
+        // Raw rapace: whatever arrived first
+        let mut raw: &[u8] = &[0, 1, 2, 3];
+        assert_eq!(read_head(&mut raw).await.unwrap(), [0, 1, 2, 3]);
+    }
+}
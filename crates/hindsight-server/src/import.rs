@@ -0,0 +1,159 @@
+//! Replay span files written by the client's `FileExporter`
+
+use anyhow::Context;
+use hindsight_protocol::upgrade::http_upgrade;
+use hindsight_protocol::*;
+use rapace::RpcSession;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_rustls::rustls::pki_types::ServerName;
+use tokio_rustls::rustls::{self, crypto, RootCertStore};
+use tokio_rustls::TlsConnector;
+
+/// Spans sent per `ingest_spans` call
+const IMPORT_BATCH_SIZE: usize = 500;
+
+/// Read every span from a span file
+///
+/// Blank lines are skipped; a malformed line is an error naming the file and
+/// line number.
+pub fn read_span_file(path: &Path) -> anyhow::Result<Vec<Span>> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read {}", path.display()))?;
+
+    let mut spans = Vec::new();
+    for (index, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let span =
+            decode_span_line(line).with_context(|| format!("{}:{}", path.display(), index + 1))?;
+        spans.push(span);
+    }
+    Ok(spans)
+}
+
+/// How [`import_span_files`] reaches the server
+#[derive(Clone, Default)]
+pub struct ImportOptions {
+    /// Token for a server started with `--auth-token`, sent as
+    /// `Authorization: Bearer <token>` in the `Upgrade: rapace` handshake
+    pub auth_token: Option<String>,
+    /// Connect with TLS (to the server's TLS port)
+    pub tls: bool,
+    /// CA certificates (PEM) to trust besides the public web PKI roots;
+    /// implies `tls`
+    pub tls_ca_file: Option<PathBuf>,
+}
+
+// By hand, so the token never ends up in logs
+impl fmt::Debug for ImportOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ImportOptions")
+            .field(
+                "auth_token",
+                &self.auth_token.as_ref().map(|_| "[REDACTED]"),
+            )
+            .field("tls", &self.tls)
+            .field("tls_ca_file", &self.tls_ca_file)
+            .finish()
+    }
+}
+
+/// Send the spans in `paths` to the server at `addr`
+///
+/// Spans keep their original IDs and timestamps, so imported traces look
+/// exactly as they would have live. Returns the number of spans the server
+/// accepted.
+pub async fn import_span_files(
+    addr: &str,
+    paths: &[impl AsRef<Path>],
+    options: &ImportOptions,
+) -> anyhow::Result<u32> {
+    let stream = tokio::net::TcpStream::connect(addr)
+        .await
+        .with_context(|| format!("failed to connect to {}", addr))?;
+
+    let transport = if options.tls || options.tls_ca_file.is_some() {
+        let connector = tls_connector(options.tls_ca_file.as_deref())?;
+        let host = addr
+            .rsplit_once(':')
+            .map_or(addr, |(host, _)| host)
+            .trim_start_matches('[')
+            .trim_end_matches(']');
+        let server_name = ServerName::try_from(host.to_string())
+            .with_context(|| format!("invalid server name {:?}", host))?;
+        let stream = connector
+            .connect(server_name, stream)
+            .await
+            .with_context(|| format!("TLS handshake with {} failed", addr))?;
+        open_transport(stream, addr, options.auth_token.as_deref()).await?
+    } else {
+        open_transport(stream, addr, options.auth_token.as_deref()).await?
+    };
+
+    let session = Arc::new(RpcSession::new(transport));
+    let session_clone = session.clone();
+    tokio::spawn(async move {
+        if let Err(e) = session_clone.run().await {
+            tracing::error!("Import session error: {}", e);
+        }
+    });
+    let client = HindsightServiceClient::new(session);
+
+    let mut accepted = 0;
+    for path in paths {
+        let path = path.as_ref();
+        let spans = read_span_file(path)?;
+        tracing::info!("Importing {} spans from {}", spans.len(), path.display());
+
+        for chunk in spans.chunks(IMPORT_BATCH_SIZE) {
+            accepted += client
+                .ingest_spans(chunk.to_vec())
+                .await
+                .map_err(|e| anyhow::anyhow!("ingest_spans failed: {:?}", e))?;
+        }
+    }
+    Ok(accepted)
+}
+
+/// Speak rapace over `stream` after an `Upgrade: rapace` handshake, like
+/// the client does, carrying the auth token if there is one
+async fn open_transport<S>(
+    stream: S,
+    addr: &str,
+    auth_token: Option<&str>,
+) -> anyhow::Result<rapace::Transport>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + Sync + 'static,
+{
+    let authorization = auth_token.map(|token| format!("Bearer {}", token));
+    let stream = http_upgrade(stream, addr, authorization.as_deref())
+        .await
+        .with_context(|| format!("upgrade to rapace with {} failed", addr))?;
+    Ok(rapace::Transport::stream(stream))
+}
+
+/// Connector trusting the public web PKI roots and the CAs in `ca_file`
+fn tls_connector(ca_file: Option<&Path>) -> anyhow::Result<TlsConnector> {
+    let mut roots = RootCertStore::empty();
+    roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+    if let Some(path) = ca_file {
+        let pem =
+            std::fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+        for cert in rustls_pemfile::certs(&mut &pem[..]) {
+            let cert =
+                cert.with_context(|| format!("invalid certificate in {}", path.display()))?;
+            roots.add(cert)?;
+        }
+    }
+
+    let config =
+        rustls::ClientConfig::builder_with_provider(Arc::new(crypto::ring::default_provider()))
+            .with_safe_default_protocol_versions()?
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+    Ok(TlsConnector::from(Arc::new(config)))
+}
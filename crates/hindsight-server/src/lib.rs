@@ -1,6 +1,15 @@
+mod head;
+mod import;
 mod seed_data;
 mod service_impl;
+#[cfg(unix)]
+mod shm;
+#[cfg(unix)]
+mod socket;
 mod storage;
+mod tls;
+#[cfg(unix)]
+mod unix;
 
 use axum::{
     extract::Request,
@@ -9,25 +18,82 @@ use axum::{
     routing::get,
     Router,
 };
+use hindsight_protocol::upgrade::Rewind;
 use hindsight_protocol::*;
 use hyper::upgrade::Upgraded;
 use hyper_util::rt::TokioIo;
 use rapace::RpcSession;
 use std::sync::Arc;
 use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite};
 use tower::Service;
 
+use crate::head::{is_authorized, is_http, read_head, RequestHead};
+pub use crate::import::{import_span_files, read_span_file, ImportOptions};
 use crate::service_impl::HindsightServiceImpl;
 use crate::storage::TraceStore;
 
+/// Optional listeners and access control
+#[derive(Clone, Debug, Default)]
+pub struct ServerOptions {
+    /// Also listen on a Unix socket
+    pub unix_socket: Option<UnixSocket>,
+    /// Also accept shared-memory sessions from clients on the same host,
+    /// through a rendezvous socket at this path (see `Tracer::connect_shm`
+    /// and [`hindsight_protocol::shm_socket_path`] for the default)
+    pub shm_socket: Option<std::path::PathBuf>,
+    /// Also listen for TLS connections
+    pub tls: Option<TlsListener>,
+    /// Require `Authorization: Bearer <token>` from network clients
+    ///
+    /// Checked on `Upgrade: rapace` and WebSocket handshakes; browsers,
+    /// which can't set headers on a WebSocket, pass `?access_token=<token>`
+    /// in the URL instead (open the web UI with it). Raw rapace connections,
+    /// which can't carry a token, are refused. The Unix socket relies on
+    /// file permissions instead.
+    pub auth_token: Option<String>,
+}
+
+/// Unix socket the server listens on in addition to its TCP port
+#[derive(Clone, Debug)]
+pub struct UnixSocket {
+    /// Socket path (a socket left there by a server that exited is replaced)
+    pub path: std::path::PathBuf,
+    /// Permission bits for the socket file, e.g. `0o600` or `0o660`
+    pub mode: u32,
+}
+
+/// TLS port, serving the same protocols as the main port
+#[derive(Clone, Debug)]
+pub struct TlsListener {
+    pub port: u16,
+    /// PEM certificate chain
+    pub cert_path: std::path::PathBuf,
+    /// PEM private key
+    pub key_path: std::path::PathBuf,
+}
+
 pub async fn run_server(
     host: impl Into<String>,
     http_port: u16,
     _tcp_port: u16, // Unused - unified server handles all protocols
     ttl_secs: u64,
     seed: bool,
+    options: ServerOptions,
+) -> anyhow::Result<()> {
+    let addr = format!("{}:{}", host.into(), http_port);
+    let listener = tokio::net::TcpListener::bind(&addr).await?;
+    run_server_on(listener, ttl_secs, seed, options).await
+}
+
+/// [`run_server`] on a listener the caller already bound, e.g. to port 0
+/// so the OS picks a free port
+pub async fn run_server_on(
+    listener: tokio::net::TcpListener,
+    ttl_secs: u64,
+    seed: bool,
+    options: ServerOptions,
 ) -> anyhow::Result<()> {
-    let host = host.into();
     tracing::info!("🔍 Hindsight server starting");
 
     let store = TraceStore::new(Duration::from_secs(ttl_secs));
@@ -41,12 +107,23 @@ pub async fn run_server(
 
     let service = Arc::new(HindsightServiceImpl::new(store));
 
+    // Same-host clients can skip TCP entirely (see `Tracer::connect_shm`)
+    if let Some(path) = options.shm_socket.clone() {
+        #[cfg(unix)]
+        tokio::spawn(shm::serve_shm(path, service.clone()));
+        #[cfg(not(unix))]
+        tracing::warn!(
+            "Shared memory is not supported on this platform, ignoring {}",
+            path.display()
+        );
+    }
+
     // Serve unified server - handles ALL protocols on single port:
     // - HTTP GET → Web UI
     // - WebSocket upgrade → WASM clients
     // - HTTP Upgrade: rapace → Native clients
     // - Raw binary → Direct Rapace TCP
-    serve_http_unified(&host, http_port, service).await?;
+    serve_http_unified(listener, options, service).await?;
 
     Ok(())
 }
@@ -54,24 +131,45 @@ pub async fn run_server(
 /// Unified HTTP server - handles ALL protocols on a single port
 /// Handles: HTTP GET /, WebSocket upgrade, Rapace upgrade, raw TCP
 async fn serve_http_unified(
-    host: &str,
-    port: u16,
+    listener: tokio::net::TcpListener,
+    options: ServerOptions,
     service: Arc<HindsightServiceImpl>,
 ) -> anyhow::Result<()> {
-    let app = Router::new()
-        .route(
-            "/",
-            get({
-                let service = service.clone();
-                move |headers: HeaderMap, req: Request| handle_root(headers, req, service.clone())
-            }),
-        )
-        .route("/pkg/*file", get(serve_pkg_file))
-        .nest_service("/static", tower_http::services::ServeDir::new("static"))
-        .with_state(service.clone());
+    let addr = listener.local_addr()?;
+    let auth_token: Option<Arc<str>> = options.auth_token.map(Arc::from);
+    let app = build_router(service.clone(), auth_token.clone());
+
+    // The Unix socket sniffs protocols exactly like the TCP port below
+    if let Some(unix_socket) = options.unix_socket {
+        #[cfg(unix)]
+        {
+            let service = service.clone();
+            let app = build_router(service.clone(), None);
+            tokio::spawn(async move {
+                if let Err(e) =
+                    unix::serve_unix(unix_socket.path, unix_socket.mode, service, app).await
+                {
+                    tracing::error!("Unix socket listener failed: {}", e);
+                }
+            });
+        }
+        #[cfg(not(unix))]
+        tracing::warn!(
+            "Unix sockets are not supported on this platform, ignoring {}",
+            unix_socket.path.display()
+        );
+    }
 
-    let addr = format!("{}:{}", host, port);
-    let listener = tokio::net::TcpListener::bind(&addr).await?;
+    if let Some(tls) = options.tls {
+        let auth_token = auth_token.clone();
+        let service = service.clone();
+        let app = app.clone();
+        tokio::spawn(async move {
+            if let Err(e) = tls::serve_tls(addr.ip(), &tls, auth_token, service, app).await {
+                tracing::error!("TLS listener failed: {:#}", e);
+            }
+        });
+    }
 
     tracing::info!("🌐 Unified server listening on {}", addr);
     tracing::info!("  - HTTP GET / → Web UI");
@@ -84,61 +182,115 @@ async fn serve_http_unified(
         let (tcp_stream, peer_addr) = listener.accept().await?;
         let service = service.clone();
         let app = app.clone();
+        let auth_token = auth_token.clone();
 
         tokio::spawn(async move {
-            // Peek at the first bytes to detect connection type
-            let mut peek_buf = [0u8; 1024];
-            match tcp_stream.peek(&mut peek_buf).await {
-                Ok(n) if n > 0 => {
-                    let peek_str = String::from_utf8_lossy(&peek_buf[..n]);
-
-                    if peek_str.contains("Upgrade: websocket") {
-                        tracing::info!(
-                            "Detected WebSocket upgrade from {}, handling with tokio-tungstenite",
-                            peer_addr
-                        );
-                        handle_websocket_tcp(tcp_stream, service).await;
-                    } else if peek_str.starts_with("GET ")
-                        || peek_str.starts_with("POST ")
-                        || peek_str.starts_with("PUT ")
-                        || peek_str.starts_with("DELETE ")
-                        || peek_str.starts_with("HEAD ")
-                        || peek_str.starts_with("OPTIONS ")
-                    {
-                        // HTTP request - handle with axum
-                        tracing::info!("Detected HTTP request from {}", peer_addr);
-                        let tower_service = app.into_service();
-                        let hyper_service = hyper::service::service_fn(
-                            move |request: hyper::Request<hyper::body::Incoming>| {
-                                tower_service.clone().call(request)
-                            },
-                        );
-
-                        if let Err(e) = hyper::server::conn::http1::Builder::new()
-                            .serve_connection(TokioIo::new(tcp_stream), hyper_service)
-                            .await
-                        {
-                            tracing::error!("HTTP connection error: {}", e);
-                        }
-                    } else {
-                        // Raw binary Rapace protocol (no HTTP)
-                        tracing::info!("Detected raw Rapace binary connection from {}", peer_addr);
-                        handle_rapace_tcp(tcp_stream, service).await;
-                    }
+            // Read the first bytes (the whole head, for HTTP) to detect the
+            // connection type, then replay them
+            let mut tcp_stream = tcp_stream;
+            match read_head(&mut tcp_stream).await {
+                Ok(head) if !head.is_empty() => {
+                    let peer = peer_addr.to_string();
+                    let stream = Rewind::new(tcp_stream, head.clone());
+                    serve_unified_connection(
+                        stream,
+                        &head,
+                        &peer,
+                        auth_token.as_deref(),
+                        service,
+                        app,
+                    )
+                    .await;
                 }
                 _ => {
-                    tracing::warn!("Failed to peek TCP stream from {}", peer_addr);
+                    tracing::warn!("Failed to read TCP stream from {}", peer_addr);
                 }
             }
         });
     }
 }
 
+/// Routes for the web UI and the `Upgrade: rapace` handshake
+fn build_router(service: Arc<HindsightServiceImpl>, auth_token: Option<Arc<str>>) -> Router {
+    Router::new()
+        .route(
+            "/",
+            get({
+                let service = service.clone();
+                move |headers: HeaderMap, req: Request| {
+                    handle_root(headers, req, service.clone(), auth_token.clone())
+                }
+            }),
+        )
+        .route("/pkg/*file", get(serve_pkg_file))
+        .nest_service("/static", tower_http::services::ServeDir::new("static"))
+        .with_state(service)
+}
+
+/// Serve one connection, picking the protocol from its first bytes
+///
+/// `stream` replays `head`, the bytes read to sniff the protocol: the whole
+/// request head for HTTP (see [`read_head`]). With an `auth_token`, rapace
+/// connections must present it.
+pub(crate) async fn serve_unified_connection<S>(
+    stream: S,
+    head: &[u8],
+    peer: &str,
+    auth_token: Option<&str>,
+    service: Arc<HindsightServiceImpl>,
+    app: Router,
+) where
+    S: AsyncRead + AsyncWrite + Unpin + Send + Sync + 'static,
+{
+    let request = RequestHead::parse(head);
+
+    if let Some(request) = request.filter(RequestHead::is_websocket_upgrade) {
+        if let Some(token) = auth_token {
+            if !request.is_authorized(token) {
+                tracing::warn!("Refusing unauthenticated WebSocket upgrade from {}", peer);
+                return;
+            }
+        }
+        tracing::info!(
+            "Detected WebSocket upgrade from {}, handling with tokio-tungstenite",
+            peer
+        );
+        handle_websocket_tcp(stream, service).await;
+    } else if is_http(head) {
+        // HTTP request - handle with axum
+        tracing::info!("Detected HTTP request from {}", peer);
+        let tower_service = app.into_service();
+        let hyper_service = hyper::service::service_fn(
+            move |request: hyper::Request<hyper::body::Incoming>| {
+                tower_service.clone().call(request)
+            },
+        );
+
+        if let Err(e) = hyper::server::conn::http1::Builder::new()
+            .serve_connection(TokioIo::new(stream), hyper_service)
+            .with_upgrades()
+            .await
+        {
+            tracing::error!("HTTP connection error: {}", e);
+        }
+    } else if auth_token.is_some() {
+        tracing::warn!(
+            "Refusing raw Rapace connection from {}: an auth token is required",
+            peer
+        );
+    } else {
+        // Raw binary Rapace protocol (no HTTP)
+        tracing::info!("Detected raw Rapace binary connection from {}", peer);
+        handle_rapace_tcp(stream, service).await;
+    }
+}
+
 /// Handle requests to "/" - detect upgrade type or serve HTML
 async fn handle_root(
     headers: HeaderMap,
     req: Request,
     service: Arc<HindsightServiceImpl>,
+    auth_token: Option<Arc<str>>,
 ) -> Response {
     // Check for Upgrade header
     let upgrade = headers
@@ -156,6 +308,12 @@ async fn handle_root(
             StatusCode::INTERNAL_SERVER_ERROR.into_response()
         }
         Some("rapace") => {
+            if let Some(token) = auth_token {
+                let authorization = headers.get("authorization").and_then(|v| v.to_str().ok());
+                if !is_authorized(authorization, &token) {
+                    return StatusCode::UNAUTHORIZED.into_response();
+                }
+            }
             // Rapace upgrade - manual handling
             handle_rapace_upgrade(req, service).await.into_response()
         }
@@ -168,10 +326,22 @@ async fn handle_root(
 }
 
 /// Handle raw binary Rapace TCP connection (no HTTP)
-async fn handle_rapace_tcp(tcp_stream: tokio::net::TcpStream, service: Arc<HindsightServiceImpl>) {
+async fn handle_rapace_tcp<S>(tcp_stream: S, service: Arc<HindsightServiceImpl>)
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + Sync + 'static,
+{
     tracing::info!("Handling raw Rapace binary connection");
 
     let transport = rapace::Transport::stream(tcp_stream);
+    run_rapace_session(transport, service, "Raw Rapace").await;
+}
+
+/// Serve the Hindsight service over an established transport until it closes
+pub(crate) async fn run_rapace_session(
+    transport: rapace::Transport,
+    service: Arc<HindsightServiceImpl>,
+    label: &str,
+) {
     let session = Arc::new(RpcSession::new(transport));
 
     session.set_dispatcher({
@@ -189,17 +359,17 @@ async fn handle_rapace_tcp(tcp_stream: tokio::net::TcpStream, service: Arc<Hinds
     });
 
     if let Err(e) = session.run().await {
-        tracing::error!("Raw Rapace session error: {}", e);
+        tracing::error!("{} session error: {}", label, e);
     }
 
-    tracing::info!("Raw Rapace connection closed");
+    tracing::info!("{} connection closed", label);
 }
 
 /// Handle WebSocket at TCP level using tokio-tungstenite
-async fn handle_websocket_tcp(
-    tcp_stream: tokio::net::TcpStream,
-    service: Arc<HindsightServiceImpl>,
-) {
+async fn handle_websocket_tcp<S>(tcp_stream: S, service: Arc<HindsightServiceImpl>)
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + Sync + 'static,
+{
     tracing::info!("Accepting WebSocket connection with tokio-tungstenite");
 
     // Let tokio-tungstenite handle the entire WebSocket handshake (including HTTP headers)
@@ -1,4 +1,5 @@
 use clap::{Parser, Subcommand};
+use std::path::PathBuf;
 
 #[derive(Parser)]
 #[command(name = "hindsight")]
@@ -31,6 +32,66 @@ enum Commands {
         /// Load seed data on startup for UI development
         #[arg(long)]
         seed: bool,
+
+        /// Also listen on a Unix socket, at this path or at
+        /// $XDG_RUNTIME_DIR/hindsight/hindsight.sock. Access is controlled by
+        /// the socket's permissions, not --auth-token
+        #[arg(long, num_args = 0..=1, value_name = "PATH")]
+        unix_socket: Option<Option<PathBuf>>,
+
+        /// Permission bits (octal) for the Unix socket; 600 = owner only,
+        /// 660 = owner and group
+        #[arg(long, default_value = "600", value_parser = parse_mode)]
+        unix_socket_mode: u32,
+
+        /// Accept shared-memory sessions from clients on this host
+        /// (rendezvous at $XDG_RUNTIME_DIR/hindsight/shm.sock)
+        #[arg(long)]
+        shm: bool,
+
+        /// Rendezvous socket for shared-memory sessions (implies --shm)
+        #[arg(long)]
+        shm_socket: Option<PathBuf>,
+
+        /// Also accept TLS connections on this port (needs --tls-cert and --tls-key)
+        #[arg(long, requires_all = ["tls_cert", "tls_key"])]
+        tls_port: Option<u16>,
+
+        /// PEM certificate chain for the TLS port
+        #[arg(long)]
+        tls_cert: Option<PathBuf>,
+
+        /// PEM private key for the TLS port
+        #[arg(long)]
+        tls_key: Option<PathBuf>,
+
+        /// Require `Authorization: Bearer <TOKEN>` from network clients
+        /// (browsers: open the web UI with `?access_token=<TOKEN>`)
+        #[arg(long)]
+        auth_token: Option<String>,
+    },
+
+    /// Replay span files recorded offline into a running server
+    Import {
+        /// Span files written by the client's file exporter
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+
+        /// Server address
+        #[arg(long, default_value = "localhost:1990")]
+        addr: String,
+
+        /// Token for a server started with --auth-token
+        #[arg(long)]
+        auth_token: Option<String>,
+
+        /// Connect with TLS (to the server's --tls-port)
+        #[arg(long)]
+        tls: bool,
+
+        /// Also trust the CA certificates in this PEM file (implies --tls)
+        #[arg(long)]
+        tls_ca_file: Option<PathBuf>,
     },
 }
 
@@ -53,14 +114,91 @@ async fn main() -> anyhow::Result<()> {
             host,
             ttl,
             seed,
+            unix_socket,
+            unix_socket_mode,
+            shm,
+            shm_socket,
+            tls_port,
+            tls_cert,
+            tls_key,
+            auth_token,
         } => {
+            let unix_socket = match unix_socket {
+                Some(Some(path)) => Some(path),
+                Some(None) => Some(hindsight_protocol::unix_socket_path().ok_or_else(|| {
+                    anyhow::anyhow!("--unix-socket needs XDG_RUNTIME_DIR, or pass a path")
+                })?),
+                None => None,
+            }
+            .map(|path| hindsight_server::UnixSocket {
+                path,
+                mode: unix_socket_mode,
+            });
+            let shm_socket = match shm_socket {
+                Some(path) => Some(path),
+                None if shm => Some(hindsight_protocol::shm_socket_path().ok_or_else(|| {
+                    anyhow::anyhow!("--shm needs XDG_RUNTIME_DIR, or pass --shm-socket")
+                })?),
+                None => None,
+            };
             eprintln!("🔍 Hindsight server starting...");
             eprintln!("   HTTP/WebSocket: http://{}:{}", if host == "0.0.0.0" { "localhost" } else { &host }, http_port);
             eprintln!("   Rapace TCP: {}:{}", host, tcp_port);
+            let tls = match (tls_port, tls_cert, tls_key) {
+                (Some(port), Some(cert_path), Some(key_path)) => {
+                    Some(hindsight_server::TlsListener {
+                        port,
+                        cert_path,
+                        key_path,
+                    })
+                }
+                _ => None,
+            };
+            if let Some(unix_socket) = &unix_socket {
+                eprintln!("   Unix socket: {}", unix_socket.path.display());
+            }
+            if let Some(shm_socket) = &shm_socket {
+                eprintln!("   Shared memory: {}", shm_socket.display());
+            }
+            if let Some(tls) = &tls {
+                eprintln!("   TLS: {}:{}", host, tls.port);
+            }
+            if auth_token.is_some() {
+                eprintln!("   Auth token: required");
+            }
             if seed {
                 eprintln!("   Seed data: enabled");
             }
-            hindsight_server::run_server(host, http_port, tcp_port, ttl, seed).await
+            let options = hindsight_server::ServerOptions {
+                unix_socket,
+                shm_socket,
+                tls,
+                auth_token,
+            };
+            hindsight_server::run_server(host, http_port, tcp_port, ttl, seed, options).await
+        }
+        Commands::Import {
+            files,
+            addr,
+            auth_token,
+            tls,
+            tls_ca_file,
+        } => {
+            let options = hindsight_server::ImportOptions {
+                auth_token,
+                tls,
+                tls_ca_file,
+            };
+            let accepted = hindsight_server::import_span_files(&addr, &files, &options).await?;
+            eprintln!("📥 Imported {} spans into {}", accepted, addr);
+            Ok(())
         }
     }
 }
+
+fn parse_mode(s: &str) -> Result<u32, String> {
+    u32::from_str_radix(s.trim_start_matches("0o"), 8)
+        .ok()
+        .filter(|mode| *mode <= 0o777)
+        .ok_or_else(|| format!("invalid mode {:?}, expected octal like 600", s))
+}
@@ -0,0 +1,72 @@
+//! Shared-memory endpoint for clients on the same host
+
+use rapace::transport::shm::ShmSession;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::UnixStream;
+
+use crate::run_rapace_session;
+use crate::service_impl::HindsightServiceImpl;
+use crate::socket::bind_socket;
+
+/// Accept shared-memory sessions through the rendezvous socket at `path`
+///
+/// Each client creates its own segment and sends its path here; the server
+/// maps it and serves rapace over it. The socket is only accessible to the
+/// server's user.
+pub(crate) async fn serve_shm(path: PathBuf, service: Arc<HindsightServiceImpl>) {
+    let listener = match bind_socket(&path, 0o600) {
+        Ok(listener) => listener,
+        Err(e) => {
+            tracing::warn!(
+                "Shared-memory endpoint disabled ({}): {}",
+                path.display(),
+                e
+            );
+            return;
+        }
+    };
+    tracing::info!("🧠 Shared-memory rendezvous on {}", path.display());
+
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                tokio::spawn(handle_shm_client(stream, service.clone()));
+            }
+            Err(e) => {
+                tracing::error!("Shared-memory rendezvous accept failed: {}", e);
+                return;
+            }
+        }
+    }
+}
+
+async fn handle_shm_client(stream: UnixStream, service: Arc<HindsightServiceImpl>) {
+    let mut stream = BufReader::new(stream);
+    let mut segment = String::new();
+    if let Err(e) = stream.read_line(&mut segment).await {
+        tracing::warn!("Failed to read shared-memory segment path: {}", e);
+        return;
+    }
+    let segment = PathBuf::from(segment.trim_end());
+
+    let session = match ShmSession::open_file(&segment) {
+        Ok(session) => session,
+        Err(e) => {
+            tracing::warn!(
+                "Failed to map shared-memory segment {}: {:?}",
+                segment.display(),
+                e
+            );
+            let _ = stream.get_mut().write_all(b"error\n").await;
+            return;
+        }
+    };
+    if stream.get_mut().write_all(b"ok\n").await.is_err() {
+        return;
+    }
+
+    tracing::info!("Accepted shared-memory session on {}", segment.display());
+    run_rapace_session(rapace::Transport::shm(session), service, "Shared-memory").await;
+}
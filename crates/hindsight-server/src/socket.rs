@@ -0,0 +1,172 @@
+//! Helpers shared by the Unix socket listeners
+
+use std::io;
+use std::os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+use tokio::net::UnixListener;
+
+/// Bind a listening socket at `path` whose file has permission bits `mode`
+///
+/// Creates the parent directory if needed and replaces a stale socket (see
+/// [`remove_stale_socket`]). The socket is bound in a private staging
+/// directory, given `mode` there, then linked into place, so it is never
+/// reachable at `path` with other permissions. The process umask, shared
+/// with every other thread, is left alone.
+pub(crate) fn bind_socket(path: &Path, mode: u32) -> io::Result<UnixListener> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    create_socket_dir(dir)?;
+    remove_stale_socket(path)?;
+
+    let staging = staging_dir(dir)?;
+    let staged = staging.join("socket");
+    let result = (|| {
+        let listener = UnixListener::bind(&staged)?;
+        std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(mode))?;
+        // Unlike a rename, a link fails rather than replace a socket that
+        // another server bound in the meantime
+        std::fs::hard_link(&staged, path)?;
+        Ok(listener)
+    })();
+    let _ = std::fs::remove_file(&staged);
+    let _ = std::fs::remove_dir(&staging);
+    result
+}
+
+/// Create a new `0o700` directory in `dir` to bind a socket in private
+fn staging_dir(dir: &Path) -> io::Result<PathBuf> {
+    static NEXT: AtomicU32 = AtomicU32::new(0);
+    let staging = dir.join(format!(
+        ".hindsight-bind-{}-{}",
+        std::process::id(),
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    ));
+    std::fs::DirBuilder::new().mode(0o700).create(&staging)?;
+    // The umask can only have narrowed it; make sure we can still use it
+    std::fs::set_permissions(&staging, std::fs::Permissions::from_mode(0o700))?;
+    Ok(staging)
+}
+
+/// Create the directory that will hold a socket, private (`0o700`) if it
+/// doesn't exist yet
+///
+/// An existing directory is left as it is: it may be shared with other
+/// programs, and its owner chose its permissions.
+pub(crate) fn create_socket_dir(dir: &Path) -> io::Result<()> {
+    if dir.as_os_str().is_empty() || dir.exists() {
+        return Ok(());
+    }
+    std::fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(dir)
+}
+
+/// Remove a socket left behind by a server that is no longer running
+///
+/// Refuses to touch anything that isn't a socket, and sockets that another
+/// process is still accepting connections on.
+pub(crate) fn remove_stale_socket(path: &Path) -> io::Result<()> {
+    let metadata = match std::fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    if !metadata.file_type().is_socket() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} exists and is not a socket", path.display()),
+        ));
+    }
+    if std::os::unix::net::UnixStream::connect(path).is_ok() {
+        return Err(io::Error::new(
+            io::ErrorKind::AddrInUse,
+            format!("another server is listening on {}", path.display()),
+        ));
+    }
+    std::fs::remove_file(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("hindsight-socket-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_remove_stale_socket() {
+        let dir = test_dir("stale");
+
+        // Nothing there
+        remove_stale_socket(&dir.join("missing.sock")).unwrap();
+
+        // Regular files are never removed
+        let file = dir.join("file.sock");
+        std::fs::write(&file, "data").unwrap();
+        let err = remove_stale_socket(&file).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        assert!(file.exists());
+
+        // Nor is the socket of a server that is still running
+        let live = dir.join("live.sock");
+        let listener = std::os::unix::net::UnixListener::bind(&live).unwrap();
+        let err = remove_stale_socket(&live).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AddrInUse);
+        assert!(live.exists());
+
+        // Once nothing listens, the socket is stale
+        drop(listener);
+        remove_stale_socket(&live).unwrap();
+        assert!(!live.exists());
+    }
+
+    #[tokio::test]
+    async fn test_bind_socket_mode() {
+        let dir = test_dir("bind");
+
+        let mut listeners = Vec::new();
+        for mode in [0o600, 0o660] {
+            let path = dir.join(format!("{:o}.sock", mode));
+            listeners.push(bind_socket(&path, mode).unwrap());
+            let actual = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(actual & 0o777, mode);
+        }
+
+        // A live socket is left alone
+        let err = bind_socket(&dir.join("600.sock"), 0o600).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AddrInUse);
+
+        // Staging directories are cleaned up
+        let mut names: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["600.sock", "660.sock"]);
+    }
+
+    #[test]
+    fn test_create_socket_dir() {
+        let dir = test_dir("dir");
+
+        let created = dir.join("a/b");
+        create_socket_dir(&created).unwrap();
+        let mode = std::fs::metadata(&created).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o700);
+
+        // Existing directories keep their permissions
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o755)).unwrap();
+        create_socket_dir(&dir).unwrap();
+        let mode = std::fs::metadata(&dir).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o755);
+    }
+}
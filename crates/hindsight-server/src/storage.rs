@@ -50,9 +50,15 @@ impl TraceStore {
         let count = spans.len() as u32;
 
         for span in spans {
-            // Check if this is a new trace
-            let is_new_trace =
-                span.parent_span_id.is_none() && !self.spans.contains_key(&span.span_id);
+            let existing = self.spans.get(&span.span_id).map(|s| s.is_running());
+            // A heartbeat that arrives after the span finished is stale
+            if span.is_running() && existing == Some(false) {
+                continue;
+            }
+
+            // Check if this is a new trace; its first span may be a child of
+            // a span in a process that doesn't report here
+            let is_new_trace = existing.is_none() && !self.traces.contains_key(&span.trace_id);
 
             if is_new_trace {
                 let _ = self.event_tx.send(TraceEvent::TraceStarted {
@@ -62,12 +68,17 @@ impl TraceStore {
                 });
             }
 
-            // Emit span added event
-            let _ = self.event_tx.send(TraceEvent::SpanAdded {
-                trace_id: span.trace_id,
-                span: span.clone(),
-            });
+            // Emit span added event, once when it starts and once when it
+            // finishes; heartbeats in between only refresh the stored copy
+            if existing.is_none() || !span.is_running() {
+                let _ = self.event_tx.send(TraceEvent::SpanAdded {
+                    trace_id: span.trace_id,
+                    span: span.clone(),
+                });
+            }
 
+            // In-flight records and the finished span share the span ID, so
+            // the latest one replaces the others
             self.spans.insert(span.span_id, span.clone());
 
             // Try to build/update trace
@@ -97,7 +108,11 @@ impl TraceStore {
                     }
                 }
 
-                let duration = trace.end_time.map(|e| e.0 - trace.start_time.0);
+                let is_running = trace.is_running();
+                let duration = trace
+                    .end_time
+                    .filter(|_| !is_running)
+                    .map(|e| e.0.saturating_sub(trace.start_time.0));
 
                 if let Some(min_dur) = filter.min_duration_nanos {
                     if duration.is_none_or(|d| d < min_dur) {
@@ -138,6 +153,7 @@ impl TraceStore {
                     duration_nanos: duration,
                     span_count: trace.spans.len(),
                     has_errors,
+                    is_running,
                     trace_type,
                 })
             })
@@ -174,7 +190,10 @@ impl TraceStore {
                     trace.end_time.is_some() && trace.spans.iter().all(|s| s.end_time.is_some());
 
                 if is_complete {
-                    if let Some(duration) = trace.end_time.map(|e| e.0 - trace.start_time.0) {
+                    if let Some(duration) = trace
+                        .end_time
+                        .map(|e| e.0.saturating_sub(trace.start_time.0))
+                    {
                         let _ = self.event_tx.send(TraceEvent::TraceCompleted {
                             trace_id,
                             duration_nanos: duration,
@@ -201,3 +220,114 @@ impl TraceStore {
         });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn span(trace_id: TraceId, span_id: SpanId, end_time: Option<u64>) -> Span {
+        Span {
+            trace_id,
+            span_id,
+            parent_span_id: None,
+            name: "request".to_string(),
+            start_time: Timestamp(1_000),
+            end_time: end_time.map(Timestamp),
+            attributes: BTreeMap::new(),
+            events: Vec::new(),
+            status: SpanStatus::Ok,
+            service_name: "test".to_string(),
+        }
+    }
+
+    /// Events received so far, as (variant, span end time) pairs
+    fn drain(events: &mut broadcast::Receiver<TraceEvent>) -> Vec<(&'static str, Option<u64>)> {
+        std::iter::from_fn(|| events.try_recv().ok())
+            .map(|event| match event {
+                TraceEvent::TraceStarted { .. } => ("started", None),
+                TraceEvent::TraceCompleted { .. } => ("completed", None),
+                TraceEvent::SpanAdded { span, .. } => ("span", span.end_time.map(|t| t.0)),
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_heartbeats_and_events() {
+        let store = TraceStore::new(Duration::from_secs(60));
+        let mut events = store.subscribe_events();
+        let trace_id = TraceId::new();
+        let span_id = SpanId::new();
+
+        // Start, then a heartbeat: only the start is announced
+        store.ingest(vec![span(trace_id, span_id, None)]);
+        store.ingest(vec![span(trace_id, span_id, None)]);
+        assert_eq!(drain(&mut events), [("started", None), ("span", None)]);
+
+        // Finish replaces the running copy
+        store.ingest(vec![span(trace_id, span_id, Some(5_000))]);
+        assert_eq!(
+            drain(&mut events),
+            [("span", Some(5_000)), ("completed", None)]
+        );
+
+        // A heartbeat delayed past the finish is discarded
+        store.ingest(vec![span(trace_id, span_id, None)]);
+        assert!(drain(&mut events).is_empty());
+        let trace = store.get_trace(trace_id).unwrap();
+        assert_eq!(trace.spans.len(), 1);
+        assert_eq!(trace.spans[0].end_time, Some(Timestamp(5_000)));
+    }
+
+    #[tokio::test]
+    async fn test_list_running_traces() {
+        let store = TraceStore::new(Duration::from_secs(60));
+        let trace_id = TraceId::new();
+        let span_id = SpanId::new();
+
+        // Running: no duration, so duration filters leave it out
+        store.ingest(vec![span(trace_id, span_id, None)]);
+        let summaries = store.list_traces(TraceFilter::default());
+        assert_eq!(summaries.len(), 1);
+        assert!(summaries[0].is_running);
+        assert_eq!(summaries[0].duration_nanos, None);
+        let min_duration = TraceFilter {
+            min_duration_nanos: Some(1),
+            ..Default::default()
+        };
+        assert!(store.list_traces(min_duration.clone()).is_empty());
+
+        // Finished
+        store.ingest(vec![span(trace_id, span_id, Some(5_000))]);
+        let summaries = store.list_traces(TraceFilter::default());
+        assert!(!summaries[0].is_running);
+        assert_eq!(summaries[0].duration_nanos, Some(4_000));
+        assert_eq!(store.list_traces(min_duration).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_remote_root_trace() {
+        let store = TraceStore::new(Duration::from_secs(60));
+        let mut events = store.subscribe_events();
+        let trace_id = TraceId::new();
+
+        // The root lives in a process that never reports here
+        let mut first = span(trace_id, SpanId::new(), Some(5_000));
+        first.parent_span_id = Some(SpanId::new());
+        first.name = "child_main".to_string();
+        let mut nested = span(trace_id, SpanId::new(), Some(4_000));
+        nested.parent_span_id = Some(first.span_id);
+        nested.start_time = Timestamp(2_000);
+        store.ingest(vec![first.clone(), nested]);
+
+        let events = drain(&mut events);
+        assert_eq!(events.iter().filter(|(e, _)| *e == "started").count(), 1);
+
+        let trace = store.get_trace(trace_id).unwrap();
+        assert_eq!(trace.root_span_id, first.span_id);
+        let summaries = store.list_traces(TraceFilter::default());
+        assert_eq!(summaries.len(), 1);
+        assert_eq!(summaries[0].root_span_name, "child_main");
+        assert_eq!(summaries[0].span_count, 2);
+    }
+}
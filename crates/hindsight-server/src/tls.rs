@@ -0,0 +1,94 @@
+//! TLS listener speaking the same protocols as the main port
+
+use anyhow::Context;
+use axum::Router;
+use hindsight_protocol::upgrade::Rewind;
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+use std::sync::Arc;
+use tokio_rustls::rustls::{self, crypto};
+use tokio_rustls::TlsAcceptor;
+
+use crate::head::read_head;
+use crate::service_impl::HindsightServiceImpl;
+use crate::{serve_unified_connection, TlsListener};
+
+/// Accept TLS connections on `ip:port` and sniff the protocol inside
+pub(crate) async fn serve_tls(
+    ip: IpAddr,
+    tls: &TlsListener,
+    auth_token: Option<Arc<str>>,
+    service: Arc<HindsightServiceImpl>,
+    app: Router,
+) -> anyhow::Result<()> {
+    let acceptor = load_acceptor(&tls.cert_path, &tls.key_path)?;
+    let addr = SocketAddr::new(ip, tls.port);
+    let listener = tokio::net::TcpListener::bind(&addr).await?;
+    tracing::info!("🔒 TLS listening on {}", addr);
+
+    loop {
+        let (tcp_stream, peer_addr) = listener.accept().await?;
+        let acceptor = acceptor.clone();
+        let auth_token = auth_token.clone();
+        let service = service.clone();
+        let app = app.clone();
+
+        tokio::spawn(async move {
+            let mut stream = match acceptor.accept(tcp_stream).await {
+                Ok(stream) => stream,
+                Err(e) => {
+                    tracing::warn!("TLS handshake with {} failed: {}", peer_addr, e);
+                    return;
+                }
+            };
+
+            // Read the head to pick the protocol, then replay it
+            match read_head(&mut stream).await {
+                Ok(head) if !head.is_empty() => {
+                    let peer = format!("{} (tls)", peer_addr);
+                    let stream = Rewind::new(stream, head.clone());
+                    serve_unified_connection(
+                        stream,
+                        &head,
+                        &peer,
+                        auth_token.as_deref(),
+                        service,
+                        app,
+                    )
+                    .await;
+                }
+                _ => {
+                    tracing::warn!("Failed to read TLS stream from {}", peer_addr);
+                }
+            }
+        });
+    }
+}
+
+/// Build a TLS acceptor from a PEM certificate chain and private key
+fn load_acceptor(cert_path: &Path, key_path: &Path) -> anyhow::Result<TlsAcceptor> {
+    let cert_pem = std::fs::read(cert_path)
+        .with_context(|| format!("failed to read {}", cert_path.display()))?;
+    let certs = rustls_pemfile::certs(&mut &cert_pem[..])
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("invalid certificate in {}", cert_path.display()))?;
+    anyhow::ensure!(
+        !certs.is_empty(),
+        "no certificates found in {}",
+        cert_path.display()
+    );
+
+    let key_pem = std::fs::read(key_path)
+        .with_context(|| format!("failed to read {}", key_path.display()))?;
+    let key = rustls_pemfile::private_key(&mut &key_pem[..])
+        .with_context(|| format!("invalid private key in {}", key_path.display()))?
+        .with_context(|| format!("no private key found in {}", key_path.display()))?;
+
+    let config =
+        rustls::ServerConfig::builder_with_provider(Arc::new(crypto::ring::default_provider()))
+            .with_safe_default_protocol_versions()?
+            .with_no_client_auth()
+            .with_single_cert(certs, key)?;
+
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
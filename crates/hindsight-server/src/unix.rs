@@ -0,0 +1,64 @@
+//! Unix domain socket listener speaking the same protocols as the main port
+
+use axum::Router;
+use hindsight_protocol::upgrade::Rewind;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::net::UnixStream;
+
+use crate::head::read_head;
+use crate::serve_unified_connection;
+use crate::service_impl::HindsightServiceImpl;
+use crate::socket::bind_socket;
+
+/// Accept connections on a Unix socket at `path`
+///
+/// Access is controlled by file permissions: the socket is created with
+/// `mode` (e.g. `0o600` for the server's user only, `0o660` to let its group
+/// in). A missing parent directory is created private (`0o700`). A socket
+/// left behind by a server that has exited is replaced; anything else at
+/// `path` is an error.
+pub(crate) async fn serve_unix(
+    path: PathBuf,
+    mode: u32,
+    service: Arc<HindsightServiceImpl>,
+    app: Router,
+) -> anyhow::Result<()> {
+    let listener = bind_socket(&path, mode)?;
+    tracing::info!(
+        "🔌 Unix socket listening on {} (mode {:o})",
+        path.display(),
+        mode
+    );
+
+    loop {
+        let (mut stream, _) = listener.accept().await?;
+        let service = service.clone();
+        let app = app.clone();
+
+        tokio::spawn(async move {
+            // Read the head to pick the protocol, then replay it
+            match read_head(&mut stream).await {
+                Ok(head) if !head.is_empty() => {
+                    let peer = describe_peer(&stream);
+                    let stream = Rewind::new(stream, head.clone());
+                    // File permissions already decided who may connect
+                    serve_unified_connection(stream, &head, &peer, None, service, app).await;
+                }
+                _ => {
+                    tracing::warn!("Failed to read from Unix socket connection");
+                }
+            }
+        });
+    }
+}
+
+fn describe_peer(stream: &UnixStream) -> String {
+    match stream.peer_cred() {
+        Ok(cred) => match cred.pid() {
+            Some(pid) => format!("unix:uid={},pid={}", cred.uid(), pid),
+            None => format!("unix:uid={}", cred.uid()),
+        },
+        Err(_) => "unix".to_string(),
+    }
+}
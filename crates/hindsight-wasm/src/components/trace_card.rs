@@ -24,7 +24,13 @@ pub fn TraceCard(TraceCardProps { trace, nav_state }: TraceCardProps) -> View {
                 format!("{:.2}s", ms / 1000.0)
             }
         })
-        .unwrap_or_else(|| "—".to_string());
+        .unwrap_or_else(|| {
+            if trace.is_running {
+                "running…".to_string()
+            } else {
+                "—".to_string()
+            }
+        });
 
     let trace_id = trace.trace_id.clone();
     let on_click = move |_| {
@@ -37,7 +43,8 @@ pub fn TraceCard(TraceCardProps { trace, nav_state }: TraceCardProps) -> View {
         div(
             class="trace-item",
             on:click=on_click,
-            data-has-error=trace.has_errors.to_string()
+            data-has-error=trace.has_errors.to_string(),
+            data-running=trace.is_running.to_string()
         ) {
             div(class="trace-name") { (trace.root_span_name.clone()) }
             div(class="trace-duration") { (duration_text) }
@@ -1,95 +1,40 @@
 //! Trace detail view component
 
+use gloo_timers::future::TimeoutFuture;
 use hindsight_protocol::*;
 use rapace::RpcSession;
-use std::collections::HashMap;
+use std::cell::Cell;
+use std::rc::Rc;
 use std::sync::Arc;
 use sycamore::prelude::*;
+use wasm_bindgen_futures::spawn_local;
 
 use crate::navigation::NavigationState;
 use crate::routing::Route;
 
-/// Hierarchical span node for tree rendering
-#[derive(Clone, Debug)]
-struct SpanNode {
-    span: Span,
-    children: Vec<SpanNode>,
-    depth: usize,
-}
-
-impl SpanNode {
-    fn from_trace(trace: &Trace) -> Vec<SpanNode> {
-        let mut span_map: HashMap<SpanId, &Span> = HashMap::new();
-        let mut children_map: HashMap<SpanId, Vec<SpanId>> = HashMap::new();
-        let mut roots = Vec::new();
-
-        // Build maps
-        for span in &trace.spans {
-            span_map.insert(span.span_id, span);
-            if let Some(parent_id) = span.parent_span_id {
-                children_map
-                    .entry(parent_id)
-                    .or_default()
-                    .push(span.span_id);
-            } else {
-                roots.push(span.span_id);
-            }
-        }
-
-        // Build tree recursively
-        fn build_tree(
-            span_id: SpanId,
-            span_map: &HashMap<SpanId, &Span>,
-            children_map: &HashMap<SpanId, Vec<SpanId>>,
-            depth: usize,
-        ) -> SpanNode {
-            let span = span_map.get(&span_id).unwrap();
-            let mut children = Vec::new();
-
-            if let Some(child_ids) = children_map.get(&span_id) {
-                // Sort children by start time
-                let mut sorted_children = child_ids.clone();
-                sorted_children
-                    .sort_by_key(|id| span_map.get(id).map(|s| s.start_time.0).unwrap_or(0));
-
-                for child_id in sorted_children {
-                    children.push(build_tree(child_id, span_map, children_map, depth + 1));
-                }
-            }
-
-            SpanNode {
-                span: (*span).clone(),
-                children,
-                depth,
-            }
-        }
-
-        roots
-            .into_iter()
-            .map(|root_id| build_tree(root_id, &span_map, &children_map, 0))
-            .collect()
-    }
-
-    fn flatten(&self) -> Vec<(Span, usize, bool)> {
-        let mut result = Vec::new();
-        let has_children = !self.children.is_empty();
-        result.push((self.span.clone(), self.depth, has_children));
-        for child in &self.children {
-            result.extend(child.flatten());
-        }
-        result
-    }
-}
+/// How often a trace with running spans is re-fetched
+const REFRESH_INTERVAL_MS: u32 = 1_000;
 
 /// Trace detail view - shows full trace information
+///
+/// While spans are still running, the trace is re-fetched every second and
+/// their bars grow up to the current time.
 #[component]
 pub async fn TraceDetail(props: TraceDetailProps) -> View {
     let nav_state = props.nav_state;
     let trace_id = props.trace_id.clone();
     let session = props.session;
 
+    let current = create_signal(Option::<Trace>::None);
+    let now = create_signal(now_nanos());
+    let alive = Rc::new(Cell::new(true));
+    on_cleanup({
+        let alive = alive.clone();
+        move || alive.set(false)
+    });
+
     // Fetch trace directly with async/await
-    let client = HindsightServiceClient::new(session);
+    let client = HindsightServiceClient::new(session.clone());
     let trace = client
         .get_trace(trace_id.clone()).await
         .expect("Failed to fetch trace")
@@ -107,8 +52,16 @@ pub async fn TraceDetail(props: TraceDetailProps) -> View {
         .map(|s| s.name.clone())
         .unwrap_or_else(|| "Trace Detail".to_string());
 
-    let nodes = SpanNode::from_trace(&trace);
-    let flat_spans: Vec<_> = nodes.iter().flat_map(|n| n.flatten()).collect();
+    if trace.is_running() {
+        spawn_local(follow_running_trace(
+            session,
+            trace_id.clone(),
+            current,
+            now,
+            alive,
+        ));
+    }
+    current.set(Some(trace));
 
     view! {
         div(class="trace-detail") {
@@ -129,43 +82,117 @@ pub async fn TraceDetail(props: TraceDetailProps) -> View {
                     div(class="waterfall-header") {
                         div { "Operation" }
                         div { "Service" }
+                        div { "Timeline" }
                         div { "Duration" }
                     }
-                    (
-                        flat_spans.clone().into_iter().map(|(span, depth, has_children)| {
-                            span_row_view(span, depth, has_children)
-                        }).collect::<Vec<_>>()
-                    )
+                    (View::from_dynamic(move || {
+                        let now = now.get();
+                        current.with(|trace| match trace {
+                            Some(trace) => span_rows_view(trace, now),
+                            None => view! {},
+                        })
+                    }))
                 }
             }
         }
     }
 }
 
+/// Re-fetch the trace until none of its spans are running
+async fn follow_running_trace(
+    session: Arc<RpcSession>,
+    trace_id: TraceId,
+    current: Signal<Option<Trace>>,
+    now: Signal<u64>,
+    alive: Rc<Cell<bool>>,
+) {
+    let client = HindsightServiceClient::new(session);
+    loop {
+        TimeoutFuture::new(REFRESH_INTERVAL_MS).await;
+        let latest = client.get_trace(trace_id.clone()).await;
+        // The view may have gone away while we waited
+        if !alive.get() {
+            return;
+        }
+        now.set(now_nanos());
+        match latest {
+            Ok(Some(trace)) => {
+                let running = trace.is_running();
+                current.set(Some(trace));
+                if !running {
+                    return;
+                }
+            }
+            // Expired from the store
+            Ok(None) => return,
+            Err(e) => tracing::warn!("Failed to refresh trace: {:?}", e),
+        }
+    }
+}
+
+/// Wall-clock time in the same unit as span timestamps
+fn now_nanos() -> u64 {
+    (js_sys::Date::now() * 1_000_000.0) as u64
+}
+
+/// Rows for every span, with bars laid out on the trace's time window
+fn span_rows_view(trace: &Trace, now: u64) -> View {
+    // Running spans extend to now (but never before their own start, in
+    // case the browser's clock is behind the traced service's)
+    let end_of = |span: &Span| {
+        span.end_time
+            .map(|t| t.0)
+            .unwrap_or(now)
+            .max(span.start_time.0)
+    };
+    let window_start = trace
+        .spans
+        .iter()
+        .map(|s| s.start_time.0)
+        .min()
+        .unwrap_or(trace.start_time.0);
+    let window_end = trace.spans.iter().map(end_of).max().unwrap_or(window_start);
+    let window = (window_end.saturating_sub(window_start)).max(1) as f64;
+
+    let nodes = SpanNode::from_trace(trace);
+    let rows: Vec<View> = nodes
+        .iter()
+        .flat_map(|n| n.flatten())
+        .map(|(span, depth, has_children)| {
+            let end = end_of(&span);
+            let left = (span.start_time.0 - window_start) as f64 / window * 100.0;
+            let width = (end - span.start_time.0) as f64 / window * 100.0;
+            let bar_style = format!("left: {:.3}%; width: {:.3}%", left, width);
+            span_row_view(span, depth, has_children, end, bar_style)
+        })
+        .collect();
+
+    view! { (rows) }
+}
+
 /// Create a span row view
-fn span_row_view(span: Span, depth: usize, has_children: bool) -> View {
+fn span_row_view(
+    span: Span,
+    depth: usize,
+    has_children: bool,
+    end: u64,
+    bar_style: String,
+) -> View {
     let is_error = matches!(span.status, SpanStatus::Error { .. });
+    let is_running = span.is_running();
 
-    let duration_text = if let Some(end) = span.end_time {
-        let nanos = end.0.saturating_sub(span.start_time.0);
-        let ms = nanos as f64 / 1_000_000.0;
-        if ms < 1.0 {
-            format!("{:.0}µs", nanos as f64 / 1_000.0)
-        } else if ms < 10.0 {
-            format!("{:.2}ms", ms)
-        } else if ms < 1000.0 {
-            format!("{:.1}ms", ms)
-        } else {
-            format!("{:.2}s", ms / 1000.0)
-        }
+    let elapsed = format_duration(end.saturating_sub(span.start_time.0));
+    let duration_text = if is_running {
+        format!("{}…", elapsed)
     } else {
-        "—".to_string()
+        elapsed
     };
 
     view! {
         div(
             class="span-row",
             data-error=is_error.to_string(),
+            data-running=is_running.to_string(),
             data-has-children=has_children.to_string(),
             style=format!("--depth: {}", depth),
             tabindex="0"
@@ -175,11 +202,27 @@ fn span_row_view(span: Span, depth: usize, has_children: bool) -> View {
                 div(class="span-name") { (span.name.clone()) }
             }
             div(class="span-service") { (span.service_name.clone()) }
+            div(class="span-timeline") {
+                div(class="span-waterfall-bar", style=bar_style) {}
+            }
             div(class="span-duration") { (duration_text) }
         }
     }
 }
 
+fn format_duration(nanos: u64) -> String {
+    let ms = nanos as f64 / 1_000_000.0;
+    if ms < 1.0 {
+        format!("{:.0}µs", nanos as f64 / 1_000.0)
+    } else if ms < 10.0 {
+        format!("{:.2}ms", ms)
+    } else if ms < 1000.0 {
+        format!("{:.1}ms", ms)
+    } else {
+        format!("{:.2}s", ms / 1000.0)
+    }
+}
+
 #[derive(Props)]
 pub struct TraceDetailProps {
     pub trace_id: TraceId,
@@ -268,9 +268,15 @@ async fn init_session() -> Result<Arc<RpcSession>, String> {
         .and_then(|w| w.location().host().ok())
         .unwrap_or_else(|| "localhost:1990".to_string());
 
-    let url = format!("{}//{}/", protocol, host);
+    // Pass the page's query string on, so a server started with
+    // `--auth-token` gets the `access_token` the UI was opened with
+    let search = web_sys::window()
+        .and_then(|w| w.location().search().ok())
+        .unwrap_or_default();
 
-    tracing::info!("Connecting to {}", url);
+    let url = format!("{}//{}/{}", protocol, host, search);
+
+    tracing::info!("Connecting to {}//{}/", protocol, host);
 
     let ws = WebSocketTransport::connect(&url)
         .await
@@ -0,0 +1,106 @@
+use hindsight_protocol::*;
+
+// Helper trait for converting to AttributeValue
+pub trait IntoAttributeValue {
+    fn into_attribute_value(self) -> AttributeValue;
+}
+
+impl IntoAttributeValue for &str {
+    fn into_attribute_value(self) -> AttributeValue {
+        AttributeValue::String(self.to_string())
+    }
+}
+
+impl IntoAttributeValue for String {
+    fn into_attribute_value(self) -> AttributeValue {
+        AttributeValue::String(self)
+    }
+}
+
+impl IntoAttributeValue for i64 {
+    fn into_attribute_value(self) -> AttributeValue {
+        AttributeValue::Int(self)
+    }
+}
+
+impl IntoAttributeValue for i32 {
+    fn into_attribute_value(self) -> AttributeValue {
+        AttributeValue::Int(self as i64)
+    }
+}
+
+impl IntoAttributeValue for &String {
+    fn into_attribute_value(self) -> AttributeValue {
+        AttributeValue::String(self.clone())
+    }
+}
+
+impl IntoAttributeValue for i16 {
+    fn into_attribute_value(self) -> AttributeValue {
+        AttributeValue::Int(self as i64)
+    }
+}
+
+impl IntoAttributeValue for i8 {
+    fn into_attribute_value(self) -> AttributeValue {
+        AttributeValue::Int(self as i64)
+    }
+}
+
+impl IntoAttributeValue for u32 {
+    fn into_attribute_value(self) -> AttributeValue {
+        AttributeValue::Int(self as i64)
+    }
+}
+
+impl IntoAttributeValue for u16 {
+    fn into_attribute_value(self) -> AttributeValue {
+        AttributeValue::Int(self as i64)
+    }
+}
+
+impl IntoAttributeValue for u8 {
+    fn into_attribute_value(self) -> AttributeValue {
+        AttributeValue::Int(self as i64)
+    }
+}
+
+// Values beyond i64::MAX are kept exact as strings rather than wrapped
+impl IntoAttributeValue for u64 {
+    fn into_attribute_value(self) -> AttributeValue {
+        match i64::try_from(self) {
+            Ok(value) => AttributeValue::Int(value),
+            Err(_) => AttributeValue::String(self.to_string()),
+        }
+    }
+}
+
+impl IntoAttributeValue for usize {
+    fn into_attribute_value(self) -> AttributeValue {
+        (self as u64).into_attribute_value()
+    }
+}
+
+impl IntoAttributeValue for bool {
+    fn into_attribute_value(self) -> AttributeValue {
+        AttributeValue::Bool(self)
+    }
+}
+
+impl IntoAttributeValue for f64 {
+    fn into_attribute_value(self) -> AttributeValue {
+        AttributeValue::Float(self)
+    }
+}
+
+impl IntoAttributeValue for f32 {
+    fn into_attribute_value(self) -> AttributeValue {
+        AttributeValue::Float(self as f64)
+    }
+}
+
+impl IntoAttributeValue for AttributeValue {
+    fn into_attribute_value(self) -> AttributeValue {
+        self
+    }
+}
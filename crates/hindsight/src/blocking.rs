@@ -0,0 +1,194 @@
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+use tokio::runtime::Handle;
+use tokio::sync::oneshot;
+
+use crate::builder::TracerBuilder;
+use crate::span_builder::SpanBuilder;
+use crate::tracer::{Tracer, TracerError};
+
+/// How long [`BlockingTracer::flush`] waits for queued spans
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long dropping the last [`BlockingTracer`] waits for queued spans
+const DROP_FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A tracer for programs without an async runtime
+///
+/// Runs its own single-threaded tokio runtime on a background thread, which
+/// owns the connection and the batching loop. Spans use the same
+/// [`SpanBuilder`]/[`ActiveSpan`](crate::ActiveSpan) API as with
+/// [`Tracer`]. Clones share the background thread; it stops when
+/// [`BlockingTracer::shutdown`] is called or the last clone is dropped,
+/// after sending queued spans.
+///
+/// Don't use it from inside an async runtime: [`BlockingTracer::connect`]
+/// blocks the calling thread.
+///
+/// # Example
+/// ```no_run
+/// # fn example() -> Result<(), hindsight::TracerError> {
+/// let tracer = hindsight::BlockingTracer::connect("localhost:1990")?;
+///
+/// let mut span = tracer.span("compile").start();
+/// span.set_attribute("files", 42);
+/// span.end();
+///
+/// tracer.shutdown();
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct BlockingTracer {
+    inner: Arc<BlockingInner>,
+}
+
+struct BlockingInner {
+    tracer: Tracer,
+    worker: Mutex<Option<Worker>>,
+}
+
+/// The background thread and the means to stop it
+struct Worker {
+    shutdown_tx: oneshot::Sender<()>,
+    thread: JoinHandle<()>,
+}
+
+impl BlockingTracer {
+    /// Connect to a Hindsight server via HTTP upgrade
+    pub fn connect(addr: impl AsRef<str>) -> Result<Self, TracerError> {
+        TracerBuilder::new()
+            .endpoint(addr.as_ref())
+            .connect_blocking()
+    }
+
+    /// Connect using `HINDSIGHT_*` environment variables (see [`TracerBuilder`])
+    pub fn from_env() -> Result<Self, TracerError> {
+        TracerBuilder::new().with_env()?.connect_blocking()
+    }
+
+    pub(crate) fn start(builder: TracerBuilder) -> Result<Self, TracerError> {
+        if cfg!(feature = "noop") {
+            return Ok(Self::without_worker(Tracer::noop()));
+        }
+
+        let (handle_tx, handle_rx) = std::sync::mpsc::channel();
+        let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+        let thread = std::thread::Builder::new()
+            .name("hindsight".to_string())
+            .spawn(move || {
+                let runtime = match tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                {
+                    Ok(runtime) => runtime,
+                    Err(e) => {
+                        let _ = handle_tx.send(Err(e));
+                        return;
+                    }
+                };
+                let _ = handle_tx.send(Ok(runtime.handle().clone()));
+                // Drives every task of the tracer until shutdown
+                runtime.block_on(async {
+                    let _ = shutdown_rx.await;
+                });
+            })
+            .map_err(|e| runtime_error(&e))?;
+
+        let handle: Handle = match handle_rx.recv() {
+            Ok(Ok(handle)) => handle,
+            Ok(Err(e)) => return Err(runtime_error(&e)),
+            Err(e) => return Err(runtime_error(&e)),
+        };
+
+        // The background thread is inside `Runtime::block_on`, so IO and
+        // timers make progress while this thread waits
+        let worker = Worker {
+            shutdown_tx,
+            thread,
+        };
+        match handle.block_on(builder.connect()) {
+            Ok(tracer) => Ok(Self {
+                inner: Arc::new(BlockingInner {
+                    tracer,
+                    worker: Mutex::new(Some(worker)),
+                }),
+            }),
+            Err(e) => {
+                worker.stop();
+                Err(e)
+            }
+        }
+    }
+
+    fn without_worker(tracer: Tracer) -> Self {
+        Self {
+            inner: Arc::new(BlockingInner {
+                tracer,
+                worker: Mutex::new(None),
+            }),
+        }
+    }
+
+    /// Start building a new span
+    pub fn span(&self, name: impl Into<String>) -> SpanBuilder {
+        self.inner.tracer.span(name)
+    }
+
+    /// The underlying tracer, e.g. for [`Tracer::install_global`] or
+    /// [`Tracer::stats`]
+    pub fn tracer(&self) -> &Tracer {
+        &self.inner.tracer
+    }
+
+    /// Send every queued span now, blocking until it's done or ten seconds
+    /// have passed
+    ///
+    /// Returns false if some spans may not have been sent yet.
+    pub fn flush(&self) -> bool {
+        self.flush_with_timeout(FLUSH_TIMEOUT)
+    }
+
+    /// [`BlockingTracer::flush`] with a custom timeout
+    pub fn flush_with_timeout(&self, timeout: Duration) -> bool {
+        self.inner.tracer.flush_blocking(timeout)
+    }
+
+    /// Send queued spans, then stop the background thread
+    ///
+    /// Affects every clone; spans ended afterwards are dropped.
+    pub fn shutdown(self) {
+        self.flush();
+        self.inner.stop();
+    }
+}
+
+impl BlockingInner {
+    fn stop(&self) {
+        let worker = self.worker.lock().unwrap_or_else(|e| e.into_inner()).take();
+        if let Some(worker) = worker {
+            worker.stop();
+        }
+    }
+}
+
+impl Drop for BlockingInner {
+    fn drop(&mut self) {
+        if self.worker.get_mut().map_or(true, |w| w.is_some()) {
+            self.tracer.flush_blocking(DROP_FLUSH_TIMEOUT);
+            self.stop();
+        }
+    }
+}
+
+impl Worker {
+    fn stop(self) {
+        let _ = self.shutdown_tx.send(());
+        let _ = self.thread.join();
+    }
+}
+
+fn runtime_error(e: &dyn std::fmt::Display) -> TracerError {
+    TracerError::ConnectionFailed(format!("failed to start the tracer runtime: {}", e))
+}
@@ -0,0 +1,897 @@
+use hindsight_protocol::*;
+#[cfg(feature = "client")]
+use rapace::Transport;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+#[cfg(all(feature = "client", not(target_arch = "wasm32")))]
+use tokio::io::{AsyncRead, AsyncWrite};
+#[cfg(all(feature = "client", not(target_arch = "wasm32")))]
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+
+#[cfg(all(feature = "client", not(target_arch = "wasm32")))]
+use crate::blocking::BlockingTracer;
+#[cfg(feature = "client")]
+use crate::exporter::{Exporter, Exporters};
+#[cfg(all(feature = "client", not(target_arch = "wasm32")))]
+use crate::file_exporter::FileExporter;
+use crate::processor::{SpanProcessor, SpanProcessors};
+use crate::sampler::Sampler;
+use crate::span_builder::IntoAttributeValue;
+use crate::stats::{DropRateCallback, TracerStats};
+use crate::testing::InMemorySpans;
+#[cfg(feature = "tls")]
+use crate::tls::TlsConfig;
+use crate::tracer::{Tracer, TracerError};
+#[cfg(all(feature = "client", not(target_arch = "wasm32")))]
+use hindsight_protocol::upgrade::http_upgrade;
+
+/// Default server address for network transports
+#[cfg(feature = "client")]
+const DEFAULT_ENDPOINT: &str = "localhost:1990";
+
+/// How the tracer reaches the Hindsight server
+///
+/// In browsers (`wasm32`) only [`TransportKind::WebSocket`] is available,
+/// and it is the default.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TransportKind {
+    /// HTTP `Upgrade: rapace` on the server's main port
+    #[cfg_attr(not(target_arch = "wasm32"), default)]
+    HttpUpgrade,
+    /// Raw rapace over TCP
+    Tcp,
+    /// Rapace over WebSocket (`endpoint` is `host:port`; in browsers it may
+    /// also be a `ws://` or `wss://` URL)
+    #[cfg_attr(target_arch = "wasm32", default)]
+    WebSocket,
+    /// Raw rapace over a Unix domain socket (`endpoint` is the socket path,
+    /// `$XDG_RUNTIME_DIR/hindsight/hindsight.sock` by default)
+    Unix,
+    /// Rapace over shared memory with a server on the same host (`endpoint`
+    /// is the rendezvous socket, discovered under `$XDG_RUNTIME_DIR` by
+    /// default)
+    Shm,
+    /// No server: append spans to a local file (`endpoint` is the file path)
+    File,
+}
+
+impl FromStr for TransportKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "http" | "http-upgrade" => Ok(TransportKind::HttpUpgrade),
+            "tcp" => Ok(TransportKind::Tcp),
+            "websocket" | "ws" => Ok(TransportKind::WebSocket),
+            "unix" => Ok(TransportKind::Unix),
+            "shm" => Ok(TransportKind::Shm),
+            "file" => Ok(TransportKind::File),
+            other => Err(format!(
+                "invalid transport {:?}, expected http, tcp, websocket, unix, shm or file",
+                other
+            )),
+        }
+    }
+}
+
+/// Settings the tracer runs with once connected
+#[derive(Clone, Debug)]
+#[cfg_attr(not(feature = "client"), allow(dead_code))]
+pub(crate) struct TracerConfig {
+    pub service_name: String,
+    pub resource_attributes: BTreeMap<String, AttributeValue>,
+    pub batch_size: usize,
+    pub flush_interval: Duration,
+    pub queue_capacity: usize,
+    pub sampler: Sampler,
+    pub heartbeat_interval: Option<Duration>,
+    pub on_drop_rate: Option<DropRateCallback>,
+    pub processors: SpanProcessors,
+    #[cfg(feature = "client")]
+    pub exporters: Exporters,
+    /// Parent of spans started without one (see [`TracerBuilder::parent_context`])
+    pub parent: Option<TraceContext>,
+}
+
+/// Builder for a [`Tracer`] with non-default settings
+///
+/// Every setting can also come from a `HINDSIGHT_*` environment variable
+/// via [`TracerBuilder::with_env`]:
+///
+/// | Variable                          | Setting                                      |
+/// |-----------------------------------|----------------------------------------------|
+/// | `HINDSIGHT_ENDPOINT`              | server address, or socket/file path          |
+/// | `HINDSIGHT_TRANSPORT`             | `http`/`tcp`/`websocket`/`unix`/`shm`/`file` |
+/// | `HINDSIGHT_SERVICE_NAME`          | service name                                 |
+/// | `HINDSIGHT_RESOURCE_ATTRIBUTES`   | `key=value,key=value` added to all spans     |
+/// | `HINDSIGHT_BATCH_SIZE`            | spans per batch                              |
+/// | `HINDSIGHT_FLUSH_INTERVAL_MS`     | max time between batches                     |
+/// | `HINDSIGHT_QUEUE_CAPACITY`        | spans buffered before dropping               |
+/// | `HINDSIGHT_SAMPLER`               | `always_on`, `always_off`, `ratio:0.25`      |
+/// | `HINDSIGHT_HEARTBEAT_INTERVAL_MS` | report open spans this often (0 = off)       |
+/// | `HINDSIGHT_AUTH_TOKEN`            | sent as `Authorization: Bearer <token>`      |
+/// | `HINDSIGHT_TLS`                   | `true` to use TLS (`tls` feature)            |
+/// | `HINDSIGHT_TLS_CA_FILE`           | extra CA certificates (PEM), implies TLS     |
+/// | `HINDSIGHT_TLS_PINNED_CERT_FILE`  | trust only this certificate, implies TLS     |
+///
+/// # Example
+/// ```no_run
+/// # use hindsight::{Sampler, Tracer};
+/// # async fn example() -> Result<(), hindsight::TracerError> {
+/// let tracer = Tracer::builder()
+///     .service_name("dodeca")
+///     .resource_attribute("deployment.environment", "dev")
+///     .sampler(Sampler::Ratio(0.1))
+///     .with_env()? // HINDSIGHT_* variables win over the above
+///     .connect()
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+#[cfg_attr(not(feature = "client"), allow(dead_code))]
+pub struct TracerBuilder {
+    endpoint: Option<String>,
+    transport: TransportKind,
+    service_name: Option<String>,
+    resource_attributes: BTreeMap<String, AttributeValue>,
+    batch_size: usize,
+    flush_interval: Duration,
+    queue_capacity: usize,
+    sampler: Sampler,
+    heartbeat_interval: Option<Duration>,
+    on_drop_rate: Option<DropRateCallback>,
+    processors: SpanProcessors,
+    #[cfg(feature = "client")]
+    exporters: Exporters,
+    parent: Option<TraceContext>,
+    inherit_env_parent: bool,
+    authorization: Option<String>,
+    #[cfg(feature = "tls")]
+    tls: Option<TlsConfig>,
+}
+
+// By hand, so the credentials in `authorization` never end up in logs
+impl fmt::Debug for TracerBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("TracerBuilder");
+        debug
+            .field("endpoint", &self.endpoint)
+            .field("transport", &self.transport)
+            .field("service_name", &self.service_name)
+            .field("resource_attributes", &self.resource_attributes)
+            .field("batch_size", &self.batch_size)
+            .field("flush_interval", &self.flush_interval)
+            .field("queue_capacity", &self.queue_capacity)
+            .field("sampler", &self.sampler)
+            .field("heartbeat_interval", &self.heartbeat_interval)
+            .field("on_drop_rate", &self.on_drop_rate)
+            .field("processors", &self.processors)
+            .field("parent", &self.parent)
+            .field("inherit_env_parent", &self.inherit_env_parent)
+            .field(
+                "authorization",
+                &self.authorization.as_ref().map(|_| "[REDACTED]"),
+            );
+        #[cfg(feature = "client")]
+        debug.field("exporters", &self.exporters);
+        #[cfg(feature = "tls")]
+        debug.field("tls", &self.tls);
+        debug.finish()
+    }
+}
+
+impl Default for TracerBuilder {
+    fn default() -> Self {
+        Self {
+            endpoint: None,
+            transport: TransportKind::default(),
+            service_name: None,
+            resource_attributes: BTreeMap::new(),
+            batch_size: 100,
+            flush_interval: Duration::from_millis(100),
+            queue_capacity: 10_000,
+            sampler: Sampler::default(),
+            heartbeat_interval: None,
+            on_drop_rate: None,
+            processors: SpanProcessors::default(),
+            #[cfg(feature = "client")]
+            exporters: Exporters::default(),
+            parent: None,
+            inherit_env_parent: true,
+            authorization: None,
+            #[cfg(feature = "tls")]
+            tls: None,
+        }
+    }
+}
+
+impl TracerBuilder {
+    /// Builder with default settings
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Server address (`host:port`), socket path for [`TransportKind::Unix`],
+    /// or file path for [`TransportKind::File`]
+    pub fn endpoint(mut self, endpoint: impl Into<String>) -> Self {
+        self.endpoint = Some(endpoint.into());
+        self
+    }
+
+    /// How to reach the server (defaults to HTTP upgrade)
+    pub fn transport(mut self, transport: TransportKind) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    /// Service name attached to every span
+    pub fn service_name(mut self, service_name: impl Into<String>) -> Self {
+        self.service_name = Some(service_name.into());
+        self
+    }
+
+    /// Attribute added to every span (unless the span sets it itself)
+    pub fn resource_attribute(
+        mut self,
+        key: impl Into<String>,
+        value: impl IntoAttributeValue,
+    ) -> Self {
+        self.resource_attributes
+            .insert(key.into(), value.into_attribute_value());
+        self
+    }
+
+    /// Maximum number of spans sent in one batch (default 100)
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Maximum time a finished span waits before being sent (default 100ms)
+    pub fn flush_interval(mut self, flush_interval: Duration) -> Self {
+        self.flush_interval = flush_interval;
+        self
+    }
+
+    /// Number of finished spans buffered before new ones are dropped
+    /// (default 10,000)
+    pub fn queue_capacity(mut self, queue_capacity: usize) -> Self {
+        self.queue_capacity = queue_capacity.max(1);
+        self
+    }
+
+    /// Which new traces to record (default: all)
+    ///
+    /// Connecting fails with [`TracerError::InvalidConfig`] if `sampler` is
+    /// a [`Sampler::Ratio`] that is NaN or outside 0.0 to 1.0.
+    pub fn sampler(mut self, sampler: Sampler) -> Self {
+        self.sampler = sampler;
+        self
+    }
+
+    /// Report spans while they run, not only once they end (default: off)
+    ///
+    /// Each span is sent when it starts and again every `interval` until it
+    /// ends, so long-running or stuck operations show up on the server. The
+    /// in-flight records carry the span as it was started; attributes and
+    /// events added later arrive with the finished span, which replaces
+    /// them. Costs one extra span per started span plus the heartbeats.
+    pub fn heartbeat_interval(mut self, interval: Duration) -> Self {
+        self.heartbeat_interval = Some(interval);
+        self
+    }
+
+    /// Call `callback` when too many spans are being dropped
+    ///
+    /// The drop rate (dropped spans over finished spans) is measured every
+    /// 10 seconds; `callback` runs on the tracer's background task when it
+    /// reaches `threshold` (0 to 1), and again only after it has dropped
+    /// back below. See also [`Tracer::stats`].
+    ///
+    /// # Example
+    /// ```no_run
+    /// # async fn example() -> Result<(), hindsight::TracerError> {
+    /// let tracer = hindsight::Tracer::builder()
+    ///     .on_drop_rate(0.05, |rate, stats| {
+    ///         eprintln!(
+    ///             "hindsight: dropping {:.0}% of spans ({:?})",
+    ///             rate * 100.0,
+    ///             stats.connection
+    ///         );
+    ///     })
+    ///     .connect()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn on_drop_rate(
+        mut self,
+        threshold: f64,
+        callback: impl Fn(f64, &TracerStats) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_drop_rate = Some(DropRateCallback {
+            threshold,
+            callback: Arc::new(callback),
+        });
+        self
+    }
+
+    /// Run `processor` on every span before it is exported
+    ///
+    /// Processors run in the order they are added. See [`SpanProcessor`]
+    /// and the built-in ones: [`FilterProcessor`](crate::FilterProcessor),
+    /// [`EnrichProcessor`](crate::EnrichProcessor),
+    /// [`RedactProcessor`](crate::RedactProcessor) and
+    /// [`AttributeLimitProcessor`](crate::AttributeLimitProcessor).
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use hindsight::{AttributeLimitProcessor, FilterProcessor, RedactProcessor};
+    /// # async fn example() -> Result<(), hindsight::TracerError> {
+    /// let tracer = hindsight::Tracer::builder()
+    ///     .processor(FilterProcessor::new(|span| span.name != "GET /health"))
+    ///     .processor(RedactProcessor::new(["*token*", "*email*"]))
+    ///     .processor(AttributeLimitProcessor::new().max_value_len(4096))
+    ///     .connect()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn processor(mut self, processor: impl SpanProcessor) -> Self {
+        self.processors.push(processor);
+        self
+    }
+
+    /// Also send spans to `exporter`
+    ///
+    /// Spans go to the server (or file) the tracer is started with, and to
+    /// every exporter added here, each with its own batching and retries
+    /// (see [`Exporter`]). [`TracerBuilder::build_exporters`] starts a tracer
+    /// with these exporters only.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # async fn example() -> Result<(), hindsight::TracerError> {
+    /// // Keep a copy of every span in CI artifacts
+    /// let tracer = hindsight::Tracer::builder()
+    ///     .exporter(hindsight::FileExporter::new("target/hindsight/spans.jsonl"))
+    ///     .connect()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "client")]
+    pub fn exporter(mut self, exporter: impl Exporter) -> Self {
+        self.exporters.push(exporter);
+        self
+    }
+
+    /// Parent of every span started without one, instead of starting a new
+    /// trace
+    ///
+    /// Defaults to the context in the `TRACEPARENT`, `TRACESTATE` and
+    /// `BAGGAGE` environment variables when a process spawned with
+    /// [`process::CommandExt`](crate::process::CommandExt) set them, so it
+    /// joins its parent's trace. To adopt a `TRACEPARENT` from elsewhere,
+    /// pass [`process::extract_env`](crate::process::extract_env) here.
+    /// Spans started with [`SpanBuilder::root`](crate::SpanBuilder::root)
+    /// still start new traces.
+    pub fn parent_context(mut self, context: TraceContext) -> Self {
+        self.parent = Some(context);
+        self
+    }
+
+    /// Whether to read the parent context from `TRACEPARENT` when
+    /// [`HINDSIGHT_PARENT`](crate::process::HINDSIGHT_PARENT) marks it as
+    /// set by [`process::CommandExt`](crate::process::CommandExt) (default
+    /// true)
+    ///
+    /// In-memory tracers never read it, so tests behave the same wherever
+    /// they run.
+    pub fn inherit_env_parent(mut self, inherit: bool) -> Self {
+        self.inherit_env_parent = inherit;
+        self
+    }
+
+    /// `Authorization` header sent with the HTTP upgrade and WebSocket
+    /// handshakes (in browsers, only bearer tokens, see
+    /// [`TracerBuilder::connect`])
+    pub fn authorization(mut self, authorization: impl Into<String>) -> Self {
+        self.authorization = Some(authorization.into());
+        self
+    }
+
+    /// Authenticate with `Authorization: Bearer <token>` (see the server's
+    /// `--auth-token`)
+    pub fn bearer_token(self, token: impl AsRef<str>) -> Self {
+        self.authorization(format!("Bearer {}", token.as_ref()))
+    }
+
+    /// Encrypt network transports with TLS
+    #[cfg(feature = "tls")]
+    pub fn tls(mut self, tls: TlsConfig) -> Self {
+        self.tls = Some(tls);
+        self
+    }
+
+    /// Override settings from `HINDSIGHT_*` environment variables
+    ///
+    /// Variables that are unset are ignored; variables that are set but
+    /// invalid are an error.
+    pub fn with_env(self) -> Result<Self, TracerError> {
+        self.with_env_from(&process_env)
+    }
+
+    /// [`TracerBuilder::with_env`], reading variables through `lookup`
+    fn with_env_from(mut self, lookup: Lookup<'_>) -> Result<Self, TracerError> {
+        if let Some(endpoint) = env_var(lookup, "HINDSIGHT_ENDPOINT") {
+            self.endpoint = Some(endpoint);
+        }
+        if let Some(transport) = env_var(lookup, "HINDSIGHT_TRANSPORT") {
+            self.transport = transport.parse().map_err(TracerError::InvalidConfig)?;
+        }
+        if let Some(service_name) = env_var(lookup, "HINDSIGHT_SERVICE_NAME") {
+            self.service_name = Some(service_name);
+        }
+        if let Some(attributes) = env_var(lookup, "HINDSIGHT_RESOURCE_ATTRIBUTES") {
+            for pair in attributes.split(',').filter(|pair| !pair.trim().is_empty()) {
+                let (key, value) = pair.split_once('=').ok_or_else(|| {
+                    TracerError::InvalidConfig(format!(
+                        "invalid HINDSIGHT_RESOURCE_ATTRIBUTES entry {:?}, expected key=value",
+                        pair
+                    ))
+                })?;
+                self.resource_attributes.insert(
+                    key.trim().to_string(),
+                    AttributeValue::String(value.trim().to_string()),
+                );
+            }
+        }
+        if let Some(batch_size) = env_parse::<usize>(lookup, "HINDSIGHT_BATCH_SIZE")? {
+            self = self.batch_size(batch_size);
+        }
+        if let Some(millis) = env_parse::<u64>(lookup, "HINDSIGHT_FLUSH_INTERVAL_MS")? {
+            self.flush_interval = Duration::from_millis(millis);
+        }
+        if let Some(capacity) = env_parse::<usize>(lookup, "HINDSIGHT_QUEUE_CAPACITY")? {
+            self = self.queue_capacity(capacity);
+        }
+        if let Some(sampler) = env_var(lookup, "HINDSIGHT_SAMPLER") {
+            self.sampler = sampler.parse().map_err(TracerError::InvalidConfig)?;
+        }
+        if let Some(millis) = env_parse::<u64>(lookup, "HINDSIGHT_HEARTBEAT_INTERVAL_MS")? {
+            self.heartbeat_interval = (millis > 0).then(|| Duration::from_millis(millis));
+        }
+        if let Some(token) = env_var(lookup, "HINDSIGHT_AUTH_TOKEN") {
+            self = self.bearer_token(token);
+        }
+        #[cfg(feature = "tls")]
+        {
+            if env_parse::<bool>(lookup, "HINDSIGHT_TLS")? == Some(true) {
+                self.tls.get_or_insert_with(TlsConfig::new);
+            }
+            if let Some(path) = env_var(lookup, "HINDSIGHT_TLS_CA_FILE") {
+                let tls = self.tls.take().unwrap_or_default();
+                self.tls = Some(tls.add_ca_pem_file(path)?);
+            }
+            if let Some(path) = env_var(lookup, "HINDSIGHT_TLS_PINNED_CERT_FILE") {
+                let tls = self.tls.take().unwrap_or_default();
+                self.tls = Some(tls.pin_cert_pem_file(path)?);
+            }
+        }
+        Ok(self)
+    }
+
+    /// Connect to the configured endpoint and start the tracer
+    #[cfg(all(feature = "client", not(target_arch = "wasm32")))]
+    pub async fn connect(self) -> Result<Tracer, TracerError> {
+        if cfg!(feature = "noop") {
+            return Ok(Tracer::noop());
+        }
+        self.validate()?;
+
+        let endpoint = match (&self.endpoint, self.transport) {
+            (Some(endpoint), _) => endpoint.clone(),
+            (None, TransportKind::Unix) => unix_socket_path()
+                .ok_or_else(|| {
+                    TracerError::InvalidConfig(
+                        "unix transport needs XDG_RUNTIME_DIR or an endpoint (socket path)"
+                            .to_string(),
+                    )
+                })?
+                .to_string_lossy()
+                .into_owned(),
+            (None, TransportKind::Shm) => shm_socket_path()
+                .ok_or_else(|| {
+                    TracerError::InvalidConfig(
+                        "shm transport needs XDG_RUNTIME_DIR or an endpoint (rendezvous socket)"
+                            .to_string(),
+                    )
+                })?
+                .to_string_lossy()
+                .into_owned(),
+            (None, TransportKind::File) => {
+                return Err(TracerError::InvalidConfig(
+                    "file transport needs an endpoint (file path)".to_string(),
+                ))
+            }
+            (None, _) => DEFAULT_ENDPOINT.to_string(),
+        };
+
+        let transport = match self.transport {
+            TransportKind::HttpUpgrade | TransportKind::Tcp | TransportKind::WebSocket => {
+                let stream = tokio::net::TcpStream::connect(&endpoint)
+                    .await
+                    .map_err(|e| {
+                        TracerError::ConnectionFailed(format!(
+                            "Failed to connect to {}: {}",
+                            endpoint, e
+                        ))
+                    })?;
+                let authorization = self.authorization.as_deref();
+                #[cfg(feature = "tls")]
+                if let Some(tls) = &self.tls {
+                    let stream = tls.connect(&endpoint, stream).await?;
+                    let transport =
+                        network_transport(self.transport, stream, &endpoint, authorization).await?;
+                    return self.build(transport).await;
+                }
+                network_transport(self.transport, stream, &endpoint, authorization).await?
+            }
+            #[cfg(unix)]
+            TransportKind::Unix => {
+                let stream = tokio::net::UnixStream::connect(&endpoint)
+                    .await
+                    .map_err(|e| {
+                        TracerError::ConnectionFailed(format!(
+                            "Failed to connect to {}: {}",
+                            endpoint, e
+                        ))
+                    })?;
+                Transport::stream(stream)
+            }
+            #[cfg(not(unix))]
+            TransportKind::Unix => {
+                return Err(TracerError::InvalidConfig(
+                    "unix sockets are not supported on this platform".to_string(),
+                ))
+            }
+            #[cfg(unix)]
+            TransportKind::Shm => crate::tracer::shm_connect(&endpoint).await?,
+            #[cfg(not(unix))]
+            TransportKind::Shm => {
+                return Err(TracerError::InvalidConfig(
+                    "shared memory is not supported on this platform".to_string(),
+                ))
+            }
+            TransportKind::File => return Ok(self.build_file(FileExporter::new(endpoint))),
+        };
+
+        self.build(transport).await
+    }
+
+    /// Connect to the configured endpoint and start the tracer
+    ///
+    /// Browsers can only reach the server over WebSocket, and can't set an
+    /// `Authorization` header on it: a bearer token travels in the URL's
+    /// `access_token` query parameter instead.
+    #[cfg(all(feature = "client", target_arch = "wasm32"))]
+    pub async fn connect(self) -> Result<Tracer, TracerError> {
+        if cfg!(feature = "noop") {
+            return Ok(Tracer::noop());
+        }
+        self.validate()?;
+        if self.transport != TransportKind::WebSocket {
+            return Err(TracerError::InvalidConfig(format!(
+                "the {:?} transport is not available in the browser, use WebSocket",
+                self.transport
+            )));
+        }
+        let endpoint = self.endpoint.as_deref().unwrap_or(DEFAULT_ENDPOINT);
+        let base_url = if endpoint.starts_with("ws://") || endpoint.starts_with("wss://") {
+            endpoint.to_string()
+        } else {
+            format!("ws://{}/", endpoint)
+        };
+        // Errors name `base_url`, without the token
+        let mut url = base_url.clone();
+        if let Some(authorization) = &self.authorization {
+            let token = authorization.strip_prefix("Bearer ").ok_or_else(|| {
+                TracerError::InvalidConfig(
+                    "browsers can only send a bearer token over WebSocket".to_string(),
+                )
+            })?;
+            url.push(if url.contains('?') { '&' } else { '?' });
+            url.push_str("access_token=");
+            url.push_str(&percent_encode(token));
+        }
+        let ws = rapace::WebSocketTransport::connect(&url)
+            .await
+            .map_err(|e| {
+                TracerError::ConnectionFailed(format!("Failed to connect to {}: {:?}", base_url, e))
+            })?;
+
+        self.build(Transport::WebSocket(ws)).await
+    }
+
+    /// Start the tracer on an already-established transport
+    #[cfg(feature = "client")]
+    pub async fn build(self, transport: Transport) -> Result<Tracer, TracerError> {
+        if cfg!(feature = "noop") {
+            return Ok(Tracer::noop());
+        }
+        self.validate()?;
+        Tracer::start(transport, self.into_config()).await
+    }
+
+    /// Start the tracer writing spans to a local file, for offline use
+    ///
+    /// Import the file into a server later with `hindsight import`. Must be
+    /// called from within a Tokio runtime.
+    #[cfg(all(feature = "client", not(target_arch = "wasm32")))]
+    pub fn build_file(self, exporter: FileExporter) -> Tracer {
+        if cfg!(feature = "noop") {
+            return Tracer::noop();
+        }
+        Tracer::start_file(exporter, self.into_config())
+    }
+
+    /// Start the tracer sending spans only to the exporters added with
+    /// [`TracerBuilder::exporter`], without a server
+    ///
+    /// Must be called from within a Tokio runtime (natively).
+    #[cfg(feature = "client")]
+    pub fn build_exporters(self) -> Tracer {
+        if cfg!(feature = "noop") {
+            return Tracer::noop();
+        }
+        Tracer::with_exporters(self.into_config(), None)
+    }
+
+    /// Start a tracer recording finished spans in memory, for tests
+    ///
+    /// Like [`Tracer::in_memory`], with this builder's service name, resource
+    /// attributes, sampler and processors.
+    pub fn build_in_memory(self) -> (Tracer, InMemorySpans) {
+        Tracer::start_in_memory(self.inherit_env_parent(false).into_config())
+    }
+
+    /// Connect like [`TracerBuilder::connect`], for programs without an
+    /// async runtime
+    ///
+    /// The tracer runs on a background thread with its own runtime (see
+    /// [`BlockingTracer`]). Don't call it from inside an async runtime.
+    #[cfg(all(feature = "client", not(target_arch = "wasm32")))]
+    pub fn connect_blocking(self) -> Result<BlockingTracer, TracerError> {
+        BlockingTracer::start(self)
+    }
+
+    /// Check settings whose setters can't fail
+    fn validate(&self) -> Result<(), TracerError> {
+        self.sampler.validate().map_err(TracerError::InvalidConfig)
+    }
+
+    pub(crate) fn into_config(self) -> TracerConfig {
+        // Without an explicit name, fall back to the environment like `Tracer::new` always has
+        let service_name = self
+            .service_name
+            .or_else(|| env_var(&process_env, "HINDSIGHT_SERVICE_NAME"))
+            .unwrap_or_else(|| "unknown".to_string());
+
+        TracerConfig {
+            service_name,
+            resource_attributes: self.resource_attributes,
+            batch_size: self.batch_size,
+            // `tokio::time::interval` panics on a zero period
+            flush_interval: self.flush_interval.max(Duration::from_millis(1)),
+            queue_capacity: self.queue_capacity,
+            sampler: self.sampler,
+            heartbeat_interval: self
+                .heartbeat_interval
+                .map(|interval| interval.max(Duration::from_millis(1))),
+            on_drop_rate: self.on_drop_rate,
+            processors: self.processors,
+            #[cfg(feature = "client")]
+            exporters: self.exporters,
+            parent: self.parent.or_else(|| {
+                #[cfg(not(target_arch = "wasm32"))]
+                if self.inherit_env_parent {
+                    return crate::process::extract_inherited_env();
+                }
+                None
+            }),
+        }
+    }
+}
+
+/// Finish connecting one of the TCP-based transports over `stream`
+#[cfg(all(feature = "client", not(target_arch = "wasm32")))]
+async fn network_transport<S>(
+    kind: TransportKind,
+    stream: S,
+    endpoint: &str,
+    authorization: Option<&str>,
+) -> Result<Transport, TracerError>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + Sync + 'static,
+{
+    match kind {
+        TransportKind::HttpUpgrade => Ok(Transport::stream(
+            http_upgrade(stream, endpoint, authorization)
+                .await
+                .map_err(|e| TracerError::ConnectionFailed(e.to_string()))?,
+        )),
+        TransportKind::Tcp => Ok(Transport::stream(stream)),
+        TransportKind::WebSocket => {
+            let mut request = format!("ws://{}/", endpoint)
+                .into_client_request()
+                .map_err(|e| TracerError::InvalidConfig(format!("invalid endpoint: {}", e)))?;
+            if let Some(authorization) = authorization {
+                let value = authorization.parse().map_err(|_| {
+                    TracerError::InvalidConfig("invalid authorization header".to_string())
+                })?;
+                request.headers_mut().insert("authorization", value);
+            }
+            let (ws_stream, _) = tokio_tungstenite::client_async(request, stream)
+                .await
+                .map_err(|e| {
+                    TracerError::ConnectionFailed(format!("WebSocket handshake failed: {}", e))
+                })?;
+            Ok(Transport::websocket(ws_stream))
+        }
+        other => unreachable!("{:?} is not a TCP-based transport", other),
+    }
+}
+
+/// Escape everything but unreserved characters, for a URL query value
+#[cfg(all(feature = "client", target_arch = "wasm32"))]
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// Reads an environment variable
+type Lookup<'a> = &'a dyn Fn(&str) -> Option<String>;
+
+fn process_env(name: &str) -> Option<String> {
+    std::env::var(name).ok()
+}
+
+fn env_var(lookup: Lookup<'_>, name: &str) -> Option<String> {
+    lookup(name).filter(|value| !value.trim().is_empty())
+}
+
+fn env_parse<T: FromStr>(lookup: Lookup<'_>, name: &str) -> Result<Option<T>, TracerError> {
+    match env_var(lookup, name) {
+        Some(value) => value
+            .trim()
+            .parse()
+            .map(Some)
+            .map_err(|_| TracerError::InvalidConfig(format!("invalid {}: {:?}", name, value))),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn with_vars(vars: &[(&str, &str)]) -> Result<TracerBuilder, TracerError> {
+        let vars: HashMap<&str, &str> = vars.iter().copied().collect();
+        TracerBuilder::new()
+            .service_name("app")
+            .with_env_from(&|name| vars.get(name).map(|value| value.to_string()))
+    }
+
+    #[test]
+    fn test_with_env() {
+        let builder = with_vars(&[
+            ("HINDSIGHT_ENDPOINT", "collector:1990"),
+            ("HINDSIGHT_TRANSPORT", "WebSocket"),
+            ("HINDSIGHT_SERVICE_NAME", "from-env"),
+            ("HINDSIGHT_RESOURCE_ATTRIBUTES", "region = eu, host=a,"),
+            ("HINDSIGHT_BATCH_SIZE", "0"),
+            ("HINDSIGHT_FLUSH_INTERVAL_MS", " 250 "),
+            ("HINDSIGHT_QUEUE_CAPACITY", "500"),
+            ("HINDSIGHT_SAMPLER", "ratio:0.5"),
+            ("HINDSIGHT_HEARTBEAT_INTERVAL_MS", "1000"),
+            ("HINDSIGHT_AUTH_TOKEN", "secret"),
+        ])
+        .unwrap();
+        assert_eq!(builder.endpoint.as_deref(), Some("collector:1990"));
+        assert_eq!(builder.transport, TransportKind::WebSocket);
+        assert_eq!(builder.service_name.as_deref(), Some("from-env"));
+        assert_eq!(
+            builder.resource_attributes.get("region"),
+            Some(&AttributeValue::String("eu".into()))
+        );
+        assert_eq!(builder.resource_attributes.len(), 2);
+        assert_eq!(builder.batch_size, 1);
+        assert_eq!(builder.flush_interval, Duration::from_millis(250));
+        assert_eq!(builder.queue_capacity, 500);
+        assert_eq!(builder.sampler, Sampler::Ratio(0.5));
+        assert_eq!(builder.heartbeat_interval, Some(Duration::from_secs(1)));
+        assert_eq!(builder.authorization.as_deref(), Some("Bearer secret"));
+    }
+
+    /// Unset and empty variables leave settings alone
+    #[test]
+    fn test_with_env_ignores_unset() {
+        let builder = with_vars(&[("HINDSIGHT_ENDPOINT", " ")]).unwrap();
+        assert_eq!(builder.endpoint, None);
+        assert_eq!(builder.service_name.as_deref(), Some("app"));
+        assert_eq!(builder.sampler, Sampler::AlwaysOn);
+
+        let builder = with_vars(&[("HINDSIGHT_HEARTBEAT_INTERVAL_MS", "0")]).unwrap();
+        assert_eq!(builder.heartbeat_interval, None);
+    }
+
+    #[test]
+    fn test_with_env_rejects_invalid() {
+        for invalid in [
+            ("HINDSIGHT_TRANSPORT", "carrier-pigeon"),
+            ("HINDSIGHT_RESOURCE_ATTRIBUTES", "region"),
+            ("HINDSIGHT_BATCH_SIZE", "lots"),
+            ("HINDSIGHT_FLUSH_INTERVAL_MS", "-1"),
+            ("HINDSIGHT_SAMPLER", "ratio:2"),
+        ] {
+            assert!(
+                matches!(with_vars(&[invalid]), Err(TracerError::InvalidConfig(_))),
+                "{:?} accepted",
+                invalid
+            );
+        }
+    }
+
+    #[cfg(feature = "tls")]
+    #[test]
+    fn test_with_env_tls() {
+        let builder = with_vars(&[("HINDSIGHT_TLS", "true")]).unwrap();
+        assert!(builder.tls.is_some());
+
+        let builder = with_vars(&[("HINDSIGHT_TLS", "false")]).unwrap();
+        assert!(builder.tls.is_none());
+
+        let missing = ("HINDSIGHT_TLS_CA_FILE", "/nonexistent/ca.pem");
+        assert!(with_vars(&[missing]).is_err());
+    }
+
+    #[test]
+    fn test_debug_redacts_authorization() {
+        let builder = TracerBuilder::new().bearer_token("hunter2");
+        let debug = format!("{:?}", builder);
+        assert!(!debug.contains("hunter2"), "{}", debug);
+        assert!(debug.contains("[REDACTED]"));
+    }
+
+    /// An invalid ratio fails to connect rather than panicking in the setter
+    #[cfg(all(feature = "client", not(feature = "noop"), not(target_arch = "wasm32")))]
+    #[tokio::test]
+    async fn test_connect_rejects_invalid_sampler() {
+        for ratio in [f64::NAN, 1.5, -0.1] {
+            // Rejected before connecting, so the endpoint is never dialled
+            let result = TracerBuilder::new()
+                .endpoint("127.0.0.1:1")
+                .sampler(Sampler::Ratio(ratio))
+                .connect()
+                .await;
+            assert!(
+                matches!(result, Err(TracerError::InvalidConfig(ref e)) if e.contains("invalid sampler ratio")),
+                "ratio {} accepted",
+                ratio
+            );
+        }
+    }
+}
@@ -0,0 +1,372 @@
+use hindsight_protocol::*;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io::{IsTerminal, Write as _};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::exporter::{ExportFuture, Exporter};
+
+/// How long a trace without a local root waits for more spans before it is
+/// printed anyway
+const IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Longest attribute value printed before it is cut short
+const MAX_VALUE_CHARS: usize = 40;
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const DIM: &str = "\x1b[2m";
+const RED: &str = "\x1b[31m";
+const YELLOW: &str = "\x1b[33m";
+const CYAN: &str = "\x1b[36m";
+
+/// Prints each trace to stderr as an indented tree, for local development
+///
+/// Spans are buffered per trace; the tree (the same one the web UI shows)
+/// is printed when the root span ends, with durations, errors and
+/// attributes. Traces whose root lives in another process are printed after
+/// 30 seconds without new spans, or when the tracer shuts down.
+///
+/// Colours are used when stderr is a terminal and `NO_COLOR` is unset.
+///
+/// # Example
+/// ```no_run
+/// # async fn example() -> Result<(), hindsight::TracerError> {
+/// use hindsight::{ConsoleExporter, Tracer};
+///
+/// let tracer = Tracer::builder()
+///     .exporter(ConsoleExporter::new().attribute_keys(["http.method", "user_id"]))
+///     .connect()
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct ConsoleExporter {
+    colors: bool,
+    attribute_keys: Option<Vec<String>>,
+    traces: Mutex<HashMap<TraceId, PendingTrace>>,
+}
+
+struct PendingTrace {
+    spans: Vec<Span>,
+    /// Nanoseconds since the UNIX epoch (`Instant` panics in browsers)
+    last_update: u64,
+}
+
+impl ConsoleExporter {
+    /// Exporter printing every attribute
+    pub fn new() -> Self {
+        Self {
+            colors: std::io::stderr().is_terminal() && std::env::var_os("NO_COLOR").is_none(),
+            attribute_keys: None,
+            traces: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Print only these attributes (in this order)
+    pub fn attribute_keys(mut self, keys: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.attribute_keys = Some(keys.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Force colours on or off
+    pub fn colors(mut self, colors: bool) -> Self {
+        self.colors = colors;
+        self
+    }
+
+    /// Buffer `spans` and return the traces that are complete
+    fn collect(&self, spans: Vec<Span>, now: Timestamp) -> Vec<Vec<Span>> {
+        let now = now.0;
+        let mut traces = self.traces.lock().unwrap_or_else(|e| e.into_inner());
+        let mut complete = Vec::new();
+        for span in spans {
+            // In-flight records are replaced by the finished span later
+            if span.is_running() {
+                continue;
+            }
+            let is_root = span.parent_span_id.is_none();
+            let trace_id = span.trace_id;
+            let pending = traces.entry(trace_id).or_insert_with(|| PendingTrace {
+                spans: Vec::new(),
+                last_update: now,
+            });
+            pending.spans.push(span);
+            pending.last_update = now;
+            if is_root {
+                if let Some(pending) = traces.remove(&trace_id) {
+                    complete.push(pending.spans);
+                }
+            }
+        }
+
+        drop(traces);
+        complete.extend(self.take_idle(Timestamp(now)));
+        complete
+    }
+
+    /// Remove and return the traces without new spans for [`IDLE_TIMEOUT`]
+    fn take_idle(&self, now: Timestamp) -> Vec<Vec<Span>> {
+        let timeout = IDLE_TIMEOUT.as_nanos() as u64;
+        let mut traces = self.traces.lock().unwrap_or_else(|e| e.into_inner());
+        let idle: Vec<TraceId> = traces
+            .iter()
+            .filter(|(_, pending)| now.0.saturating_sub(pending.last_update) >= timeout)
+            .map(|(trace_id, _)| *trace_id)
+            .collect();
+        idle.into_iter()
+            .filter_map(|trace_id| traces.remove(&trace_id))
+            .map(|pending| pending.spans)
+            .collect()
+    }
+
+    fn print(&self, spans: &[Span]) {
+        let output = self.render(spans);
+        // One write, so concurrent output doesn't interleave with the tree
+        let _ = std::io::stderr().lock().write_all(output.as_bytes());
+    }
+
+    /// Render one trace as a tree
+    fn render(&self, spans: &[Span]) -> String {
+        let mut out = String::new();
+        for root in SpanNode::from_spans(spans) {
+            let _ = writeln!(
+                out,
+                "{}trace {}{}",
+                self.paint(DIM),
+                root.span.trace_id,
+                self.paint(RESET)
+            );
+            self.render_node(&mut out, &root, "", "", None);
+        }
+        out
+    }
+
+    fn render_node(
+        &self,
+        out: &mut String,
+        node: &SpanNode,
+        prefix: &str,
+        child_prefix: &str,
+        parent_service: Option<&str>,
+    ) {
+        let span = &node.span;
+        let _ = write!(
+            out,
+            "{}{}{}{}{}{} {}{}{}",
+            self.paint(DIM),
+            prefix,
+            self.paint(RESET),
+            self.paint(BOLD),
+            span.name,
+            self.paint(RESET),
+            self.paint(CYAN),
+            format_duration(span.duration_nanos().unwrap_or(0)),
+            self.paint(RESET),
+        );
+        if parent_service != Some(span.service_name.as_str()) && !span.service_name.is_empty() {
+            let _ = write!(
+                out,
+                " {}[{}]{}",
+                self.paint(YELLOW),
+                span.service_name,
+                self.paint(RESET)
+            );
+        }
+        if let SpanStatus::Error { message } = &span.status {
+            let _ = write!(
+                out,
+                " {}✗ {}{}",
+                self.paint(RED),
+                message,
+                self.paint(RESET)
+            );
+        }
+        let attributes = self.format_attributes(&span.attributes);
+        if !attributes.is_empty() {
+            let _ = write!(
+                out,
+                " {}{}{}",
+                self.paint(DIM),
+                attributes,
+                self.paint(RESET)
+            );
+        }
+        out.push('\n');
+
+        let last = node.children.len().saturating_sub(1);
+        for (index, child) in node.children.iter().enumerate() {
+            let (branch, indent) = if index == last {
+                ("└─ ", "   ")
+            } else {
+                ("├─ ", "│  ")
+            };
+            self.render_node(
+                out,
+                child,
+                &format!("{}{}", child_prefix, branch),
+                &format!("{}{}", child_prefix, indent),
+                Some(&span.service_name),
+            );
+        }
+    }
+
+    fn format_attributes(
+        &self,
+        attributes: &std::collections::BTreeMap<String, AttributeValue>,
+    ) -> String {
+        let pairs: Vec<(&String, &AttributeValue)> = match &self.attribute_keys {
+            Some(keys) => keys
+                .iter()
+                .filter_map(|key| attributes.get_key_value(key))
+                .collect(),
+            None => attributes.iter().collect(),
+        };
+        pairs
+            .into_iter()
+            .map(|(key, value)| format!("{}={}", key, format_value(value)))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn paint(&self, code: &'static str) -> &'static str {
+        if self.colors {
+            code
+        } else {
+            ""
+        }
+    }
+}
+
+impl Default for ConsoleExporter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Exporter for ConsoleExporter {
+    fn export(&self, spans: Vec<Span>) -> ExportFuture<'_> {
+        for trace in self.collect(spans, Timestamp::now()) {
+            self.print(&trace);
+        }
+        Box::pin(async { Ok(()) })
+    }
+
+    /// Print traces that went idle, even when no new spans arrive
+    fn tick(&self) {
+        for trace in self.take_idle(Timestamp::now()) {
+            self.print(&trace);
+        }
+    }
+}
+
+impl Drop for ConsoleExporter {
+    /// Print whatever is still buffered when the tracer shuts down
+    fn drop(&mut self) {
+        let traces = std::mem::take(self.traces.get_mut().unwrap_or_else(|e| e.into_inner()));
+        for pending in traces.into_values() {
+            self.print(&pending.spans);
+        }
+    }
+}
+
+fn format_value(value: &AttributeValue) -> String {
+    match value {
+        AttributeValue::String(s) if s.chars().count() > MAX_VALUE_CHARS => {
+            let cut: String = s.chars().take(MAX_VALUE_CHARS).collect();
+            format!("{:?}", format!("{}…", cut))
+        }
+        AttributeValue::String(s) => format!("{:?}", s),
+        AttributeValue::Int(i) => i.to_string(),
+        AttributeValue::Float(f) => f.to_string(),
+        AttributeValue::Bool(b) => b.to_string(),
+    }
+}
+
+fn format_duration(nanos: u64) -> String {
+    let ms = nanos as f64 / 1_000_000.0;
+    if ms < 1.0 {
+        format!("{:.0}µs", nanos as f64 / 1_000.0)
+    } else if ms < 10.0 {
+        format!("{:.2}ms", ms)
+    } else if ms < 1000.0 {
+        format!("{:.1}ms", ms)
+    } else {
+        format!("{:.2}s", ms / 1000.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(name: &str, trace_id: TraceId, parent: Option<&Span>, millis: u64) -> Span {
+        Span {
+            trace_id,
+            span_id: SpanId::new(),
+            parent_span_id: parent.map(|parent| parent.span_id),
+            name: name.to_string(),
+            start_time: Timestamp(0),
+            end_time: Some(Timestamp(millis * 1_000_000)),
+            attributes: Default::default(),
+            events: Vec::new(),
+            status: SpanStatus::Ok,
+            service_name: "api".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_prints_tree_when_root_ends() {
+        let exporter = ConsoleExporter::new()
+            .colors(false)
+            .attribute_keys(["user_id"]);
+        let trace_id = TraceId::new();
+        let root = span("GET /users", trace_id, None, 20);
+        let mut load = span("load_user", trace_id, Some(&root), 5);
+        load.attributes
+            .insert("user_id".into(), AttributeValue::Int(42));
+        load.attributes
+            .insert("ignored".into(), AttributeValue::Bool(true));
+        let mut query = span("query", trace_id, Some(&load), 3);
+        query.status = SpanStatus::Error {
+            message: "timeout".into(),
+        };
+        let render = span("render", trace_id, Some(&root), 2);
+
+        // Children end first; nothing is printed until the root does
+        let children = vec![query, load, render];
+        assert!(exporter.collect(children, Timestamp(0)).is_empty());
+        let complete = exporter.collect(vec![root], Timestamp(0));
+        assert_eq!(complete.len(), 1);
+
+        let expected = format!(
+            "trace {}\n\
+             GET /users 20.0ms [api]\n\
+             ├─ load_user 5.00ms user_id=42\n\
+             │  └─ query 3.00ms ✗ timeout\n\
+             └─ render 2.00ms\n",
+            trace_id
+        );
+        assert_eq!(exporter.render(&complete[0]), expected);
+    }
+
+    #[test]
+    fn test_prints_idle_trace_without_new_spans() {
+        let exporter = ConsoleExporter::new().colors(false);
+        let trace_id = TraceId::new();
+        let remote_parent = span("remote", trace_id, None, 0);
+        let local = span("handle", trace_id, Some(&remote_parent), 1);
+
+        let start = Timestamp::now();
+        assert!(exporter.collect(vec![local], start).is_empty());
+        assert!(exporter.take_idle(start).is_empty());
+
+        // The next tick after the timeout prints it, with no batch arriving
+        let later = Timestamp(start.0 + IDLE_TIMEOUT.as_nanos() as u64);
+        let idle = exporter.take_idle(later);
+        assert_eq!(idle.len(), 1);
+        assert_eq!(idle[0][0].name, "handle");
+        assert!(exporter.take_idle(later).is_empty());
+    }
+}
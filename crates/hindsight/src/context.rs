@@ -0,0 +1,170 @@
+use hindsight_protocol::*;
+use pin_project_lite::pin_project;
+use std::cell::{Cell, RefCell};
+use std::future::Future;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+thread_local! {
+    /// Context of the span currently executing on this thread
+    ///
+    /// `Instrumented` installs its context for the duration of each poll, so
+    /// this behaves like a task-local for any future driven through it.
+    static CURRENT: RefCell<Option<TraceContext>> = const { RefCell::new(None) };
+
+    /// Set while polling hindsight's own export machinery
+    static UNTRACED: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Get the trace context of the current span, if any
+///
+/// `SpanBuilder::start` uses this as the default parent.
+pub fn current_context() -> Option<TraceContext> {
+    CURRENT.with(|current| current.borrow().clone())
+}
+
+pub(crate) fn replace_current(context: Option<TraceContext>) -> Option<TraceContext> {
+    CURRENT.with(|current| current.replace(context))
+}
+
+/// Context of a no-op span with no parent
+///
+/// All-zero IDs and not sampled, so it costs no randomness and nothing
+/// downstream records it.
+pub(crate) fn noop_context() -> TraceContext {
+    TraceContext {
+        trace_id: TraceId([0; 16]),
+        span_id: SpanId([0; 8]),
+        parent_span_id: None,
+        flags: 0,
+        tracestate: None,
+        baggage: None,
+    }
+}
+
+/// Guard that makes a span current until dropped
+///
+/// Created by `ActiveSpan::enter` or [`enter_context`]. Dropping it restores
+/// whatever context was current before.
+#[must_use = "the context is only current while the guard is alive"]
+pub struct ContextGuard {
+    previous: Option<TraceContext>,
+    // Guards must be dropped on the thread that created them
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Drop for ContextGuard {
+    fn drop(&mut self) {
+        replace_current(self.previous.take());
+    }
+}
+
+/// Make `context` the current span context until the guard is dropped
+///
+/// Prefer [`Instrument::instrument`] for async code: a guard held across an
+/// `.await` leaks its context into whatever else runs on the thread.
+pub fn enter_context(context: TraceContext) -> ContextGuard {
+    ContextGuard {
+        previous: replace_current(Some(context)),
+        _not_send: PhantomData,
+    }
+}
+
+pin_project! {
+    /// Future that runs with a span context installed as current
+    ///
+    /// Created by [`Instrument::instrument`] or [`Instrument::in_current_context`].
+    pub struct Instrumented<F> {
+        #[pin]
+        inner: F,
+        context: Option<TraceContext>,
+    }
+}
+
+impl<F: Future> Future for Instrumented<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let _guard = ContextGuard {
+            previous: replace_current(this.context.clone()),
+            _not_send: PhantomData,
+        };
+        this.inner.poll(cx)
+    }
+}
+
+/// Extension trait for attaching a span context to a future
+pub trait Instrument: Sized {
+    /// Run this future with `context` as the current span context
+    fn instrument(self, context: TraceContext) -> Instrumented<Self> {
+        Instrumented {
+            inner: self,
+            context: Some(context),
+        }
+    }
+
+    /// Run this future with whatever span context is current right now
+    ///
+    /// Useful when handing a future to another task or executor.
+    fn in_current_context(self) -> Instrumented<Self> {
+        Instrumented {
+            inner: self,
+            context: current_context(),
+        }
+    }
+}
+
+impl<F: Future> Instrument for F {}
+
+/// Spawn a tokio task that inherits the current span context
+///
+/// Spans started inside the task default to the caller's current span as
+/// their parent, exactly as if the future had been awaited in place.
+#[cfg(all(feature = "client", not(target_arch = "wasm32")))]
+pub fn spawn<F>(future: F) -> tokio::task::JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    tokio::spawn(future.in_current_context())
+}
+
+/// Whether the caller is running inside hindsight's own export machinery
+///
+/// Integrations that turn other instrumentation into spans (such as the
+/// `tracing` layer) must check this, or exporting a span would produce more
+/// spans to export.
+pub fn is_untraced() -> bool {
+    UNTRACED.with(Cell::get)
+}
+
+pin_project! {
+    /// Future that is never traced
+    ///
+    /// Created by [`untraced`].
+    pub struct Untraced<F> {
+        #[pin]
+        inner: F,
+    }
+}
+
+impl<F: Future> Future for Untraced<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let previous = UNTRACED.with(|untraced| untraced.replace(true));
+        let output = this.inner.poll(cx);
+        UNTRACED.with(|untraced| untraced.set(previous));
+        output
+    }
+}
+
+/// Mark a future as part of hindsight's export machinery
+///
+/// While it is polled, [`is_untraced`] returns true.
+pub fn untraced<F: Future>(future: F) -> Untraced<F> {
+    Untraced { inner: future }
+}
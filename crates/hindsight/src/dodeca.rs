@@ -0,0 +1,173 @@
+//! Spans for dodeca's build pipeline.
+
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use crate::context::Instrument;
+use crate::span_builder::{ActiveSpan, SpanBuilder};
+use crate::tracer::Tracer;
+
+/// Hook for dodeca that turns every rebuild into one trace
+///
+/// Each rebuild gets a root span tagged `dodeca.build` (so the trace is
+/// classified as `TraceType::Dodeca`) and numbered with
+/// `dodeca.build_number`, so successive builds can be compared. Pipeline
+/// steps are child spans tagged with `dodeca.step`.
+///
+/// # Example
+/// ```no_run
+/// # async fn example(tracer: hindsight::Tracer) {
+/// use hindsight::dodeca::DodecaTracing;
+///
+/// let dodeca = DodecaTracing::new(tracer);
+///
+/// let build = dodeca.start_build(Some("content/index.md"));
+/// build
+///     .markdown_parse("content/index.md")
+///     .run(async { /* parse */ })
+///     .await;
+/// build
+///     .template_render("index.html", "page.html")
+///     .run(async { /* render */ })
+///     .await;
+/// build.finish();
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct DodecaTracing {
+    tracer: Tracer,
+    builds: Arc<AtomicU64>,
+}
+
+impl DodecaTracing {
+    /// Report build spans to `tracer`
+    pub fn new(tracer: Tracer) -> Self {
+        Self {
+            tracer,
+            builds: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Start the root span for a rebuild
+    ///
+    /// `trigger` is the changed file that caused the rebuild, if known.
+    pub fn start_build(&self, trigger: Option<&str>) -> BuildSpan {
+        let build_number = self.builds.fetch_add(1, Ordering::Relaxed) + 1;
+        let mut builder = self
+            .tracer
+            .span("dodeca build")
+            .root()
+            .with_attribute("dodeca.build", true)
+            .with_attribute("dodeca.build_number", build_number);
+        if let Some(trigger) = trigger {
+            builder = builder.with_attribute("dodeca.trigger", trigger);
+        }
+        BuildSpan {
+            span: builder.start(),
+            tracer: self.tracer.clone(),
+        }
+    }
+}
+
+/// Root span of one rebuild
+pub struct BuildSpan {
+    span: ActiveSpan,
+    tracer: Tracer,
+}
+
+impl BuildSpan {
+    fn step(&self, name: String, step: &str) -> SpanBuilder {
+        self.tracer
+            .span(name)
+            .with_parent(self.span.context().clone())
+            .with_attribute("dodeca.step", step)
+    }
+
+    /// Span for parsing one markdown source file
+    pub fn markdown_parse(&self, source: &str) -> StepSpan {
+        StepSpan {
+            span: self
+                .step(format!("parse {}", source), "markdown_parse")
+                .with_attribute("dodeca.source", source)
+                .start(),
+        }
+    }
+
+    /// Span for optimizing one image
+    pub fn image_optimization(&self, image: &str) -> StepSpan {
+        StepSpan {
+            span: self
+                .step(format!("optimize {}", image), "image_optimization")
+                .with_attribute("dodeca.image", image)
+                .start(),
+        }
+    }
+
+    /// Span for rendering `page` with `template`
+    pub fn template_render(&self, page: &str, template: &str) -> StepSpan {
+        StepSpan {
+            span: self
+                .step(format!("render {}", page), "template_render")
+                .with_attribute("dodeca.page", page)
+                .with_attribute("dodeca.template", template)
+                .start(),
+        }
+    }
+
+    /// Span for a call into a plugin
+    pub fn plugin_call(&self, plugin: &str, method: &str) -> StepSpan {
+        StepSpan {
+            span: self
+                .step(format!("{}.{}", plugin, method), "plugin_call")
+                .with_attribute("dodeca.plugin", plugin)
+                .with_attribute("dodeca.plugin_method", method)
+                .start(),
+        }
+    }
+
+    /// Run a future with the build as the current span
+    pub async fn run<F: Future>(&self, future: F) -> F::Output {
+        future.instrument(self.span.context().clone()).await
+    }
+
+    /// Mark the build as failed
+    pub fn set_error(&mut self, message: impl Into<String>) {
+        self.span.set_error(message);
+    }
+
+    /// End the build span
+    pub fn finish(self) {
+        self.span.end();
+    }
+
+    /// End the build span, recording how many pages were written
+    pub fn finish_with_pages(mut self, page_count: usize) {
+        self.span.set_attribute("dodeca.page_count", page_count);
+        self.span.end();
+    }
+}
+
+/// Span for one step of a rebuild
+pub struct StepSpan {
+    span: ActiveSpan,
+}
+
+impl StepSpan {
+    /// Run the step's work inside this span, then end it
+    pub async fn run<F: Future>(self, future: F) -> F::Output {
+        let output = future.instrument(self.span.context().clone()).await;
+        self.span.end();
+        output
+    }
+
+    /// Mark the step as failed
+    pub fn set_error(&mut self, message: impl Into<String>) {
+        self.span.set_error(message);
+    }
+
+    /// End the step span (for steps not driven through [`StepSpan::run`])
+    pub fn end(self) {
+        self.span.end();
+    }
+}
@@ -0,0 +1,325 @@
+use hindsight_protocol::*;
+use rapace::RpcSession;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::mpsc::SyncSender;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use crate::builder::TracerConfig;
+use crate::context::untraced;
+use crate::runtime::{self, Interval, MaybeSend, MaybeSync};
+use crate::stats::{ConnectionState, ExporterCounters, StatsCounters};
+
+/// Destination for batches of finished spans
+///
+/// Besides the Hindsight server a tracer connects to, add exporters with
+/// [`TracerBuilder::exporter`](crate::TracerBuilder::exporter); every span
+/// is fanned out to all of them. Each exporter has its own queue and
+/// batching task, so a slow or failing one neither holds up nor loses spans
+/// for the others. A batch that fails is retried a few times with backoff
+/// before its spans are counted as dropped.
+///
+/// # Example
+/// ```no_run
+/// use hindsight::{ExportFuture, Exporter, Span};
+///
+/// struct StderrExporter;
+///
+/// impl Exporter for StderrExporter {
+///     fn export(&self, spans: Vec<Span>) -> ExportFuture<'_> {
+///         Box::pin(async move {
+///             for span in spans {
+///                 eprintln!("{} {:?}", span.name, span.duration_nanos());
+///             }
+///             Ok(())
+///         })
+///     }
+/// }
+///
+/// # async fn example() -> Result<(), hindsight::TracerError> {
+/// let tracer = hindsight::Tracer::builder()
+///     .exporter(StderrExporter)
+///     .connect()
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+pub trait Exporter: MaybeSend + MaybeSync + 'static {
+    /// Send one batch; on error the same batch may be passed again
+    fn export(&self, spans: Vec<Span>) -> ExportFuture<'_>;
+
+    /// Called every flush interval, with or without spans to send, for
+    /// exporters that hold spans back themselves (default: nothing)
+    fn tick(&self) {}
+
+    /// Name in [`TracerStats::exporters`](crate::TracerStats::exporters)
+    /// and error messages (default: the type's name)
+    fn name(&self) -> String {
+        let name = std::any::type_name::<Self>();
+        let path = name.split('<').next().unwrap_or(name);
+        path.rsplit("::").next().unwrap_or(path).to_string()
+    }
+}
+
+/// Future returned by [`Exporter::export`]
+#[cfg(not(target_arch = "wasm32"))]
+pub type ExportFuture<'a> = Pin<Box<dyn Future<Output = Result<(), ExportError>> + Send + 'a>>;
+
+/// Future returned by [`Exporter::export`]
+#[cfg(target_arch = "wasm32")]
+pub type ExportFuture<'a> = Pin<Box<dyn Future<Output = Result<(), ExportError>> + 'a>>;
+
+/// Why an [`Exporter`] failed to send a batch
+#[derive(Debug, thiserror::Error)]
+#[error("{0}")]
+pub struct ExportError(String);
+
+impl ExportError {
+    /// Error with this message
+    pub fn new(message: impl fmt::Display) -> Self {
+        Self(message.to_string())
+    }
+}
+
+/// Sends batches to a Hindsight server over a rapace session
+pub(crate) struct RapaceExporter {
+    session: Arc<RpcSession>,
+}
+
+impl RapaceExporter {
+    pub(crate) fn new(session: Arc<RpcSession>) -> Self {
+        Self { session }
+    }
+}
+
+impl Exporter for RapaceExporter {
+    fn export(&self, spans: Vec<Span>) -> ExportFuture<'_> {
+        let client = HindsightServiceClient::new(self.session.clone());
+        Box::pin(async move {
+            client
+                .ingest_spans(spans)
+                .await
+                .map(|_| ())
+                .map_err(|e| ExportError::new(format!("{:?}", e)))
+        })
+    }
+
+    fn name(&self) -> String {
+        "server".to_string()
+    }
+}
+
+/// Exporters added with [`TracerBuilder::exporter`](crate::TracerBuilder::exporter), in order
+#[derive(Clone, Default)]
+pub(crate) struct Exporters(Vec<Arc<dyn Exporter>>);
+
+impl Exporters {
+    pub(crate) fn push(&mut self, exporter: impl Exporter) {
+        self.0.push(Arc::new(exporter));
+    }
+}
+
+impl fmt::Debug for Exporters {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Exporters")
+            .field("len", &self.0.len())
+            .finish()
+    }
+}
+
+/// Every exporter's queue; each span goes to all of them
+pub(crate) struct FanOut {
+    queues: Vec<BatchQueue>,
+    counters: Vec<ExporterCounters>,
+}
+
+/// Queue feeding one exporter's batching task
+struct BatchQueue {
+    stats: Arc<StatsCounters>,
+    span_tx: mpsc::Sender<Span>,
+    /// Asks the batching task to send everything now, then acknowledge
+    flush_tx: mpsc::UnboundedSender<SyncSender<()>>,
+}
+
+impl FanOut {
+    /// Spawn a batching task for `first` (if any) and each of the configured
+    /// exporters
+    pub(crate) fn start(first: Option<Arc<dyn Exporter>>, config: &TracerConfig) -> Self {
+        let mut queues = Vec::new();
+        let mut counters = Vec::new();
+        for exporter in first.into_iter().chain(config.exporters.0.iter().cloned()) {
+            let stats = Arc::new(StatsCounters::new(ConnectionState::Connected));
+            counters.push(ExporterCounters {
+                name: exporter.name(),
+                counters: stats.clone(),
+            });
+            queues.push(BatchQueue::start(exporter, config, stats));
+        }
+        Self { queues, counters }
+    }
+
+    /// Each exporter's counters, in order
+    pub(crate) fn counters(&self) -> &[ExporterCounters] {
+        &self.counters
+    }
+
+    /// Queue `span` for every exporter
+    ///
+    /// A full queue drops the span for that exporter only.
+    pub(crate) fn send(&self, span: Span) {
+        let Some((last, rest)) = self.queues.split_last() else {
+            return;
+        };
+        for queue in rest {
+            queue.send(span.clone());
+        }
+        last.send(span);
+    }
+
+    /// Ask every exporter to send its queued spans now, and wait for all of
+    /// them (see [`Tracer::flush_blocking`](crate::Tracer::flush_blocking))
+    pub(crate) fn flush_blocking(&self, timeout: Duration) -> bool {
+        let mut acks = Vec::with_capacity(self.queues.len());
+        for queue in &self.queues {
+            let (ack_tx, ack_rx) = std::sync::mpsc::sync_channel(1);
+            if queue.flush_tx.send(ack_tx).is_err() {
+                return false;
+            }
+            acks.push(ack_rx);
+        }
+        if cfg!(target_arch = "wasm32") {
+            return false;
+        }
+        // `Instant` panics in browsers, hence only after the check above
+        let deadline = std::time::Instant::now().checked_add(timeout);
+        acks.iter().all(|ack| match deadline {
+            Some(deadline) => ack
+                .recv_timeout(deadline.saturating_duration_since(std::time::Instant::now()))
+                .is_ok(),
+            None => ack.recv().is_ok(),
+        })
+    }
+}
+
+impl BatchQueue {
+    /// Spawn the batching loop feeding finished spans to `exporter`
+    fn start(
+        exporter: Arc<dyn Exporter>,
+        config: &TracerConfig,
+        stats: Arc<StatsCounters>,
+    ) -> Self {
+        let (span_tx, mut span_rx) = mpsc::channel(config.queue_capacity);
+        let (flush_tx, mut flush_rx) = mpsc::unbounded_channel::<SyncSender<()>>();
+
+        let batch_size = config.batch_size;
+        let flush_interval = config.flush_interval;
+        let queue_stats = stats.clone();
+        runtime::spawn(untraced(async move {
+            let exporter = &*exporter;
+            let mut batch = Vec::new();
+            let mut interval = Interval::new(flush_interval);
+
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {
+                        exporter.tick();
+                        if !batch.is_empty() {
+                            deliver(exporter, std::mem::take(&mut batch), &stats).await;
+                        }
+                    }
+                    Some(span) = span_rx.recv() => {
+                        stats.span_dequeued();
+                        batch.push(span);
+                        if batch.len() >= batch_size {
+                            deliver(exporter, std::mem::take(&mut batch), &stats).await;
+                        }
+                    }
+                    Some(ack) = flush_rx.recv() => {
+                        while let Ok(span) = span_rx.try_recv() {
+                            stats.span_dequeued();
+                            batch.push(span);
+                        }
+                        for spans in std::mem::take(&mut batch).chunks(batch_size) {
+                            deliver(exporter, spans.to_vec(), &stats).await;
+                        }
+                        let _ = ack.try_send(());
+                    }
+                    else => break,
+                }
+            }
+
+            // Flush remaining spans on shutdown
+            if !batch.is_empty() {
+                deliver(exporter, batch, &stats).await;
+            }
+        }));
+
+        Self {
+            stats: queue_stats,
+            span_tx,
+            flush_tx,
+        }
+    }
+
+    fn send(&self, span: Span) {
+        let stats = &self.stats;
+        let finished = !span.is_running();
+        if finished {
+            stats.span_finished();
+        }
+        // Count it first so the batching task never sees it early
+        stats.span_queued();
+        if self.span_tx.try_send(span).is_err() {
+            stats.span_dequeued();
+            if finished {
+                stats.spans_dropped(1);
+            }
+        }
+    }
+}
+
+/// Most times a failed batch is sent again before its spans are dropped
+const MAX_RETRIES: u32 = 3;
+
+/// Wait before the first retry; doubles for each one after
+const RETRY_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Send one batch, retrying with backoff, and record the outcome
+async fn deliver(exporter: &dyn Exporter, spans: Vec<Span>, stats: &StatsCounters) {
+    let finished = spans.iter().filter(|span| !span.is_running()).count() as u64;
+    let mut attempt = 0;
+    loop {
+        // This exporter's session is gone for good: retrying would only hold
+        // up its queue
+        if stats.is_disconnected() {
+            stats.batch_failed(finished);
+            return;
+        }
+        match exporter.export(spans.clone()).await {
+            Ok(()) => {
+                stats.batch_sent(finished);
+                return;
+            }
+            Err(e) => {
+                // Report when things go wrong, not for every failed batch
+                if stats.send_failed() {
+                    eprintln!(
+                        "Hindsight client failed to send spans to {}: {}",
+                        exporter.name(),
+                        e
+                    );
+                }
+                if attempt == MAX_RETRIES {
+                    stats.batch_failed(finished);
+                    return;
+                }
+                runtime::sleep(RETRY_BACKOFF * 2u32.pow(attempt)).await;
+                attempt += 1;
+                stats.batch_retried();
+            }
+        }
+    }
+}
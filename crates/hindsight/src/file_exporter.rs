@@ -0,0 +1,146 @@
+use hindsight_protocol::*;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::exporter::{ExportError, ExportFuture, Exporter};
+
+/// Writes span batches to a local rotating file instead of a server
+///
+/// Spans are appended in the span file format (JSON Lines, see
+/// [`SpanFileRecord`]) with their original IDs and timestamps. Once the file
+/// exceeds `max_file_size`, it is rotated to `<path>.1` (and `.1` to `.2`,
+/// and so on), keeping at most `max_files` rotated files.
+///
+/// Import the files later with `hindsight import <files>`. Clones append
+/// to the same file.
+///
+/// Use it on its own with [`TracerBuilder::build_file`](crate::TracerBuilder::build_file),
+/// or next to a server with [`TracerBuilder::exporter`](crate::TracerBuilder::exporter).
+///
+/// # Example
+/// ```no_run
+/// # async fn example() {
+/// use hindsight::{FileExporter, Tracer};
+///
+/// let tracer = Tracer::builder()
+///     .service_name("ci")
+///     .build_file(FileExporter::new("target/hindsight/spans.jsonl"));
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct FileExporter {
+    path: PathBuf,
+    max_file_size: u64,
+    max_files: usize,
+    state: Arc<Mutex<FileState>>,
+}
+
+#[derive(Default)]
+struct FileState {
+    file: Option<File>,
+    size: u64,
+}
+
+impl FileExporter {
+    /// Export to `path`, rotating at 64 MiB and keeping 5 rotated files
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            max_file_size: 64 * 1024 * 1024,
+            max_files: 5,
+            state: Arc::new(Mutex::new(FileState::default())),
+        }
+    }
+
+    /// Rotate once the file grows past this many bytes
+    pub fn max_file_size(mut self, bytes: u64) -> Self {
+        self.max_file_size = bytes.max(1);
+        self
+    }
+
+    /// Number of rotated files to keep (older ones are deleted)
+    pub fn max_files(mut self, count: usize) -> Self {
+        self.max_files = count;
+        self
+    }
+
+    /// Path of the file currently being written
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Append a batch of spans, rotating first if the file is full
+    pub fn write_batch(&self, spans: &[Span]) -> io::Result<()> {
+        if spans.is_empty() {
+            return Ok(());
+        }
+
+        let mut lines = String::new();
+        for span in spans {
+            lines.push_str(&encode_span_line(span));
+            lines.push('\n');
+        }
+
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if state.file.is_some() && state.size + lines.len() as u64 > self.max_file_size {
+            state.file = None;
+            self.rotate()?;
+        }
+        if state.file.is_none() {
+            if let Some(parent) = self.path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)?;
+            state.size = file.metadata()?.len();
+            state.file = Some(file);
+        }
+
+        let file = state.file.as_mut().expect("file was just opened");
+        file.write_all(lines.as_bytes())?;
+        file.flush()?;
+        state.size += lines.len() as u64;
+        Ok(())
+    }
+
+    fn rotated_path(&self, index: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{}", index));
+        PathBuf::from(path)
+    }
+
+    fn rotate(&self) -> io::Result<()> {
+        if self.max_files == 0 {
+            return fs::remove_file(&self.path);
+        }
+
+        // Shift spans.jsonl.N-1 -> .N, ..., spans.jsonl -> .1
+        let _ = fs::remove_file(self.rotated_path(self.max_files));
+        for index in (1..self.max_files).rev() {
+            let from = self.rotated_path(index);
+            if from.exists() {
+                fs::rename(&from, self.rotated_path(index + 1))?;
+            }
+        }
+        fs::rename(&self.path, self.rotated_path(1))
+    }
+}
+
+impl Exporter for FileExporter {
+    /// Append a batch without blocking the async runtime
+    fn export(&self, spans: Vec<Span>) -> ExportFuture<'_> {
+        let exporter = self.clone();
+        Box::pin(async move {
+            let path = exporter.path.clone();
+            match tokio::task::spawn_blocking(move || exporter.write_batch(&spans)).await {
+                Ok(Ok(())) => Ok(()),
+                Ok(Err(e)) => Err(ExportError::new(format!("{}: {}", path.display(), e))),
+                Err(e) => Err(ExportError::new(format!("{}: {}", path.display(), e))),
+            }
+        })
+    }
+}
@@ -0,0 +1,281 @@
+//! W3C trace context propagation over HTTP.
+
+use hindsight_protocol::*;
+use http::header::{HeaderMap, HeaderName, HeaderValue};
+
+/// `traceparent` header name
+pub const TRACEPARENT: HeaderName = HeaderName::from_static("traceparent");
+
+/// `tracestate` header name
+pub const TRACESTATE: HeaderName = HeaderName::from_static("tracestate");
+
+/// Write `traceparent` (and `tracestate`, if any) headers for `context`
+pub fn inject_headers(context: &TraceContext, headers: &mut HeaderMap) {
+    if let Ok(value) = HeaderValue::from_str(&context.to_traceparent()) {
+        headers.insert(TRACEPARENT, value);
+    }
+    if let Some(tracestate) = &context.tracestate {
+        if let Ok(value) = HeaderValue::from_str(tracestate) {
+            headers.insert(TRACESTATE, value);
+        }
+    }
+}
+
+/// Read the remote parent context from `traceparent` and `tracestate` headers
+///
+/// Returns None if there is no `traceparent` header or it is malformed.
+pub fn extract_headers(headers: &HeaderMap) -> Option<TraceContext> {
+    let traceparent = headers.get(TRACEPARENT)?.to_str().ok()?;
+
+    // Multiple tracestate headers are equivalent to one comma-joined header
+    let tracestate = headers
+        .get_all(TRACESTATE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .collect::<Vec<_>>()
+        .join(",");
+    let tracestate = (!tracestate.is_empty()).then_some(tracestate.as_str());
+
+    TraceContext::from_headers(traceparent, tracestate).ok()
+}
+
+#[cfg(feature = "tower")]
+pub use self::server::{TraceLayer, TraceService, TraceServiceFuture};
+
+#[cfg(feature = "tower")]
+mod server {
+    use hindsight_protocol::*;
+    use http::{Request, Response};
+    use pin_project_lite::pin_project;
+    use std::fmt;
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    use crate::context::{Instrument, Instrumented};
+    use crate::span_builder::ActiveSpan;
+    use crate::tracer::Tracer;
+
+    /// Tower layer that opens a server span for every incoming request
+    ///
+    /// The remote parent is taken from the `traceparent` and `tracestate`
+    /// headers, so the span joins the caller's trace. The span is current
+    /// while the inner service runs, records `http.*` attributes, and is
+    /// marked as errored on 5xx responses or service errors.
+    ///
+    /// Works with axum (`Router::layer`) and with plain hyper services through
+    /// `hyper_util::service::TowerToHyperService`.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # fn example(tracer: hindsight::Tracer) {
+    /// let app: axum::Router = axum::Router::new()
+    ///     .route("/", axum::routing::get(|| async { "hello" }))
+    ///     .layer(hindsight::http::TraceLayer::new(tracer));
+    /// # }
+    /// ```
+    #[derive(Clone)]
+    pub struct TraceLayer {
+        tracer: Tracer,
+    }
+
+    impl TraceLayer {
+        /// Create a layer that reports request spans to `tracer`
+        pub fn new(tracer: Tracer) -> Self {
+            Self { tracer }
+        }
+    }
+
+    impl<S> tower::Layer<S> for TraceLayer {
+        type Service = TraceService<S>;
+
+        fn layer(&self, inner: S) -> Self::Service {
+            TraceService {
+                inner,
+                tracer: self.tracer.clone(),
+            }
+        }
+    }
+
+    /// Service created by [`TraceLayer`]
+    #[derive(Clone)]
+    pub struct TraceService<S> {
+        inner: S,
+        tracer: Tracer,
+    }
+
+    impl<S, ReqBody, ResBody> tower::Service<Request<ReqBody>> for TraceService<S>
+    where
+        S: tower::Service<Request<ReqBody>, Response = Response<ResBody>>,
+        S::Error: fmt::Display,
+    {
+        type Response = S::Response;
+        type Error = S::Error;
+        type Future = TraceServiceFuture<S::Future>;
+
+        fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            self.inner.poll_ready(cx)
+        }
+
+        fn call(&mut self, request: Request<ReqBody>) -> Self::Future {
+            let method = request.method().as_str().to_string();
+            let path = request.uri().path().to_string();
+
+            let mut builder = self
+                .tracer
+                .span(format!("{} {}", method, path))
+                .with_attribute("span.kind", "server")
+                .with_attribute("http.method", method)
+                .with_attribute("http.target", path);
+            if let Some(host) = request.headers().get(http::header::HOST) {
+                if let Ok(host) = host.to_str() {
+                    builder = builder.with_attribute("http.host", host);
+                }
+            }
+            if let Some(user_agent) = request.headers().get(http::header::USER_AGENT) {
+                if let Ok(user_agent) = user_agent.to_str() {
+                    builder = builder.with_attribute("http.user_agent", user_agent);
+                }
+            }
+            builder = match super::extract_headers(request.headers()) {
+                Some(parent) => builder.with_parent(parent),
+                None => builder.root(),
+            };
+
+            let span = builder.start();
+            let context = span.context().clone();
+            let future = {
+                let _guard = span.enter();
+                self.inner.call(request)
+            };
+
+            TraceServiceFuture {
+                inner: future.instrument(context),
+                span: Some(span),
+            }
+        }
+    }
+
+    pin_project! {
+        /// Response future of [`TraceService`]
+        pub struct TraceServiceFuture<F> {
+            #[pin]
+            inner: Instrumented<F>,
+            span: Option<ActiveSpan>,
+        }
+    }
+
+    impl<F, ResBody, E> Future for TraceServiceFuture<F>
+    where
+        F: Future<Output = Result<Response<ResBody>, E>>,
+        E: fmt::Display,
+    {
+        type Output = F::Output;
+
+        fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+            let this = self.project();
+            let result = std::task::ready!(this.inner.poll(cx));
+
+            if let Some(mut span) = this.span.take() {
+                match &result {
+                    Ok(response) => {
+                        let status = response.status();
+                        span.set_attribute("http.status_code", status.as_u16());
+                        if status.is_server_error() {
+                            span.set_error(status.to_string());
+                        }
+                    }
+                    Err(e) => span.set_error(e.to_string()),
+                }
+                span.end();
+            }
+
+            Poll::Ready(result)
+        }
+    }
+}
+
+#[cfg(feature = "reqwest")]
+pub use self::client::RequestBuilderExt;
+
+#[cfg(feature = "reqwest")]
+mod client {
+    use crate::context::current_context;
+    use crate::tracer::Tracer;
+    use hindsight_protocol::*;
+
+    /// Trace context propagation for outgoing reqwest requests
+    ///
+    /// # Example
+    /// ```no_run
+    /// # async fn example(tracer: hindsight::Tracer) -> reqwest::Result<()> {
+    /// use hindsight::http::RequestBuilderExt;
+    ///
+    /// let client = reqwest::Client::new();
+    ///
+    /// // Open a client span and send its context downstream
+    /// let response = client
+    ///     .get("http://localhost:8080/users")
+    ///     .send_traced(&tracer)
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[allow(async_fn_in_trait)]
+    pub trait RequestBuilderExt: Sized {
+        /// Add `traceparent`/`tracestate` headers for `context`
+        fn with_trace_context(self, context: &TraceContext) -> Self;
+
+        /// Add headers for the current span, if there is one
+        fn with_current_trace_context(self) -> Self;
+
+        /// Send the request inside a new client span
+        ///
+        /// The span records `http.*` attributes and is marked as errored on
+        /// transport errors and 5xx responses.
+        async fn send_traced(self, tracer: &Tracer) -> reqwest::Result<reqwest::Response>;
+    }
+
+    impl RequestBuilderExt for reqwest::RequestBuilder {
+        fn with_trace_context(self, context: &TraceContext) -> Self {
+            let mut headers = http::HeaderMap::new();
+            super::inject_headers(context, &mut headers);
+            self.headers(headers)
+        }
+
+        fn with_current_trace_context(self) -> Self {
+            match current_context() {
+                Some(context) => self.with_trace_context(&context),
+                None => self,
+            }
+        }
+
+        async fn send_traced(self, tracer: &Tracer) -> reqwest::Result<reqwest::Response> {
+            let (client, request) = self.build_split();
+            let mut request = request?;
+
+            let mut span = tracer
+                .span(format!("{} {}", request.method(), request.url().path()))
+                .with_attribute("span.kind", "client")
+                .with_attribute("http.method", request.method().as_str())
+                .with_attribute("http.url", request.url().as_str())
+                .start();
+            super::inject_headers(span.context(), request.headers_mut());
+
+            let result = client.execute(request).await;
+            match &result {
+                Ok(response) => {
+                    let status = response.status();
+                    span.set_attribute("http.status_code", status.as_u16());
+                    if status.is_server_error() {
+                        span.set_error(status.to_string());
+                    }
+                }
+                Err(e) => span.set_error(e.to_string()),
+            }
+            span.end();
+
+            result
+        }
+    }
+}
@@ -21,10 +21,265 @@
 //!     Ok(())
 //! }
 //! ```
+//!
+//! # Configuration
+//!
+//! [`Tracer::builder`] configures batching, queueing, sampling, resource
+//! attributes and the transport; each setting can be overridden from
+//! `HINDSIGHT_*` environment variables (see [`TracerBuilder`]).
+//! [`Tracer::from_env`] connects using the environment alone.
+//! [`Tracer::stats`] reports on the client itself (spans sent and dropped,
+//! queue depth, connection state).
+//!
+//! # Without an async runtime
+//!
+//! [`BlockingTracer`] (or [`TracerBuilder::connect_blocking`]) runs the
+//! tracer on a background thread with its own tokio runtime, for CLIs,
+//! build scripts and other synchronous programs. It hands out the same
+//! [`SpanBuilder`]s and flushes with plain blocking calls.
+//!
+//! # Processing spans
+//!
+//! [`SpanProcessor`]s registered with [`TracerBuilder::processor`] see every
+//! span before it is exported, in order: [`FilterProcessor`] drops spans
+//! (health checks, say), [`EnrichProcessor`] adds attributes,
+//! [`RedactProcessor`] hides values by key pattern, and
+//! [`AttributeLimitProcessor`] caps attribute counts and sizes.
+//!
+//! # Exporters
+//!
+//! Spans can go to several places at once: add [`Exporter`]s (a
+//! [`FileExporter`], or your own) with [`TracerBuilder::exporter`] and
+//! every span is fanned out to them as well as to the server. Each exporter
+//! batches and retries on its own, so one failing doesn't affect the rest.
+//! During development, [`ConsoleExporter`] prints each trace to stderr as a
+//! tree.
+//!
+//! # Offline recording
+//!
+//! Without a reachable server (CI jobs, air-gapped machines), build the
+//! tracer with [`TracerBuilder::build_file`] and a [`FileExporter`], or set
+//! `HINDSIGHT_TRANSPORT=file` and `HINDSIGHT_ENDPOINT=<path>`. Load the files
+//! into a server later with `hindsight import <files>`; spans keep their
+//! original timestamps.
+//!
+//! # Testing
+//!
+//! [`Tracer::in_memory`] records finished spans synchronously, so tests can
+//! assert on instrumentation with [`InMemorySpans::assert_has`] and
+//! [`SpanMatcher`] instead of running a server.
+//!
+//! # Panics
+//!
+//! [`install_panic_hook`] reports panics as errored spans carrying an
+//! `exception` event (message, location, backtrace) and flushes them before
+//! the process goes down.
+//!
+//! # Browsers
+//!
+//! The crate also builds for `wasm32` browser apps, with default features:
+//! connect with [`Tracer::connect_websocket`], and batching runs on browser
+//! timers instead of tokio. Timestamps come from `performance.now()`, so
+//! front-end spans line up with those of the services they call. File
+//! export, the Unix, shared-memory and TCP transports, TLS settings and
+//! [`spawn`] are native only.
+//!
+//! # Libraries
+//!
+//! Libraries can instrument unconditionally by accepting a [`Tracer`] and
+//! defaulting to [`Tracer::noop`], which needs no server or runtime and
+//! costs close to nothing. With `default-features = false`, the `client`
+//! feature is off and hindsight doesn't pull in tokio or rapace.
+//!
+//! # Context propagation
+//!
+//! New spans default to the current span as their parent. Use
+//! [`Tracer::in_span`] or [`Instrument::instrument`] to make a span current
+//! for a future, [`ActiveSpan::enter`] for synchronous code, and [`spawn`] to
+//! carry the current span into a new tokio task.
+//!
+//! Across processes, [`process::CommandExt`] passes the current span to a
+//! child in the `TRACEPARENT` environment variable; the child's tracer picks
+//! it up at startup and parents its root spans under it.
+//!
+//! # Features
+//!
+//! - `client` (default): connecting to a server, with batching, exporters
+//!   and every transport. Pulls in tokio and rapace. Libraries that only
+//!   accept a [`Tracer`] can turn default features off and stay light.
+//! - `tracing`: [`HindsightLayer`], a `tracing_subscriber` layer that exports
+//!   `tracing` spans and events to hindsight.
+//! - `macros`: `#[hindsight::instrument]`, which wraps a function in a span
+//!   sent to the global tracer (see [`Tracer::install_global`]).
+//! - `http`: `traceparent`/`tracestate` header helpers in [`http`].
+//! - `tower`: `http::TraceLayer`, which opens a server span per request for
+//!   axum and hyper services.
+//! - `reqwest`: `http::RequestBuilderExt`, which propagates context on
+//!   outgoing requests.
+//! - `rpc`: `rpc::RpcSessionExt`, which traces every call made or served
+//!   over a rapace session and carries context in frame metadata.
+//! - `picante`: `picante::RuntimeExt`, which hooks a picante runtime so
+//!   every query execution emits a span.
+//! - `dodeca`: `dodeca::DodecaTracing`, which turns each dodeca rebuild into
+//!   one trace with a span per pipeline step.
+//! - `tls`: `TracerBuilder::tls` and `TlsConfig`, encrypting connections with rustls
+//!   (public roots, a private CA, or a pinned self-signed certificate).
+//! - `noop`: compiles the API down to stubs. Every constructor returns
+//!   [`Tracer::noop`] without connecting, and spans become empty types
+//!   whose methods do nothing. Lets an application switch off
+//!   instrumentation in all its dependencies; to also drop tokio and rapace,
+//!   turn off default features.
 
+mod attribute;
+#[cfg(all(feature = "client", not(target_arch = "wasm32")))]
+mod blocking;
+mod builder;
+#[cfg(all(feature = "client", not(target_arch = "wasm32")))]
+mod console_exporter;
+mod context;
+#[cfg(feature = "dodeca")]
+pub mod dodeca;
+#[cfg(feature = "client")]
+mod exporter;
+#[cfg(all(feature = "client", not(target_arch = "wasm32")))]
+mod file_exporter;
+#[cfg(feature = "http")]
+pub mod http;
+#[cfg(feature = "noop")]
+mod noop;
+mod open_spans;
+mod panic_hook;
+#[cfg(feature = "picante")]
+pub mod picante;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod process;
+mod processor;
+#[cfg(feature = "rpc")]
+pub mod rpc;
+#[cfg(feature = "client")]
+mod runtime;
+mod sampler;
+#[cfg(not(feature = "noop"))]
 mod span_builder;
+// Stubs with the same API that compile to nothing
+#[cfg(feature = "noop")]
+use crate::noop as span_builder;
+mod stats;
+mod testing;
+#[cfg(feature = "tls")]
+mod tls;
 mod tracer;
+#[cfg(feature = "tracing")]
+mod tracing_layer;
 
+#[cfg(all(feature = "client", not(target_arch = "wasm32")))]
+pub use blocking::BlockingTracer;
+pub use builder::{TracerBuilder, TransportKind};
+#[cfg(all(feature = "client", not(target_arch = "wasm32")))]
+pub use console_exporter::ConsoleExporter;
+#[cfg(all(feature = "client", not(target_arch = "wasm32")))]
+pub use context::spawn;
+pub use context::{
+    current_context, enter_context, is_untraced, untraced, ContextGuard, Instrument, Instrumented,
+    Untraced,
+};
+#[cfg(feature = "client")]
+pub use exporter::{ExportError, ExportFuture, Exporter};
+#[cfg(all(feature = "client", not(target_arch = "wasm32")))]
+pub use file_exporter::FileExporter;
 pub use hindsight_protocol::*;
+pub use panic_hook::{install_panic_hook, install_panic_hook_with_timeout};
+pub use processor::{
+    AttributeLimitProcessor, EnrichProcessor, FilterProcessor, RedactProcessor, SpanProcessor,
+};
+#[cfg(feature = "client")]
+pub use runtime::{MaybeSend, MaybeSync};
+pub use sampler::Sampler;
 pub use span_builder::{ActiveSpan, IntoAttributeValue, SpanBuilder};
-pub use tracer::{Tracer, TracerError};
+pub use stats::{ConnectionState, ExporterStats, TracerStats};
+pub use testing::{InMemorySpans, SpanMatcher};
+#[cfg(feature = "tls")]
+pub use tls::TlsConfig;
+pub use tracer::{global_tracer, Tracer, TracerError};
+#[cfg(feature = "tracing")]
+pub use tracing_layer::HindsightLayer;
+
+#[cfg(feature = "macros")]
+pub use hindsight_macros::instrument;
+
+/// Support code for `#[instrument]`; not public API
+#[doc(hidden)]
+pub mod __macro_support {
+    use std::fmt;
+    use std::future::Future;
+
+    use crate::context::{Instrument, Instrumented};
+    use crate::span_builder::{ActiveSpan, IntoAttributeValue};
+    use crate::AttributeValue;
+
+    /// Run `future` inside `span`, or in the current context when untraced
+    pub fn instrument<F: Future>(span: Option<&ActiveSpan>, future: F) -> Instrumented<F> {
+        match span {
+            Some(span) => future.instrument(span.context().clone()),
+            None => future.in_current_context(),
+        }
+    }
+
+    /// Run a sync function's body
+    ///
+    /// Taking `FnOnce` makes the closure `FnOnce` too, so the body may return
+    /// borrows of the `&mut` arguments it captured.
+    pub fn call_once<R>(body: impl FnOnce() -> R) -> R {
+        body()
+    }
+
+    /// An instrumented function argument, recorded through `RecordValue`
+    /// when its type allows and through `RecordDebug` otherwise
+    pub struct Arg<'a, T: ?Sized>(pub &'a T);
+
+    /// Records the argument as its own attribute value
+    pub trait RecordValue {
+        fn attribute_value(&self) -> AttributeValue;
+    }
+
+    impl<T: IntoAttributeValue + Clone> RecordValue for Arg<'_, T> {
+        fn attribute_value(&self) -> AttributeValue {
+            self.0.clone().into_attribute_value()
+        }
+    }
+
+    /// Records the argument as its `Debug` output; implemented on `&Arg` so
+    /// method resolution only reaches it when `RecordValue` does not apply
+    pub trait RecordDebug {
+        fn attribute_value(&self) -> AttributeValue;
+    }
+
+    impl<T: fmt::Debug + ?Sized> RecordDebug for &Arg<'_, T> {
+        fn attribute_value(&self) -> AttributeValue {
+            AttributeValue::String(format!("{:?}", self.0))
+        }
+    }
+
+    /// Describes a returned error with its `Display` output
+    pub trait ErrorDisplay {
+        fn error_message(&self) -> String;
+    }
+
+    impl<T: fmt::Display + ?Sized> ErrorDisplay for Arg<'_, T> {
+        fn error_message(&self) -> String {
+            self.0.to_string()
+        }
+    }
+
+    /// Describes a returned error with its `Debug` output, for errors
+    /// without `Display`
+    pub trait ErrorDebug {
+        fn error_message(&self) -> String;
+    }
+
+    impl<T: fmt::Debug + ?Sized> ErrorDebug for &Arg<'_, T> {
+        fn error_message(&self) -> String {
+            format!("{:?}", self.0)
+        }
+    }
+}
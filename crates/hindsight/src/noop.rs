@@ -0,0 +1,146 @@
+//! Span types for the `noop` feature.
+//!
+//! Same API as the real `SpanBuilder` and `ActiveSpan`, but they hold
+//! nothing beyond the trace context they pass through, and every method
+//! compiles to nothing.
+
+use hindsight_protocol::*;
+
+pub use crate::attribute::IntoAttributeValue;
+use crate::sampler::SAMPLED_FLAG;
+use crate::tracer::Tracer;
+
+/// Builder for creating and starting spans
+pub struct SpanBuilder {
+    parent: Option<TraceContext>,
+    root: bool,
+}
+
+impl SpanBuilder {
+    #[inline]
+    pub(crate) fn new(_name: impl Into<String>, _tracer: &Tracer) -> Self {
+        Self {
+            parent: None,
+            root: false,
+        }
+    }
+
+    /// Set the parent trace context (for propagation)
+    #[inline]
+    pub fn with_parent(mut self, parent: TraceContext) -> Self {
+        self.parent = Some(parent);
+        self.root = false;
+        self
+    }
+
+    /// Start a new trace, ignoring the current span
+    #[inline]
+    pub fn root(mut self) -> Self {
+        self.root = true;
+        self.parent = None;
+        self
+    }
+
+    /// Add an attribute to the span
+    #[inline]
+    pub fn with_attribute(self, _key: impl Into<String>, _value: impl IntoAttributeValue) -> Self {
+        self
+    }
+
+    /// Start the span at an explicit time instead of now
+    #[inline]
+    pub fn start_at(self, _start_time: Timestamp) -> ActiveSpan {
+        self.start()
+    }
+
+    /// Start the span
+    ///
+    /// Passes the parent's context straight through, so propagation keeps
+    /// working.
+    #[inline]
+    pub fn start(self) -> ActiveSpan {
+        let parent = if self.root {
+            None
+        } else {
+            self.parent.or_else(crate::context::current_context)
+        };
+        ActiveSpan {
+            context: parent.unwrap_or_else(crate::context::noop_context),
+        }
+    }
+}
+
+/// Active span (not yet finished)
+pub struct ActiveSpan {
+    context: TraceContext,
+}
+
+impl ActiveSpan {
+    /// Get the trace context (for propagation to downstream calls)
+    #[inline]
+    pub fn context(&self) -> &TraceContext {
+        &self.context
+    }
+
+    /// Always None: there is no span being recorded
+    #[inline]
+    pub(crate) fn span_mut(&mut self) -> Option<&mut Span> {
+        None
+    }
+
+    /// Whether this span's trace is being recorded elsewhere
+    #[inline]
+    pub fn is_sampled(&self) -> bool {
+        self.context.flags & SAMPLED_FLAG != 0
+    }
+
+    /// Make this span current on this thread until the guard is dropped
+    #[inline]
+    pub fn enter(&self) -> crate::context::ContextGuard {
+        crate::context::enter_context(self.context.clone())
+    }
+
+    /// Add or replace an attribute
+    #[inline]
+    pub fn set_attribute(&mut self, _key: impl Into<String>, _value: impl IntoAttributeValue) {}
+
+    /// Add an event to the span
+    #[inline]
+    pub fn add_event(&mut self, _name: impl Into<String>) {}
+
+    /// Add an event with attributes to the span
+    #[inline]
+    pub fn add_event_with_attributes<K, V>(
+        &mut self,
+        _name: impl Into<String>,
+        _attributes: impl IntoIterator<Item = (K, V)>,
+    ) where
+        K: Into<String>,
+        V: IntoAttributeValue,
+    {
+    }
+
+    /// Rename the span
+    #[inline]
+    pub fn update_name(&mut self, _name: impl Into<String>) {}
+
+    /// Set the span status
+    #[inline]
+    pub fn set_status(&mut self, _status: SpanStatus) {}
+
+    /// Mark the span as errored
+    #[inline]
+    pub fn set_error(&mut self, _message: impl Into<String>) {}
+
+    /// Measure the span's duration from `start` instead of from `start()`
+    #[inline]
+    pub fn record_duration_from(&mut self, _start: Timestamp) {}
+
+    /// End the span
+    #[inline]
+    pub fn end(self) {}
+
+    /// End the span at an explicit time
+    #[inline]
+    pub fn end_at(self, _end_time: Timestamp) {}
+}
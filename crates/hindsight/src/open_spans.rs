@@ -0,0 +1,83 @@
+// Only real spans and the heartbeat task keep these up to date
+#![cfg_attr(any(feature = "noop", not(feature = "client")), allow(dead_code))]
+
+use hindsight_protocol::*;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+/// Snapshots of spans that have started but not ended
+///
+/// Only kept once something needs them (the panic hook, which reports the
+/// span that was current when the thread panicked, and heartbeats); until
+/// then the bookkeeping is a single atomic load per span.
+#[derive(Default)]
+pub(crate) struct OpenSpans {
+    enabled: AtomicBool,
+    spans: Mutex<HashMap<SpanId, OpenSpan>>,
+}
+
+struct OpenSpan {
+    span: Span,
+    /// Last in-flight record sent for the span, as the processors left it
+    in_flight: Option<Span>,
+}
+
+impl OpenSpans {
+    pub(crate) fn enable(&self) {
+        self.enabled.store(true, Ordering::Relaxed);
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<SpanId, OpenSpan>> {
+        self.spans.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub(crate) fn started(&self, span: &Span) {
+        if self.is_enabled() {
+            let open = OpenSpan {
+                span: span.clone(),
+                in_flight: None,
+            };
+            self.lock().insert(span.span_id, open);
+        }
+    }
+
+    /// Note that `record` is about to be sent; false if its span has ended
+    /// meanwhile, and the record would be stale
+    pub(crate) fn in_flight_sent(&self, record: &Span) -> bool {
+        match self.lock().get_mut(&record.span_id) {
+            Some(open) => {
+                open.in_flight = Some(record.clone());
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Forget a span that ended; returns the last in-flight record sent
+    /// for it, if any
+    pub(crate) fn finished(&self, span_id: SpanId) -> Option<Span> {
+        if self.is_enabled() {
+            self.lock().remove(&span_id)?.in_flight
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn get(&self, span_id: SpanId) -> Option<Span> {
+        self.lock().get(&span_id).map(|open| open.span.clone())
+    }
+
+    /// Every open span started at or before `started_before`
+    pub(crate) fn snapshot(&self, started_before: Timestamp) -> Vec<Span> {
+        self.lock()
+            .values()
+            .filter(|open| open.span.start_time.0 <= started_before.0)
+            .map(|open| open.span.clone())
+            .collect()
+    }
+}
@@ -0,0 +1,164 @@
+use hindsight_protocol::*;
+use std::backtrace::Backtrace;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::panic::PanicHookInfo;
+use std::time::Duration;
+
+use crate::context::current_context;
+use crate::tracer::Tracer;
+
+/// How long the panic hook waits for queued spans to be sent
+const PANIC_FLUSH_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// The most recent panic on this thread, for spans dropped while unwinding
+#[derive(Clone)]
+#[cfg_attr(feature = "noop", allow(dead_code))]
+pub(crate) struct PanicRecord {
+    pub message: String,
+    pub event: SpanEvent,
+    /// Open span the hook already reported, which mustn't be exported again
+    /// when it is dropped while unwinding
+    pub reported: Option<SpanId>,
+}
+
+thread_local! {
+    static LAST_PANIC: RefCell<Option<PanicRecord>> = const { RefCell::new(None) };
+}
+
+#[cfg_attr(feature = "noop", allow(dead_code))]
+pub(crate) fn last_panic() -> Option<PanicRecord> {
+    LAST_PANIC.with(|last| last.borrow().clone())
+}
+
+/// Report panics to `tracer` as errored spans
+///
+/// On panic, the hook builds an `exception` event with the message, source
+/// location and a backtrace, and marks the current span as errored with it
+/// (or, without a current span, records a new errored `panic` span). It then
+/// flushes queued spans, waiting up to two seconds, so the report makes it
+/// out even if the process aborts. Spans dropped while unwinding are ended
+/// as errored too; the current span is reported only once. The previously
+/// installed hook still runs afterwards.
+///
+/// # Example
+/// ```no_run
+/// # async fn example() -> Result<(), hindsight::TracerError> {
+/// let tracer = hindsight::Tracer::from_env().await?;
+/// hindsight::install_panic_hook(&tracer);
+/// # Ok(())
+/// # }
+/// ```
+pub fn install_panic_hook(tracer: &Tracer) {
+    install_panic_hook_with_timeout(tracer, PANIC_FLUSH_TIMEOUT);
+}
+
+/// [`install_panic_hook`] with a custom flush timeout
+pub fn install_panic_hook_with_timeout(tracer: &Tracer, flush_timeout: Duration) {
+    let tracer = tracer.clone();
+    tracer.open_spans().enable();
+
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        report_panic(&tracer, info, flush_timeout);
+        previous(info);
+    }));
+}
+
+fn report_panic(tracer: &Tracer, info: &PanicHookInfo<'_>, flush_timeout: Duration) {
+    if tracer.is_noop() || crate::context::is_untraced() {
+        return;
+    }
+
+    let message = panic_message(info);
+    let event = exception_event(&message, info);
+    let current = current_context();
+    let open = current
+        .as_ref()
+        .and_then(|context| tracer.open_spans().get(context.span_id));
+    LAST_PANIC.with(|last| {
+        *last.borrow_mut() = Some(PanicRecord {
+            message: message.clone(),
+            event: event.clone(),
+            reported: open.as_ref().map(|span| span.span_id),
+        })
+    });
+
+    match open {
+        // Report the current span as it stands, in case the process aborts
+        // before it is dropped; dropping it while unwinding won't report it
+        // a second time
+        Some(mut span) => {
+            span.status = SpanStatus::Error {
+                message: format!("panicked: {}", message),
+            };
+            span.events.push(event);
+            span.end_time = Some(Timestamp::now());
+            tracer.export(span);
+        }
+        None => {
+            let mut span = tracer.span("panic").start();
+            span.set_error(format!("panicked: {}", message));
+            if let Some(recorded) = span.span_mut() {
+                recorded.events.push(event);
+            }
+            span.end();
+        }
+    }
+
+    tracer.flush_blocking(flush_timeout);
+}
+
+fn panic_message(info: &PanicHookInfo<'_>) -> String {
+    let payload = info.payload();
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "Box<dyn Any>".to_string()
+    }
+}
+
+/// `exception` event following the OpenTelemetry semantic conventions
+fn exception_event(message: &str, info: &PanicHookInfo<'_>) -> SpanEvent {
+    let mut attributes = BTreeMap::new();
+    attributes.insert(
+        "exception.type".to_string(),
+        AttributeValue::String("panic".to_string()),
+    );
+    attributes.insert(
+        "exception.message".to_string(),
+        AttributeValue::String(message.to_string()),
+    );
+    attributes.insert(
+        "exception.stacktrace".to_string(),
+        AttributeValue::String(Backtrace::force_capture().to_string()),
+    );
+    if let Some(location) = info.location() {
+        attributes.insert(
+            "code.filepath".to_string(),
+            AttributeValue::String(location.file().to_string()),
+        );
+        attributes.insert(
+            "code.lineno".to_string(),
+            AttributeValue::Int(location.line() as i64),
+        );
+        attributes.insert(
+            "code.column".to_string(),
+            AttributeValue::Int(location.column() as i64),
+        );
+    }
+    if let Some(name) = std::thread::current().name() {
+        attributes.insert(
+            "thread.name".to_string(),
+            AttributeValue::String(name.to_string()),
+        );
+    }
+
+    SpanEvent {
+        name: "exception".to_string(),
+        timestamp: Timestamp::now(),
+        attributes,
+    }
+}
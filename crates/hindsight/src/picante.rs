@@ -0,0 +1,149 @@
+//! Spans for picante query execution.
+
+use picante::{CacheOutcome, QueryHook, QueryInfo, QueryNext, QueryReport, Runtime};
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+
+use crate::context::Instrument;
+use crate::span_builder::ActiveSpan;
+use crate::tracer::Tracer;
+
+/// How a query result was obtained
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CacheStatus {
+    /// Memoized value reused without checking dependencies
+    Hit,
+    /// Value computed from scratch
+    Miss,
+    /// Memoized value reused after revalidating its dependencies
+    Validated,
+}
+
+impl CacheStatus {
+    /// Value of the `picante.cache_status` attribute
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CacheStatus::Hit => "hit",
+            CacheStatus::Miss => "miss",
+            CacheStatus::Validated => "validated",
+        }
+    }
+}
+
+impl From<CacheOutcome> for CacheStatus {
+    fn from(outcome: CacheOutcome) -> Self {
+        match outcome {
+            CacheOutcome::Hit => CacheStatus::Hit,
+            CacheOutcome::Computed => CacheStatus::Miss,
+            CacheOutcome::Revalidated => CacheStatus::Validated,
+        }
+    }
+}
+
+impl fmt::Display for CacheStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+type HookFuture<'a> = Pin<Box<dyn Future<Output = QueryReport> + Send + 'a>>;
+
+/// Trace every query a picante runtime executes
+pub trait RuntimeExt {
+    /// Emit a span per query execution to `tracer`
+    ///
+    /// See [`PicanteTracing`].
+    fn set_tracer(&self, tracer: Tracer);
+}
+
+impl RuntimeExt for Runtime {
+    fn set_tracer(&self, tracer: Tracer) {
+        self.set_query_hook(PicanteTracing::new(tracer));
+    }
+}
+
+/// Hook for the picante runtime that emits one span per query execution
+///
+/// Spans carry `picante.query`, `picante.query_kind`, `picante.query_key`,
+/// `picante.revision`, `picante.cache_status` and
+/// `picante.dependency_count`, so traces are classified as
+/// `TraceType::Picante`. Dependencies are fetched while their caller's
+/// compute runs, so their spans become children of the caller's span.
+///
+/// # Example
+/// ```no_run
+/// # fn example(db: &impl picante::HasRuntime, tracer: hindsight::Tracer) {
+/// use hindsight::picante::RuntimeExt;
+///
+/// db.runtime().set_tracer(tracer);
+///
+/// // Every query run through `db` from now on shows up as a span
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct PicanteTracing {
+    tracer: Tracer,
+}
+
+impl PicanteTracing {
+    /// Report query spans to `tracer`
+    pub fn new(tracer: Tracer) -> Self {
+        Self { tracer }
+    }
+
+    /// Start the span for one execution of a query
+    ///
+    /// `key` is recorded with its `Debug` representation. The span's parent
+    /// is the current span, which is the calling query's span when the
+    /// query is a dependency.
+    fn start_query(&self, kind: &str, key: &dyn fmt::Debug, revision: u64) -> QuerySpan {
+        let span = self
+            .tracer
+            .span(kind)
+            .with_attribute("picante.query", true)
+            .with_attribute("picante.query_kind", kind)
+            .with_attribute("picante.query_key", format!("{:?}", key))
+            .with_attribute("picante.revision", revision)
+            .start();
+        QuerySpan { span }
+    }
+}
+
+impl QueryHook for PicanteTracing {
+    fn execute<'a>(&'a self, query: QueryInfo<'a>, next: QueryNext<'a>) -> HookFuture<'a> {
+        let span = self.start_query(query.kind_name(), query.key(), query.revision().0);
+        Box::pin(async move {
+            let report = span.run(next.run()).await;
+            span.finish(&report);
+            report
+        })
+    }
+}
+
+/// Span for a query that is executing
+struct QuerySpan {
+    span: ActiveSpan,
+}
+
+impl QuerySpan {
+    /// Run the query's compute (or validation) inside this span
+    async fn run<F: Future>(&self, future: F) -> F::Output {
+        future.instrument(self.span.context().clone()).await
+    }
+
+    /// End the span with the final cache status and dependency count
+    fn finish(mut self, report: &QueryReport) {
+        match &report.result {
+            Ok(outcome) => {
+                self.span
+                    .set_attribute("picante.cache_status", CacheStatus::from(*outcome).as_str());
+            }
+            // Cycles, panics and cancellation
+            Err(e) => self.span.set_error(e.to_string()),
+        }
+        self.span
+            .set_attribute("picante.dependency_count", report.dependency_count);
+        self.span.end();
+    }
+}
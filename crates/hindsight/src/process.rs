@@ -0,0 +1,132 @@
+//! W3C trace context propagation to child processes.
+//!
+//! The context travels in the `TRACEPARENT`, `TRACESTATE` and `BAGGAGE`
+//! environment variables. [`CommandExt`] also sets [`HINDSIGHT_PARENT`];
+//! tracers that find it at startup attach the root spans of the child
+//! process under the span that spawned it. A `TRACEPARENT` set by something
+//! else (a CI runner, a wrapper script) is left alone, since its spans never
+//! reach hindsight.
+
+use hindsight_protocol::*;
+
+use crate::context::current_context;
+
+/// `TRACEPARENT` environment variable name
+pub const TRACEPARENT: &str = "TRACEPARENT";
+
+/// `TRACESTATE` environment variable name
+pub const TRACESTATE: &str = "TRACESTATE";
+
+/// `BAGGAGE` environment variable name
+pub const BAGGAGE: &str = "BAGGAGE";
+
+/// Marks `TRACEPARENT` as set by [`CommandExt`], for a parent that reports
+/// to hindsight
+pub const HINDSIGHT_PARENT: &str = "HINDSIGHT_PARENT";
+
+/// Read the parent context from this process's environment
+///
+/// Returns None if `TRACEPARENT` is unset or malformed.
+pub fn extract_env() -> Option<TraceContext> {
+    let traceparent = std::env::var(TRACEPARENT).ok()?;
+    let tracestate = std::env::var(TRACESTATE).ok();
+    let mut context = TraceContext::from_headers(traceparent.trim(), tracestate.as_deref()).ok()?;
+    context.baggage = std::env::var(BAGGAGE)
+        .ok()
+        .map(|baggage| baggage.trim().to_string())
+        .filter(|baggage| !baggage.is_empty());
+    Some(context)
+}
+
+/// [`extract_env`], if [`CommandExt`] set the variables
+pub(crate) fn extract_inherited_env() -> Option<TraceContext> {
+    std::env::var_os(HINDSIGHT_PARENT)?;
+    extract_env()
+}
+
+/// Trace context propagation for spawned processes
+///
+/// Implemented for [`std::process::Command`] and, with the `client`
+/// feature, `tokio::process::Command`.
+///
+/// # Example
+/// ```no_run
+/// # fn example(tracer: hindsight::Tracer) -> std::io::Result<()> {
+/// use hindsight::process::CommandExt;
+///
+/// let span = tracer.span("cargo build").start();
+/// let status = std::process::Command::new("cargo")
+///     .arg("build")
+///     .with_trace_context(span.context())
+///     .status()?;
+/// span.end();
+/// # Ok(())
+/// # }
+/// ```
+pub trait CommandExt {
+    /// Set `TRACEPARENT` (and `TRACESTATE`, `BAGGAGE`) for `context`, and
+    /// [`HINDSIGHT_PARENT`] so the child's tracer picks them up
+    ///
+    /// A `TRACESTATE` inherited from this process is removed if `context`
+    /// has none; an inherited `BAGGAGE` is kept.
+    fn with_trace_context(&mut self, context: &TraceContext) -> &mut Self;
+
+    /// Set the variables for the current span, if there is one
+    ///
+    /// Otherwise the child inherits this process's own variables, if any.
+    fn with_current_trace_context(&mut self) -> &mut Self;
+}
+
+/// Variables to set for `context`; `None` values are to be removed
+fn env_vars(context: &TraceContext) -> [(&'static str, Option<String>); 3] {
+    [
+        (TRACEPARENT, Some(context.to_traceparent())),
+        (TRACESTATE, context.tracestate.clone()),
+        (HINDSIGHT_PARENT, Some("1".to_string())),
+    ]
+}
+
+impl CommandExt for std::process::Command {
+    fn with_trace_context(&mut self, context: &TraceContext) -> &mut Self {
+        for (key, value) in env_vars(context) {
+            match value {
+                Some(value) => self.env(key, value),
+                None => self.env_remove(key),
+            };
+        }
+        if let Some(baggage) = &context.baggage {
+            self.env(BAGGAGE, baggage);
+        }
+        self
+    }
+
+    fn with_current_trace_context(&mut self) -> &mut Self {
+        match current_context() {
+            Some(context) => self.with_trace_context(&context),
+            None => self,
+        }
+    }
+}
+
+#[cfg(feature = "client")]
+impl CommandExt for tokio::process::Command {
+    fn with_trace_context(&mut self, context: &TraceContext) -> &mut Self {
+        for (key, value) in env_vars(context) {
+            match value {
+                Some(value) => self.env(key, value),
+                None => self.env_remove(key),
+            };
+        }
+        if let Some(baggage) = &context.baggage {
+            self.env(BAGGAGE, baggage);
+        }
+        self
+    }
+
+    fn with_current_trace_context(&mut self) -> &mut Self {
+        match current_context() {
+            Some(context) => self.with_trace_context(&context),
+            None => self,
+        }
+    }
+}
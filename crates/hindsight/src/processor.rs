@@ -0,0 +1,297 @@
+use hindsight_protocol::*;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::span_builder::IntoAttributeValue;
+
+/// Hook run on every span before it is queued for export
+///
+/// Register processors with [`TracerBuilder::processor`](crate::TracerBuilder::processor);
+/// they run in registration order, after resource attributes are added.
+/// In-flight records (see
+/// [`TracerBuilder::heartbeat_interval`](crate::TracerBuilder::heartbeat_interval))
+/// go through them too, so check [`Span::is_running`] when that matters. If
+/// the finished span is dropped after in-flight records of it were sent, the
+/// last of those is sent again, ended, rather than left running forever.
+///
+/// Processors run on the thread that ends the span; keep them cheap.
+/// Closures taking `&mut Span` and returning `bool` are processors.
+///
+/// # Example
+/// ```no_run
+/// # async fn example() -> Result<(), hindsight::TracerError> {
+/// let tracer = hindsight::Tracer::builder()
+///     .processor(|span: &mut hindsight::Span| {
+///         span.name = span.name.to_lowercase();
+///         true
+///     })
+///     .connect()
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+pub trait SpanProcessor: Send + Sync + 'static {
+    /// Inspect or modify `span`; return false to drop it
+    fn process(&self, span: &mut Span) -> bool;
+}
+
+impl<F> SpanProcessor for F
+where
+    F: Fn(&mut Span) -> bool + Send + Sync + 'static,
+{
+    fn process(&self, span: &mut Span) -> bool {
+        self(span)
+    }
+}
+
+/// Drops spans that don't match a predicate
+///
+/// # Example
+/// ```
+/// use hindsight::FilterProcessor;
+///
+/// // Keep health checks out of the trace list
+/// let filter = FilterProcessor::new(|span| span.name != "GET /health");
+/// ```
+pub struct FilterProcessor {
+    keep: Box<dyn Fn(&Span) -> bool + Send + Sync>,
+}
+
+impl FilterProcessor {
+    /// Keep only spans for which `keep` returns true
+    pub fn new(keep: impl Fn(&Span) -> bool + Send + Sync + 'static) -> Self {
+        Self {
+            keep: Box::new(keep),
+        }
+    }
+}
+
+impl SpanProcessor for FilterProcessor {
+    fn process(&self, span: &mut Span) -> bool {
+        (self.keep)(span)
+    }
+}
+
+/// Adds fixed attributes to every span, unless the span sets them itself
+///
+/// # Example
+/// ```
+/// use hindsight::EnrichProcessor;
+///
+/// let enrich = EnrichProcessor::new()
+///     .with_attribute("deployment.environment", "staging")
+///     .with_attribute("deployment.region", "eu-west-1");
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct EnrichProcessor {
+    attributes: BTreeMap<String, AttributeValue>,
+}
+
+impl EnrichProcessor {
+    /// Processor adding nothing yet
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `key` to every span
+    pub fn with_attribute(
+        mut self,
+        key: impl Into<String>,
+        value: impl IntoAttributeValue,
+    ) -> Self {
+        self.attributes
+            .insert(key.into(), value.into_attribute_value());
+        self
+    }
+}
+
+impl SpanProcessor for EnrichProcessor {
+    fn process(&self, span: &mut Span) -> bool {
+        for (key, value) in &self.attributes {
+            span.attributes
+                .entry(key.clone())
+                .or_insert_with(|| value.clone());
+        }
+        true
+    }
+}
+
+/// Replaces the values of attributes whose keys match a pattern
+///
+/// Patterns match whole keys, ignoring case; `*` matches any run of
+/// characters. Span and event attributes are both redacted; keys are kept so
+/// it stays visible that a value was there.
+///
+/// # Example
+/// ```
+/// use hindsight::RedactProcessor;
+///
+/// let redact = RedactProcessor::new(["*token*", "*password*", "user.email"]);
+/// ```
+#[derive(Clone, Debug)]
+pub struct RedactProcessor {
+    patterns: Vec<String>,
+    replacement: AttributeValue,
+}
+
+impl RedactProcessor {
+    /// Redact attributes matching any of `patterns` with `"[REDACTED]"`
+    pub fn new(patterns: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self {
+            patterns: patterns
+                .into_iter()
+                .map(|pattern| pattern.into().to_ascii_lowercase())
+                .collect(),
+            replacement: AttributeValue::String("[REDACTED]".to_string()),
+        }
+    }
+
+    /// Value written in place of redacted ones
+    pub fn replacement(mut self, value: impl IntoAttributeValue) -> Self {
+        self.replacement = value.into_attribute_value();
+        self
+    }
+
+    fn redact(&self, attributes: &mut BTreeMap<String, AttributeValue>) {
+        for (key, value) in attributes.iter_mut() {
+            let key = key.to_ascii_lowercase();
+            if self
+                .patterns
+                .iter()
+                .any(|pattern| glob_match(pattern, &key))
+            {
+                *value = self.replacement.clone();
+            }
+        }
+    }
+}
+
+impl SpanProcessor for RedactProcessor {
+    fn process(&self, span: &mut Span) -> bool {
+        self.redact(&mut span.attributes);
+        for event in &mut span.events {
+            self.redact(&mut event.attributes);
+        }
+        true
+    }
+}
+
+/// Whether `text` matches `pattern`, where `*` matches any run of characters
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    // `split` always yields at least one part
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+    let mut parts: Vec<&str> = parts.collect();
+    let Some(last) = parts.pop() else {
+        // No `*`: the whole text must match
+        return rest.is_empty();
+    };
+    for part in parts {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+/// Caps the number and size of attributes on each span and its events
+///
+/// Long string values are cut to the limit (on a character boundary);
+/// attributes beyond the count limit are dropped, last keys first.
+///
+/// # Example
+/// ```
+/// use hindsight::AttributeLimitProcessor;
+///
+/// let limits = AttributeLimitProcessor::new()
+///     .max_attributes(64)
+///     .max_value_len(4096);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct AttributeLimitProcessor {
+    max_attributes: Option<usize>,
+    max_value_len: Option<usize>,
+}
+
+impl AttributeLimitProcessor {
+    /// Processor with no limits yet
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep at most `max` attributes per span or event
+    pub fn max_attributes(mut self, max: usize) -> Self {
+        self.max_attributes = Some(max);
+        self
+    }
+
+    /// Cut string values to at most `max` bytes
+    pub fn max_value_len(mut self, max: usize) -> Self {
+        self.max_value_len = Some(max);
+        self
+    }
+
+    fn limit(&self, attributes: &mut BTreeMap<String, AttributeValue>) {
+        if let Some(max) = self.max_attributes {
+            while attributes.len() > max {
+                attributes.pop_last();
+            }
+        }
+        if let Some(max) = self.max_value_len {
+            for value in attributes.values_mut() {
+                if let AttributeValue::String(s) = value {
+                    truncate(s, max);
+                }
+            }
+        }
+    }
+}
+
+impl SpanProcessor for AttributeLimitProcessor {
+    fn process(&self, span: &mut Span) -> bool {
+        self.limit(&mut span.attributes);
+        for event in &mut span.events {
+            self.limit(&mut event.attributes);
+        }
+        true
+    }
+}
+
+/// Cut `s` to at most `max` bytes without splitting a character
+fn truncate(s: &mut String, max: usize) {
+    if s.len() > max {
+        let mut end = max;
+        while !s.is_char_boundary(end) {
+            end -= 1;
+        }
+        s.truncate(end);
+    }
+}
+
+/// Processors registered on a tracer, in order
+#[derive(Clone, Default)]
+pub(crate) struct SpanProcessors(Vec<Arc<dyn SpanProcessor>>);
+
+impl SpanProcessors {
+    pub(crate) fn push(&mut self, processor: impl SpanProcessor) {
+        self.0.push(Arc::new(processor));
+    }
+
+    /// Run every processor on `span`; false if one of them dropped it
+    pub(crate) fn process(&self, span: &mut Span) -> bool {
+        self.0.iter().all(|processor| processor.process(span))
+    }
+}
+
+impl fmt::Debug for SpanProcessors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SpanProcessors")
+            .field("len", &self.0.len())
+            .finish()
+    }
+}
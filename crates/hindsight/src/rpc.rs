@@ -0,0 +1,196 @@
+//! Automatic spans and context propagation for rapace RPC.
+
+use hindsight_protocol::*;
+use rapace::registry::{self, MethodEntry};
+use rapace::{Frame, FrameMetadata, Layer, Next, RpcError, RpcSession};
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::pin::Pin;
+
+use crate::context::Instrument;
+use crate::span_builder::{ActiveSpan, SpanBuilder};
+use crate::tracer::Tracer;
+
+/// Metadata key carrying the W3C traceparent
+pub const TRACEPARENT_KEY: &str = "traceparent";
+
+/// Metadata key carrying the W3C tracestate
+pub const TRACESTATE_KEY: &str = "tracestate";
+
+/// Services that are never traced
+///
+/// Tracing calls to hindsight itself would produce a span per batch of
+/// spans, forever.
+const UNTRACED_SERVICES: &[&str] = &["HindsightService"];
+
+type LayerFuture<'a> = Pin<Box<dyn Future<Output = Result<Frame, RpcError>> + Send + 'a>>;
+
+/// Key/value metadata sent alongside an RPC frame
+pub trait RpcMetadata {
+    /// Look up a metadata value
+    fn get(&self, key: &str) -> Option<&str>;
+
+    /// Set a metadata value, replacing any previous one
+    fn set(&mut self, key: &str, value: String);
+}
+
+impl RpcMetadata for FrameMetadata {
+    fn get(&self, key: &str) -> Option<&str> {
+        FrameMetadata::get(self, key)
+    }
+
+    fn set(&mut self, key: &str, value: String) {
+        self.insert(key, value);
+    }
+}
+
+impl RpcMetadata for BTreeMap<String, String> {
+    fn get(&self, key: &str) -> Option<&str> {
+        BTreeMap::get(self, key).map(String::as_str)
+    }
+
+    fn set(&mut self, key: &str, value: String) {
+        self.insert(key.to_string(), value);
+    }
+}
+
+impl RpcMetadata for HashMap<String, String> {
+    fn get(&self, key: &str) -> Option<&str> {
+        HashMap::get(self, key).map(String::as_str)
+    }
+
+    fn set(&mut self, key: &str, value: String) {
+        self.insert(key.to_string(), value);
+    }
+}
+
+impl RpcMetadata for Vec<(String, String)> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn set(&mut self, key: &str, value: String) {
+        self.retain(|(k, _)| k != key);
+        self.push((key.to_string(), value));
+    }
+}
+
+/// Write `context` into frame metadata
+pub fn inject_metadata(context: &TraceContext, metadata: &mut impl RpcMetadata) {
+    metadata.set(TRACEPARENT_KEY, context.to_traceparent());
+    if let Some(tracestate) = &context.tracestate {
+        metadata.set(TRACESTATE_KEY, tracestate.clone());
+    }
+}
+
+/// Read the caller's context from frame metadata
+pub fn extract_metadata(metadata: &impl RpcMetadata) -> Option<TraceContext> {
+    let traceparent = metadata.get(TRACEPARENT_KEY)?;
+    TraceContext::from_headers(traceparent, metadata.get(TRACESTATE_KEY)).ok()
+}
+
+/// Trace every call made and served over a rapace session
+pub trait RpcSessionExt {
+    /// Open spans around the session's calls, reporting them to `tracer`
+    ///
+    /// See [`RpcTracing`].
+    fn with_tracer(self, tracer: Tracer) -> Self;
+}
+
+impl RpcSessionExt for RpcSession {
+    fn with_tracer(self, tracer: Tracer) -> Self {
+        self.layer(RpcTracing::new(tracer))
+    }
+}
+
+/// Session layer opening spans around rapace calls on both ends
+///
+/// Outgoing calls get a client span whose context is written into the
+/// request frame's metadata; dispatch of incoming calls runs inside a server
+/// span parented to the context found there, so one trace follows a request
+/// across processes. Spans are named `Service.method` and tagged with
+/// `rpc.system=rapace`, `rpc.service` and `rpc.method`, which is what
+/// `TraceType::Rapace` classification keys on.
+///
+/// Calls to `HindsightService` are never traced, whichever session they go
+/// through.
+///
+/// # Example
+/// ```no_run
+/// # async fn example(tracer: hindsight::Tracer) -> Result<(), Box<dyn std::error::Error>> {
+/// use hindsight::rpc::RpcSessionExt;
+/// use rapace::{RpcSession, Transport};
+///
+/// let stream = tokio::net::TcpStream::connect("localhost:4000").await?;
+/// let session = RpcSession::new(Transport::stream(stream)).with_tracer(tracer);
+///
+/// // Every call through a client built on `session` now opens a span
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct RpcTracing {
+    tracer: Tracer,
+}
+
+impl RpcTracing {
+    /// Report RPC spans to `tracer`
+    pub fn new(tracer: Tracer) -> Self {
+        Self { tracer }
+    }
+
+    /// Span for a call to `method`, or None if its service is untraced
+    fn span(&self, method: &MethodEntry, kind: &'static str) -> Option<SpanBuilder> {
+        if UNTRACED_SERVICES.contains(&method.service_name) {
+            return None;
+        }
+        let span = self
+            .tracer
+            .span(format!("{}.{}", method.service_name, method.method_name))
+            .with_attribute("span.kind", kind)
+            .with_attribute("rpc.system", "rapace")
+            .with_attribute("rpc.service", method.service_name)
+            .with_attribute("rpc.method", method.method_name);
+        Some(span)
+    }
+}
+
+impl Layer for RpcTracing {
+    fn call<'a>(&'a self, mut frame: Frame, next: Next<'a>) -> LayerFuture<'a> {
+        // Parented to the current span, i.e. the caller's
+        let span = registry::lookup(frame.desc.method_id)
+            .and_then(|method| self.span(&method, "client"))
+            .map(SpanBuilder::start);
+        if let Some(span) = &span {
+            inject_metadata(span.context(), frame.metadata_mut());
+        }
+        Box::pin(run_in_span(span, next.run(frame)))
+    }
+
+    fn dispatch<'a>(&'a self, frame: Frame, next: Next<'a>) -> LayerFuture<'a> {
+        let span = registry::lookup(frame.desc.method_id)
+            .and_then(|method| self.span(&method, "server"))
+            .map(|span| match extract_metadata(frame.metadata()) {
+                Some(parent) => span.with_parent(parent).start(),
+                None => span.root().start(),
+            });
+        Box::pin(run_in_span(span, next.run(frame)))
+    }
+}
+
+async fn run_in_span<F>(span: Option<ActiveSpan>, future: F) -> F::Output
+where
+    F: Future<Output = Result<Frame, RpcError>>,
+{
+    let Some(mut span) = span else {
+        return future.await;
+    };
+    let result = future.instrument(span.context().clone()).await;
+    if let Err(e) = &result {
+        span.set_error(e.to_string());
+    }
+    span.end();
+    result
+}
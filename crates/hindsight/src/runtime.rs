@@ -0,0 +1,148 @@
+//! Background tasks and timers on tokio, or on the browser event loop
+//! for `wasm32`
+
+use std::future::Future;
+use std::time::Duration;
+
+/// `Send` natively; nothing in the browser, where futures wrap JS values
+/// and everything runs on one thread
+///
+/// Bounds [`Exporter`](crate::Exporter)s, so that browser ones can hold JS
+/// values.
+#[cfg(not(target_arch = "wasm32"))]
+pub trait MaybeSend: Send {}
+#[cfg(not(target_arch = "wasm32"))]
+impl<T: Send + ?Sized> MaybeSend for T {}
+
+#[cfg(target_arch = "wasm32")]
+pub trait MaybeSend {}
+#[cfg(target_arch = "wasm32")]
+impl<T: ?Sized> MaybeSend for T {}
+
+/// `Sync` natively; nothing in the browser (see [`MaybeSend`])
+#[cfg(not(target_arch = "wasm32"))]
+pub trait MaybeSync: Sync {}
+#[cfg(not(target_arch = "wasm32"))]
+impl<T: Sync + ?Sized> MaybeSync for T {}
+
+#[cfg(target_arch = "wasm32")]
+pub trait MaybeSync {}
+#[cfg(target_arch = "wasm32")]
+impl<T: ?Sized> MaybeSync for T {}
+
+/// Run `future` in the background
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn spawn<F>(future: F)
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    tokio::spawn(future);
+}
+
+/// Run `future` in the background
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn spawn<F>(future: F)
+where
+    F: Future<Output = ()> + 'static,
+{
+    wasm_bindgen_futures::spawn_local(future);
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) async fn sleep(duration: Duration) {
+    tokio::time::sleep(duration).await;
+}
+
+#[cfg(target_arch = "wasm32")]
+pub(crate) async fn sleep(duration: Duration) {
+    let millis = duration.as_millis().min(u32::MAX as u128) as u32;
+    gloo_timers::future::TimeoutFuture::new(millis).await;
+}
+
+/// Ticks every `period`, starting one period from now
+///
+/// Safe to use in `select!`: a cancelled `tick` doesn't move the schedule.
+pub(crate) struct Interval {
+    #[cfg(not(target_arch = "wasm32"))]
+    inner: tokio::time::Interval,
+    #[cfg(target_arch = "wasm32")]
+    period_millis: f64,
+    #[cfg(target_arch = "wasm32")]
+    next_millis: f64,
+}
+
+impl Interval {
+    pub(crate) fn new(period: Duration) -> Self {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let start = tokio::time::Instant::now() + period;
+            Self {
+                inner: tokio::time::interval_at(start, period),
+            }
+        }
+        #[cfg(target_arch = "wasm32")]
+        {
+            let period_millis = period.as_secs_f64() * 1000.0;
+            Self {
+                period_millis,
+                next_millis: js_sys::Date::now() + period_millis,
+            }
+        }
+    }
+
+    pub(crate) async fn tick(&mut self) {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            self.inner.tick().await;
+        }
+        #[cfg(target_arch = "wasm32")]
+        {
+            let remaining = self.next_millis - js_sys::Date::now();
+            if remaining > 0.0 {
+                gloo_timers::future::TimeoutFuture::new(remaining.ceil() as u32).await;
+            }
+            // Skip missed ticks rather than firing them in a burst
+            let now = js_sys::Date::now();
+            self.next_millis += self.period_millis;
+            if self.next_millis < now {
+                self.next_millis = now + self.period_millis;
+            }
+        }
+    }
+}
+
+// Run with `wasm-pack test --node crates/hindsight -- --lib`
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use super::*;
+    use hindsight_protocol::Timestamp;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[wasm_bindgen_test]
+    async fn test_interval_ticks_on_browser_timers() {
+        let started = js_sys::Date::now();
+        let mut interval = Interval::new(Duration::from_millis(50));
+        interval.tick().await;
+        interval.tick().await;
+        assert!(js_sys::Date::now() - started >= 100.0);
+
+        // Missed ticks are skipped, not fired in a burst
+        sleep(Duration::from_millis(200)).await;
+        interval.tick().await;
+        let before = js_sys::Date::now();
+        interval.tick().await;
+        assert!(js_sys::Date::now() - before >= 40.0);
+    }
+
+    #[wasm_bindgen_test]
+    fn test_timestamp_now() {
+        let first = Timestamp::now();
+        let second = Timestamp::now();
+        assert!(second.0 >= first.0);
+
+        // Wall-clock time, not time since the page loaded
+        let date_millis = js_sys::Date::now();
+        let millis = first.0 as f64 / 1_000_000.0;
+        assert!((millis - date_millis).abs() < 1_000.0);
+    }
+}
@@ -0,0 +1,142 @@
+use hindsight_protocol::*;
+use std::str::FromStr;
+
+/// W3C `sampled` trace flag
+pub(crate) const SAMPLED_FLAG: u8 = 0x01;
+
+/// Decides which new traces are recorded
+///
+/// The decision is made once, when a root span starts, and stored in the
+/// trace context's `sampled` flag. Child spans (including those in other
+/// processes, via propagation) follow their parent's decision.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Sampler {
+    /// Record every trace
+    #[default]
+    AlwaysOn,
+    /// Record nothing
+    AlwaysOff,
+    /// Record this fraction (0.0 to 1.0) of traces, chosen by trace ID
+    Ratio(f64),
+}
+
+impl Sampler {
+    /// Check that a `Ratio` is a number from 0.0 to 1.0
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        match *self {
+            Sampler::Ratio(ratio) if !(0.0..=1.0).contains(&ratio) => Err(format!(
+                "invalid sampler ratio {}, expected a number from 0.0 to 1.0",
+                ratio
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether a trace with this ID should be recorded
+    ///
+    /// Deterministic, so every span of a trace gets the same answer.
+    pub fn should_sample(&self, trace_id: TraceId) -> bool {
+        match *self {
+            Sampler::AlwaysOn => true,
+            Sampler::AlwaysOff => false,
+            Sampler::Ratio(ratio) => {
+                if ratio >= 1.0 {
+                    return true;
+                }
+                if ratio <= 0.0 {
+                    return false;
+                }
+                // The low 8 bytes of a W3C trace ID are random
+                let low = u64::from_be_bytes(trace_id.0[8..].try_into().unwrap());
+                (low as f64) < ratio * (u64::MAX as f64)
+            }
+        }
+    }
+}
+
+impl FromStr for Sampler {
+    type Err = String;
+
+    /// Parse `always_on`, `always_off` or `ratio:<0.0..=1.0>`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "always_on" => Ok(Sampler::AlwaysOn),
+            "always_off" => Ok(Sampler::AlwaysOff),
+            other => other
+                .strip_prefix("ratio:")
+                .and_then(|ratio| ratio.parse::<f64>().ok())
+                .map(Sampler::Ratio)
+                .filter(|sampler| sampler.validate().is_ok())
+                .ok_or_else(|| {
+                    format!(
+                        "invalid sampler {:?}, expected always_on, always_off or ratio:<0..1>",
+                        other
+                    )
+                }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trace_id(low: u64) -> TraceId {
+        let mut bytes = [0xff; 16];
+        bytes[8..].copy_from_slice(&low.to_be_bytes());
+        TraceId(bytes)
+    }
+
+    #[test]
+    fn test_should_sample() {
+        let id = TraceId::new();
+        assert!(Sampler::AlwaysOn.should_sample(id));
+        assert!(!Sampler::AlwaysOff.should_sample(id));
+        assert!(Sampler::Ratio(1.0).should_sample(id));
+        assert!(!Sampler::Ratio(0.0).should_sample(id));
+
+        // Decided by the low 8 bytes of the trace ID
+        let half = Sampler::Ratio(0.5);
+        assert!(half.should_sample(trace_id(0)));
+        assert!(half.should_sample(trace_id(u64::MAX / 4)));
+        assert!(!half.should_sample(trace_id(u64::MAX / 4 * 3)));
+        assert!(!half.should_sample(trace_id(u64::MAX)));
+        assert_eq!(half.should_sample(id), half.should_sample(id));
+    }
+
+    #[test]
+    fn test_ratio_sampling_rate() {
+        let sampler = Sampler::Ratio(0.25);
+        let sampled = (0..10_000)
+            .filter(|_| sampler.should_sample(TraceId::new()))
+            .count();
+        assert!((2_000..3_000).contains(&sampled), "sampled {}", sampled);
+    }
+
+    #[test]
+    fn test_from_str() {
+        assert_eq!("always_on".parse(), Ok(Sampler::AlwaysOn));
+        assert_eq!(" always_off ".parse(), Ok(Sampler::AlwaysOff));
+        assert_eq!("ratio:0.25".parse(), Ok(Sampler::Ratio(0.25)));
+        assert_eq!("ratio:1".parse(), Ok(Sampler::Ratio(1.0)));
+        for invalid in [
+            "",
+            "sometimes",
+            "ratio:",
+            "ratio:abc",
+            "ratio:1.5",
+            "ratio:-0.1",
+            "ratio:NaN",
+        ] {
+            assert!(invalid.parse::<Sampler>().is_err(), "{:?} parsed", invalid);
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(Sampler::Ratio(0.5).validate().is_ok());
+        assert!(Sampler::Ratio(f64::NAN).validate().is_err());
+        assert!(Sampler::Ratio(-1.0).validate().is_err());
+        assert!(Sampler::Ratio(f64::INFINITY).validate().is_err());
+    }
+}
@@ -1,34 +1,53 @@
 use hindsight_protocol::*;
 use std::collections::BTreeMap;
-use tokio::sync::mpsc;
+
+pub use crate::attribute::IntoAttributeValue;
+use crate::sampler::SAMPLED_FLAG;
+use crate::tracer::Tracer;
 
 /// Builder for creating and starting spans
 pub struct SpanBuilder {
     name: String,
-    service_name: String,
     attributes: BTreeMap<String, AttributeValue>,
     parent: Option<TraceContext>,
-    span_tx: mpsc::UnboundedSender<Span>,
+    root: bool,
+    start_time: Option<Timestamp>,
+    tracer: Tracer,
 }
 
 impl SpanBuilder {
-    pub(crate) fn new(
-        name: String,
-        service_name: String,
-        span_tx: mpsc::UnboundedSender<Span>,
-    ) -> Self {
+    pub(crate) fn new(name: impl Into<String>, tracer: &Tracer) -> Self {
         Self {
-            name,
-            service_name,
+            // No-op spans are never sent, so don't build their name
+            name: if tracer.is_noop() {
+                String::new()
+            } else {
+                name.into()
+            },
             attributes: BTreeMap::new(),
             parent: None,
-            span_tx,
+            root: false,
+            start_time: None,
+            tracer: tracer.clone(),
         }
     }
 
     /// Set the parent trace context (for propagation)
+    ///
+    /// Without this, the span is parented to the current span (see
+    /// [`current_context`](crate::current_context)), if there is one, or
+    /// else to the tracer's parent context (see
+    /// [`TracerBuilder::parent_context`](crate::TracerBuilder::parent_context)).
     pub fn with_parent(mut self, parent: TraceContext) -> Self {
         self.parent = Some(parent);
+        self.root = false;
+        self
+    }
+
+    /// Start a new trace, ignoring the current span
+    pub fn root(mut self) -> Self {
+        self.root = true;
+        self.parent = None;
         self
     }
 
@@ -38,17 +57,65 @@ impl SpanBuilder {
         key: impl Into<String>,
         value: impl IntoAttributeValue,
     ) -> Self {
-        self.attributes
-            .insert(key.into(), value.into_attribute_value());
+        if !self.tracer.is_noop() {
+            self.attributes
+                .insert(key.into(), value.into_attribute_value());
+        }
         self
     }
 
+    /// Start the span at an explicit time instead of now
+    ///
+    /// For importing or backfilling spans from recorded timings; pair with
+    /// [`ActiveSpan::end_at`].
+    pub fn start_at(mut self, start_time: Timestamp) -> ActiveSpan {
+        self.start_time = Some(start_time);
+        self.start()
+    }
+
     /// Start the span
     pub fn start(self) -> ActiveSpan {
-        let context = if let Some(parent) = self.parent {
+        let parent = if self.root {
+            None
+        } else {
+            self.parent
+                .or_else(crate::context::current_context)
+                .or_else(|| self.tracer.parent_context().cloned())
+        };
+
+        // A no-op span passes its parent's context straight through, so
+        // propagation keeps working without minting IDs
+        if self.tracer.is_noop() {
+            let context = parent.unwrap_or_else(crate::context::noop_context);
+            let span = Span {
+                trace_id: context.trace_id,
+                span_id: context.span_id,
+                parent_span_id: context.parent_span_id,
+                name: String::new(),
+                start_time: Timestamp(0),
+                end_time: None,
+                attributes: BTreeMap::new(),
+                events: Vec::new(),
+                status: SpanStatus::Ok,
+                service_name: String::new(),
+            };
+            return ActiveSpan {
+                span,
+                context,
+                tracer: self.tracer,
+                ended: false,
+            };
+        }
+
+        // Children follow their parent's sampling decision; roots ask the sampler
+        let context = if let Some(parent) = parent {
             parent.child()
         } else {
-            TraceContext::new_root()
+            let mut context = TraceContext::new_root();
+            if !self.tracer.sampler().should_sample(context.trace_id) {
+                context.flags &= !SAMPLED_FLAG;
+            }
+            context
         };
 
         let span = Span {
@@ -56,27 +123,44 @@ impl SpanBuilder {
             span_id: context.span_id,
             parent_span_id: context.parent_span_id,
             name: self.name,
-            start_time: Timestamp::now(),
+            start_time: self.start_time.unwrap_or_else(Timestamp::now),
             end_time: None,
             attributes: self.attributes,
             events: Vec::new(),
             status: SpanStatus::Ok,
-            service_name: self.service_name,
+            service_name: self.tracer.service_name().to_string(),
         };
 
-        ActiveSpan {
+        let active = ActiveSpan {
             span,
             context,
-            span_tx: self.span_tx,
+            tracer: self.tracer,
+            ended: false,
+        };
+        active
+            .tracer
+            .stats_counters()
+            .span_created(active.is_sampled());
+        if active.is_sampled() {
+            active.tracer.open_spans().started(&active.span);
+            if active.tracer.reports_in_flight() {
+                active.tracer.export_in_flight(active.span.clone());
+            }
         }
+        active
     }
 }
 
 /// Active span (not yet finished)
+///
+/// Dropping a span without ending it discards it, except while the thread
+/// is panicking: then it is ended as errored (see
+/// [`install_panic_hook`](crate::install_panic_hook)).
 pub struct ActiveSpan {
     span: Span,
     context: TraceContext,
-    span_tx: mpsc::UnboundedSender<Span>,
+    tracer: Tracer,
+    ended: bool,
 }
 
 impl ActiveSpan {
@@ -85,8 +169,46 @@ impl ActiveSpan {
         &self.context
     }
 
+    /// Direct access to the span being recorded, for integrations
+    ///
+    /// None for no-op spans, which record nothing.
+    pub(crate) fn span_mut(&mut self) -> Option<&mut Span> {
+        if self.tracer.is_noop() {
+            return None;
+        }
+        Some(&mut self.span)
+    }
+
+    /// Whether this span's trace is being recorded
+    ///
+    /// Unsampled spans still propagate context but are never sent.
+    pub fn is_sampled(&self) -> bool {
+        self.context.flags & SAMPLED_FLAG != 0
+    }
+
+    /// Make this span current on this thread until the guard is dropped
+    ///
+    /// Spans started while the guard is alive default to this span as their
+    /// parent. For async code, use [`Instrument`](crate::Instrument) instead.
+    pub fn enter(&self) -> crate::context::ContextGuard {
+        crate::context::enter_context(self.context.clone())
+    }
+
+    /// Add or replace an attribute
+    pub fn set_attribute(&mut self, key: impl Into<String>, value: impl IntoAttributeValue) {
+        if self.tracer.is_noop() {
+            return;
+        }
+        self.span
+            .attributes
+            .insert(key.into(), value.into_attribute_value());
+    }
+
     /// Add an event to the span
     pub fn add_event(&mut self, name: impl Into<String>) {
+        if self.tracer.is_noop() {
+            return;
+        }
         self.span.events.push(SpanEvent {
             name: name.into(),
             timestamp: Timestamp::now(),
@@ -94,63 +216,133 @@ impl ActiveSpan {
         });
     }
 
-    /// Mark the span as errored
-    pub fn set_error(&mut self, message: impl Into<String>) {
-        self.span.status = SpanStatus::Error {
-            message: message.into(),
-        };
+    /// Add an event with attributes to the span
+    ///
+    /// # Example
+    /// ```no_run
+    /// # fn example(mut span: hindsight::ActiveSpan) {
+    /// span.add_event_with_attributes("cache_miss", [("key", "user:42")]);
+    /// # }
+    /// ```
+    pub fn add_event_with_attributes<K, V>(
+        &mut self,
+        name: impl Into<String>,
+        attributes: impl IntoIterator<Item = (K, V)>,
+    ) where
+        K: Into<String>,
+        V: IntoAttributeValue,
+    {
+        if self.tracer.is_noop() {
+            return;
+        }
+        self.span.events.push(SpanEvent {
+            name: name.into(),
+            timestamp: Timestamp::now(),
+            attributes: attributes
+                .into_iter()
+                .map(|(key, value)| (key.into(), value.into_attribute_value()))
+                .collect(),
+        });
     }
 
-    /// End the span and send it to the server
-    pub fn end(mut self) {
-        self.span.end_time = Some(Timestamp::now());
-        let _ = self.span_tx.send(self.span);
+    /// Rename the span (e.g. once the matched route is known)
+    pub fn update_name(&mut self, name: impl Into<String>) {
+        if self.tracer.is_noop() {
+            return;
+        }
+        self.span.name = name.into();
     }
-}
-
-// Helper trait for converting to AttributeValue
-pub trait IntoAttributeValue {
-    fn into_attribute_value(self) -> AttributeValue;
-}
 
-impl IntoAttributeValue for &str {
-    fn into_attribute_value(self) -> AttributeValue {
-        AttributeValue::String(self.to_string())
+    /// Set the span status
+    pub fn set_status(&mut self, status: SpanStatus) {
+        if self.tracer.is_noop() {
+            return;
+        }
+        self.span.status = status;
     }
-}
 
-impl IntoAttributeValue for String {
-    fn into_attribute_value(self) -> AttributeValue {
-        AttributeValue::String(self)
+    /// Mark the span as errored
+    pub fn set_error(&mut self, message: impl Into<String>) {
+        if self.tracer.is_noop() {
+            return;
+        }
+        self.set_status(SpanStatus::Error {
+            message: message.into(),
+        });
     }
-}
 
-impl IntoAttributeValue for i64 {
-    fn into_attribute_value(self) -> AttributeValue {
-        AttributeValue::Int(self)
+    /// Measure the span's duration from `start` instead of from `start()`
+    ///
+    /// Useful when the span is only created once the work turns out to be
+    /// interesting, but timing began earlier: take `Timestamp::now()` when
+    /// it begins.
+    pub fn record_duration_from(&mut self, start: Timestamp) {
+        if self.tracer.is_noop() {
+            return;
+        }
+        self.span.start_time = start;
     }
-}
 
-impl IntoAttributeValue for i32 {
-    fn into_attribute_value(self) -> AttributeValue {
-        AttributeValue::Int(self as i64)
+    /// End the span and send it to the server
+    pub fn end(self) {
+        self.end_at(Timestamp::now());
     }
-}
 
-impl IntoAttributeValue for bool {
-    fn into_attribute_value(self) -> AttributeValue {
-        AttributeValue::Bool(self)
+    /// End the span at an explicit time and send it to the server
+    ///
+    /// An `end_time` before the span's start is moved up to the start.
+    pub fn end_at(mut self, end_time: Timestamp) {
+        self.finish(end_time);
     }
-}
 
-impl IntoAttributeValue for f64 {
-    fn into_attribute_value(self) -> AttributeValue {
-        AttributeValue::Float(self)
+    fn finish(&mut self, end_time: Timestamp) {
+        self.ended = true;
+        if !self.is_sampled() || self.tracer.is_noop() {
+            return;
+        }
+        let in_flight = self.tracer.open_spans().finished(self.span.span_id);
+
+        let placeholder = Span {
+            trace_id: self.span.trace_id,
+            span_id: self.span.span_id,
+            parent_span_id: self.span.parent_span_id,
+            name: String::new(),
+            start_time: self.span.start_time,
+            end_time: None,
+            attributes: BTreeMap::new(),
+            events: Vec::new(),
+            status: SpanStatus::Ok,
+            service_name: String::new(),
+        };
+        let mut span = std::mem::replace(&mut self.span, placeholder);
+        span.end_time = Some(Timestamp(end_time.0.max(span.start_time.0)));
+        self.tracer.export_finished(span, in_flight);
     }
 }
 
-impl IntoAttributeValue for AttributeValue {
-    fn into_attribute_value(self) -> AttributeValue {
-        self
+impl Drop for ActiveSpan {
+    fn drop(&mut self) {
+        if self.ended {
+            return;
+        }
+        if std::thread::panicking() {
+            // Unwinding out of the span's scope: report it rather than lose it,
+            // unless the panic hook already did
+            match crate::panic_hook::last_panic() {
+                Some(panic) if panic.reported == Some(self.span.span_id) => {
+                    self.ended = true;
+                    self.tracer.open_spans().finished(self.span.span_id);
+                    return;
+                }
+                Some(panic) => {
+                    self.set_error(format!("panicked: {}", panic.message));
+                    self.span.events.push(panic.event);
+                }
+                None => self.set_error("panicked"),
+            }
+            self.finish(Timestamp::now());
+        } else if self.is_sampled() {
+            self.tracer.open_spans().finished(self.span.span_id);
+        }
     }
 }
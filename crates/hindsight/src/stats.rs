@@ -0,0 +1,381 @@
+// Most counters are only bumped by the batching tasks and real spans
+#![cfg_attr(any(feature = "noop", not(feature = "client")), allow(dead_code))]
+
+use hindsight_protocol::Timestamp;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, AtomicU8, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Snapshot of a tracer's own health (see [`Tracer::stats`](crate::Tracer::stats))
+///
+/// Counters are cumulative since the tracer started and cover finished
+/// spans only; in-flight records (see
+/// [`TracerBuilder::heartbeat_interval`](crate::TracerBuilder::heartbeat_interval))
+/// are not counted. With several [`Exporter`](crate::Exporter)s, sent and
+/// dropped spans and the queue depth are counted once per exporter, and
+/// [`TracerStats::exporters`] breaks them down.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TracerStats {
+    /// Spans started
+    pub spans_created: u64,
+    /// Spans started in a sampled trace, i.e. meant to be recorded
+    pub spans_sampled: u64,
+    /// Spans delivered to the server (or file, or in-memory buffer)
+    pub spans_sent: u64,
+    /// Spans lost to a full queue or to a batch that failed for good
+    pub spans_dropped: u64,
+    /// Spans discarded on purpose by a [`SpanProcessor`](crate::SpanProcessor)
+    pub spans_filtered: u64,
+    /// Batches given up on after every retry failed
+    pub batches_failed: u64,
+    /// Send attempts repeated after a failure
+    pub batches_retried: u64,
+    /// Spans waiting in the queue to be batched
+    pub queue_depth: usize,
+    /// When a batch was last delivered
+    pub last_flush: Option<SystemTime>,
+    /// State of the connection to the server (or to the first exporter)
+    pub connection: ConnectionState,
+    /// The same counters for each exporter, server first
+    pub exporters: Vec<ExporterStats>,
+}
+
+/// Delivery counters of one [`Exporter`](crate::Exporter), in [`TracerStats::exporters`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExporterStats {
+    /// The exporter's [`Exporter::name`](crate::Exporter::name)
+    pub name: String,
+    /// Spans delivered by this exporter
+    pub spans_sent: u64,
+    /// Spans this exporter lost to a full queue or a batch that failed for good
+    pub spans_dropped: u64,
+    /// Batches given up on after every retry failed
+    pub batches_failed: u64,
+    /// Send attempts repeated after a failure
+    pub batches_retried: u64,
+    /// Spans waiting in this exporter's queue
+    pub queue_depth: usize,
+    /// When this exporter last delivered a batch
+    pub last_flush: Option<SystemTime>,
+    /// Whether this exporter's batches are getting through
+    pub connection: ConnectionState,
+}
+
+impl TracerStats {
+    /// Fraction of finished sampled spans that were dropped, from 0 to 1
+    pub fn drop_rate(&self) -> f64 {
+        drop_rate(self.spans_dropped, self.spans_sent + self.spans_dropped)
+    }
+}
+
+/// Connection state reported in [`TracerStats`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConnectionState {
+    /// Batches are being delivered
+    Connected,
+    /// The last batch failed; later ones are still attempted
+    Failing,
+    /// The session with the server closed; spans can't be delivered
+    Disconnected,
+    /// Nothing to connect to (no-op and in-memory tracers)
+    Local,
+}
+
+impl ConnectionState {
+    fn from_u8(value: u8) -> Self {
+        match value {
+            0 => Self::Connected,
+            1 => Self::Failing,
+            2 => Self::Disconnected,
+            _ => Self::Local,
+        }
+    }
+
+    fn as_u8(self) -> u8 {
+        match self {
+            Self::Connected => 0,
+            Self::Failing => 1,
+            Self::Disconnected => 2,
+            Self::Local => 3,
+        }
+    }
+}
+
+/// Live counters behind [`TracerStats`], for the whole tracer or for one
+/// exporter
+pub(crate) struct StatsCounters {
+    spans_created: AtomicU64,
+    spans_sampled: AtomicU64,
+    /// Finished spans handed to the exporter, queued or not
+    spans_finished: AtomicU64,
+    spans_sent: AtomicU64,
+    spans_dropped: AtomicU64,
+    spans_filtered: AtomicU64,
+    batches_failed: AtomicU64,
+    batches_retried: AtomicU64,
+    queue_depth: AtomicUsize,
+    /// Nanoseconds since the UNIX epoch; 0 means never
+    last_flush: AtomicU64,
+    connection: AtomicU8,
+}
+
+impl StatsCounters {
+    pub(crate) fn new(connection: ConnectionState) -> Self {
+        Self {
+            spans_created: AtomicU64::new(0),
+            spans_sampled: AtomicU64::new(0),
+            spans_finished: AtomicU64::new(0),
+            spans_sent: AtomicU64::new(0),
+            spans_dropped: AtomicU64::new(0),
+            spans_filtered: AtomicU64::new(0),
+            batches_failed: AtomicU64::new(0),
+            batches_retried: AtomicU64::new(0),
+            queue_depth: AtomicUsize::new(0),
+            last_flush: AtomicU64::new(0),
+            connection: AtomicU8::new(connection.as_u8()),
+        }
+    }
+
+    pub(crate) fn span_created(&self, sampled: bool) {
+        self.spans_created.fetch_add(1, Ordering::Relaxed);
+        if sampled {
+            self.spans_sampled.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn span_finished(&self) {
+        self.spans_finished.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn span_queued(&self) {
+        self.queue_depth.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn span_dequeued(&self) {
+        self.queue_depth.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn spans_dropped(&self, count: u64) {
+        self.spans_dropped.fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub(crate) fn span_filtered(&self) {
+        self.spans_filtered.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Spans recorded without a network round trip (in-memory tracer)
+    pub(crate) fn spans_recorded(&self, count: u64) {
+        self.spans_sent.fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub(crate) fn batch_sent(&self, spans: u64) {
+        self.spans_sent.fetch_add(spans, Ordering::Relaxed);
+        // `Timestamp::now` rather than `SystemTime::now`, which panics in browsers
+        self.last_flush
+            .store(Timestamp::now().0.max(1), Ordering::Relaxed);
+        self.transition(ConnectionState::Failing, ConnectionState::Connected);
+    }
+
+    pub(crate) fn batch_retried(&self) {
+        self.batches_retried.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn batch_failed(&self, spans: u64) {
+        self.batches_failed.fetch_add(1, Ordering::Relaxed);
+        self.spans_dropped(spans);
+    }
+
+    /// Note a failed send attempt; true if the connection was healthy until now
+    pub(crate) fn send_failed(&self) -> bool {
+        self.transition(ConnectionState::Connected, ConnectionState::Failing)
+    }
+
+    pub(crate) fn disconnected(&self) {
+        self.connection
+            .store(ConnectionState::Disconnected.as_u8(), Ordering::Relaxed);
+    }
+
+    pub(crate) fn is_disconnected(&self) -> bool {
+        self.connection.load(Ordering::Relaxed) == ConnectionState::Disconnected.as_u8()
+    }
+
+    fn transition(&self, from: ConnectionState, to: ConnectionState) -> bool {
+        self.connection
+            .compare_exchange(
+                from.as_u8(),
+                to.as_u8(),
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            )
+            .is_ok()
+    }
+
+    /// The tracer's stats: its own counters plus every exporter's
+    pub(crate) fn snapshot(&self, exporters: &[ExporterCounters]) -> TracerStats {
+        let mut stats = TracerStats {
+            spans_created: self.spans_created.load(Ordering::Relaxed),
+            spans_sampled: self.spans_sampled.load(Ordering::Relaxed),
+            spans_sent: self.spans_sent.load(Ordering::Relaxed),
+            spans_dropped: self.spans_dropped.load(Ordering::Relaxed),
+            spans_filtered: self.spans_filtered.load(Ordering::Relaxed),
+            batches_failed: self.batches_failed.load(Ordering::Relaxed),
+            batches_retried: self.batches_retried.load(Ordering::Relaxed),
+            queue_depth: self.queue_depth.load(Ordering::Relaxed),
+            last_flush: self.last_flush(),
+            connection: self.connection(),
+            exporters: Vec::with_capacity(exporters.len()),
+        };
+        for exporter in exporters {
+            let counters = &exporter.counters;
+            let exporter = ExporterStats {
+                name: exporter.name.clone(),
+                spans_sent: counters.spans_sent.load(Ordering::Relaxed),
+                spans_dropped: counters.spans_dropped.load(Ordering::Relaxed),
+                batches_failed: counters.batches_failed.load(Ordering::Relaxed),
+                batches_retried: counters.batches_retried.load(Ordering::Relaxed),
+                queue_depth: counters.queue_depth.load(Ordering::Relaxed),
+                last_flush: counters.last_flush(),
+                connection: counters.connection(),
+            };
+            stats.spans_sent += exporter.spans_sent;
+            stats.spans_dropped += exporter.spans_dropped;
+            stats.batches_failed += exporter.batches_failed;
+            stats.batches_retried += exporter.batches_retried;
+            stats.queue_depth += exporter.queue_depth;
+            stats.last_flush = stats.last_flush.max(exporter.last_flush);
+            stats.exporters.push(exporter);
+        }
+        if let Some(first) = stats.exporters.first() {
+            stats.connection = first.connection;
+        }
+        stats
+    }
+
+    fn last_flush(&self) -> Option<SystemTime> {
+        match self.last_flush.load(Ordering::Relaxed) {
+            0 => None,
+            nanos => Some(UNIX_EPOCH + Duration::from_nanos(nanos)),
+        }
+    }
+
+    fn connection(&self) -> ConnectionState {
+        ConnectionState::from_u8(self.connection.load(Ordering::Relaxed))
+    }
+}
+
+/// One exporter's counters, with its name
+#[derive(Clone)]
+pub(crate) struct ExporterCounters {
+    pub name: String,
+    pub counters: Arc<StatsCounters>,
+}
+
+/// Callback for [`TracerBuilder::on_drop_rate`](crate::TracerBuilder::on_drop_rate)
+#[derive(Clone)]
+pub(crate) struct DropRateCallback {
+    pub threshold: f64,
+    pub callback: Arc<dyn Fn(f64, &TracerStats) + Send + Sync>,
+}
+
+impl fmt::Debug for DropRateCallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DropRateCallback")
+            .field("threshold", &self.threshold)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Window over which the drop rate is measured for the callback
+pub(crate) const DROP_RATE_WINDOW: Duration = Duration::from_secs(10);
+
+/// Fires the drop-rate callback when the rate over the last window crosses
+/// the threshold, and again only after it has recovered
+pub(crate) struct DropRateMonitor {
+    callback: DropRateCallback,
+    finished: u64,
+    dropped: u64,
+    above: bool,
+}
+
+impl DropRateMonitor {
+    pub(crate) fn new(callback: DropRateCallback) -> Self {
+        Self {
+            callback,
+            finished: 0,
+            dropped: 0,
+            above: false,
+        }
+    }
+
+    pub(crate) fn check(&mut self, counters: &StatsCounters, exporters: &[ExporterCounters]) {
+        let all = || std::iter::once(counters).chain(exporters.iter().map(|e| &*e.counters));
+        let finished: u64 = all()
+            .map(|c| c.spans_finished.load(Ordering::Relaxed))
+            .sum();
+        let dropped: u64 = all().map(|c| c.spans_dropped.load(Ordering::Relaxed)).sum();
+        let window_dropped = dropped - self.dropped;
+        let rate = drop_rate(window_dropped, finished - self.finished);
+        self.finished = finished;
+        self.dropped = dropped;
+
+        let above = window_dropped > 0 && rate >= self.callback.threshold;
+        if above && !self.above {
+            (self.callback.callback)(rate, &counters.snapshot(exporters));
+        }
+        self.above = above;
+    }
+}
+
+fn drop_rate(dropped: u64, total: u64) -> f64 {
+    if dropped == 0 {
+        0.0
+    } else {
+        // Batches that fail for good can drop spans finished in an earlier window
+        (dropped as f64 / total.max(dropped) as f64).min(1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// One window: `finished` spans, `dropped` of them lost
+    fn window(counters: &StatsCounters, finished: u64, dropped: u64) {
+        for _ in 0..finished {
+            counters.span_finished();
+        }
+        counters.spans_dropped(dropped);
+    }
+
+    #[test]
+    fn test_drop_rate_monitor() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let recorded = calls.clone();
+        let mut monitor = DropRateMonitor::new(DropRateCallback {
+            threshold: 0.5,
+            callback: Arc::new(move |rate, _| recorded.lock().unwrap().push(rate)),
+        });
+        let counters = StatsCounters::new(ConnectionState::Connected);
+
+        // Below the threshold
+        window(&counters, 10, 2);
+        monitor.check(&counters, &[]);
+        assert!(calls.lock().unwrap().is_empty());
+
+        // Crossing it fires once, however long the rate stays high
+        window(&counters, 10, 6);
+        monitor.check(&counters, &[]);
+        window(&counters, 10, 8);
+        monitor.check(&counters, &[]);
+        assert_eq!(*calls.lock().unwrap(), [0.6]);
+
+        // Recovering re-arms it
+        window(&counters, 10, 0);
+        monitor.check(&counters, &[]);
+        window(&counters, 10, 10);
+        monitor.check(&counters, &[]);
+        assert_eq!(*calls.lock().unwrap(), [0.6, 1.0]);
+    }
+}
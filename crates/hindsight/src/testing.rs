@@ -0,0 +1,259 @@
+use hindsight_protocol::*;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use crate::span_builder::IntoAttributeValue;
+
+/// Spans collected by a [`Tracer::in_memory`](crate::Tracer::in_memory) tracer
+///
+/// Spans are recorded synchronously when they end: no server, no batching,
+/// no sleeps. Clones share the same buffer.
+///
+/// # Example
+/// ```
+/// use hindsight::{SpanMatcher, Tracer};
+///
+/// let (tracer, spans) = Tracer::in_memory();
+/// # if tracer.is_noop() { return; } // `noop` feature: nothing is recorded
+///
+/// let request = tracer.span("handle_request").start();
+/// tracer
+///     .span("load_user")
+///     .with_parent(request.context().clone())
+///     .with_attribute("user_id", 42)
+///     .start()
+///     .end();
+/// request.end();
+///
+/// spans.assert_has(
+///     SpanMatcher::named("load_user")
+///         .with_attribute("user_id", 42)
+///         .with_parent("handle_request"),
+/// );
+/// ```
+#[derive(Clone, Default)]
+pub struct InMemorySpans {
+    spans: Arc<Mutex<Vec<Span>>>,
+}
+
+impl InMemorySpans {
+    pub(crate) fn record(&self, span: Span) {
+        self.lock().push(span);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<Span>> {
+        self.spans.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Finished spans, in the order they ended
+    pub fn spans(&self) -> Vec<Span> {
+        self.lock().clone()
+    }
+
+    /// Number of finished spans
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Whether no span has finished yet
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    /// Forget every recorded span
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    /// Finished spans grouped into traces, oldest first
+    ///
+    /// Traces whose root span hasn't ended yet are left out.
+    pub fn traces(&self) -> Vec<Trace> {
+        let mut by_trace: HashMap<TraceId, Vec<Span>> = HashMap::new();
+        for span in self.lock().iter() {
+            by_trace
+                .entry(span.trace_id)
+                .or_default()
+                .push(span.clone());
+        }
+        let mut traces: Vec<Trace> = by_trace
+            .into_values()
+            .filter_map(Trace::from_spans)
+            .collect();
+        traces.sort_by_key(|trace| trace.start_time.0);
+        traces
+    }
+
+    /// First finished span named `name`
+    pub fn find(&self, name: &str) -> Option<Span> {
+        self.lock().iter().find(|span| span.name == name).cloned()
+    }
+
+    /// Finished spans matching `matcher`
+    pub fn matching(&self, matcher: &SpanMatcher) -> Vec<Span> {
+        let spans = self.lock();
+        spans
+            .iter()
+            .filter(|span| matcher.matches(span, &spans))
+            .cloned()
+            .collect()
+    }
+
+    /// Whether any finished span matches `matcher`
+    pub fn has(&self, matcher: &SpanMatcher) -> bool {
+        !self.matching(matcher).is_empty()
+    }
+
+    /// Panic, listing the recorded spans, unless a span matches `matcher`
+    #[track_caller]
+    pub fn assert_has(&self, matcher: SpanMatcher) {
+        if !self.has(&matcher) {
+            panic!(
+                "no span matches {}\nrecorded spans:\n{}",
+                matcher,
+                self.describe()
+            );
+        }
+    }
+
+    /// Panic, listing the offending spans, if any span matches `matcher`
+    #[track_caller]
+    pub fn assert_none(&self, matcher: SpanMatcher) {
+        let found = self.matching(&matcher);
+        if !found.is_empty() {
+            panic!(
+                "expected no span to match {}, found {}\nrecorded spans:\n{}",
+                matcher,
+                found.len(),
+                self.describe()
+            );
+        }
+    }
+
+    fn describe(&self) -> String {
+        let spans = self.lock();
+        if spans.is_empty() {
+            return "  (none)".to_string();
+        }
+        spans
+            .iter()
+            .map(|span| {
+                let parent = span
+                    .parent_span_id
+                    .and_then(|id| spans.iter().find(|s| s.span_id == id))
+                    .map(|parent| parent.name.as_str())
+                    .unwrap_or("-");
+                format!("  {} (parent: {}) {:?}", span.name, parent, span.attributes)
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Description of a span to look for in [`InMemorySpans`]
+#[derive(Clone, Debug)]
+pub struct SpanMatcher {
+    name: String,
+    attributes: Vec<(String, AttributeValue)>,
+    parent: Option<ParentMatcher>,
+    error: Option<bool>,
+}
+
+#[derive(Clone, Debug)]
+enum ParentMatcher {
+    Root,
+    Named(String),
+}
+
+impl SpanMatcher {
+    /// Match spans named `name`
+    pub fn named(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            attributes: Vec::new(),
+            parent: None,
+            error: None,
+        }
+    }
+
+    /// ...that have attribute `key` set to `value`
+    pub fn with_attribute(
+        mut self,
+        key: impl Into<String>,
+        value: impl IntoAttributeValue,
+    ) -> Self {
+        self.attributes
+            .push((key.into(), value.into_attribute_value()));
+        self
+    }
+
+    /// ...whose parent is a finished span named `parent`
+    pub fn with_parent(mut self, parent: impl Into<String>) -> Self {
+        self.parent = Some(ParentMatcher::Named(parent.into()));
+        self
+    }
+
+    /// ...that have no parent
+    pub fn root(mut self) -> Self {
+        self.parent = Some(ParentMatcher::Root);
+        self
+    }
+
+    /// ...that ended with an error status
+    pub fn errored(mut self) -> Self {
+        self.error = Some(true);
+        self
+    }
+
+    /// ...that ended with an ok status
+    pub fn ok(mut self) -> Self {
+        self.error = Some(false);
+        self
+    }
+
+    fn matches(&self, span: &Span, all: &[Span]) -> bool {
+        if span.name != self.name {
+            return false;
+        }
+        if !self
+            .attributes
+            .iter()
+            .all(|(key, value)| span.attributes.get(key) == Some(value))
+        {
+            return false;
+        }
+        if let Some(error) = self.error {
+            if matches!(span.status, SpanStatus::Error { .. }) != error {
+                return false;
+            }
+        }
+        match &self.parent {
+            None => true,
+            Some(ParentMatcher::Root) => span.parent_span_id.is_none(),
+            Some(ParentMatcher::Named(name)) => span
+                .parent_span_id
+                .and_then(|id| all.iter().find(|s| s.span_id == id))
+                .is_some_and(|parent| &parent.name == name),
+        }
+    }
+}
+
+impl fmt::Display for SpanMatcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.name)?;
+        for (key, value) in &self.attributes {
+            write!(f, " with {}={:?}", key, value)?;
+        }
+        match &self.parent {
+            Some(ParentMatcher::Root) => write!(f, " at the root")?,
+            Some(ParentMatcher::Named(name)) => write!(f, " under {:?}", name)?,
+            None => {}
+        }
+        match self.error {
+            Some(true) => write!(f, " (errored)"),
+            Some(false) => write!(f, " (ok)"),
+            None => Ok(()),
+        }
+    }
+}
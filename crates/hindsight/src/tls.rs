@@ -0,0 +1,222 @@
+use std::path::Path;
+use std::sync::Arc;
+use tokio::net::TcpStream;
+use tokio_rustls::client::TlsStream;
+use tokio_rustls::rustls::client::danger::{
+    HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier,
+};
+use tokio_rustls::rustls::crypto::{self, CryptoProvider};
+use tokio_rustls::rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use tokio_rustls::rustls::{
+    self, ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme,
+};
+use tokio_rustls::TlsConnector;
+
+use crate::tracer::TracerError;
+
+/// TLS settings for connections to the server
+///
+/// By default the server certificate is checked against the public web PKI
+/// roots. Add a private CA with [`TlsConfig::add_ca_pem`], or trust one
+/// exact (e.g. self-signed) certificate with [`TlsConfig::pin_cert_pem`].
+///
+/// # Example
+/// ```no_run
+/// # async fn example() -> Result<(), hindsight::TracerError> {
+/// use hindsight::{TlsConfig, Tracer};
+///
+/// let tracer = Tracer::builder()
+///     .endpoint("traces.example.com:1993")
+///     .tls(TlsConfig::new().add_ca_pem_file("ca.pem")?)
+///     .bearer_token("s3cret")
+///     .connect()
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct TlsConfig {
+    ca_certs: Vec<CertificateDer<'static>>,
+    pinned_certs: Vec<CertificateDer<'static>>,
+    server_name: Option<String>,
+}
+
+impl TlsConfig {
+    /// Verify the server against the public web PKI roots
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Also trust certificates issued by the CA(s) in `pem`
+    pub fn add_ca_pem(mut self, pem: &[u8]) -> Result<Self, TracerError> {
+        self.ca_certs.extend(parse_certs(pem)?);
+        Ok(self)
+    }
+
+    /// Also trust certificates issued by the CA(s) in the PEM file at `path`
+    pub fn add_ca_pem_file(self, path: impl AsRef<Path>) -> Result<Self, TracerError> {
+        let pem = read_pem(path.as_ref())?;
+        self.add_ca_pem(&pem)
+    }
+
+    /// Trust exactly the certificate(s) in `pem` and nothing else
+    ///
+    /// For self-signed server certificates. The name in the certificate is
+    /// not checked; the certificate itself has to match.
+    pub fn pin_cert_pem(mut self, pem: &[u8]) -> Result<Self, TracerError> {
+        self.pinned_certs.extend(parse_certs(pem)?);
+        Ok(self)
+    }
+
+    /// Trust exactly the certificate(s) in the PEM file at `path`
+    pub fn pin_cert_pem_file(self, path: impl AsRef<Path>) -> Result<Self, TracerError> {
+        let pem = read_pem(path.as_ref())?;
+        self.pin_cert_pem(&pem)
+    }
+
+    /// Name to verify the certificate against (defaults to the endpoint host)
+    pub fn server_name(mut self, server_name: impl Into<String>) -> Self {
+        self.server_name = Some(server_name.into());
+        self
+    }
+
+    /// Run the TLS handshake over `stream`
+    pub(crate) async fn connect(
+        &self,
+        endpoint: &str,
+        stream: TcpStream,
+    ) -> Result<TlsStream<TcpStream>, TracerError> {
+        let host = match &self.server_name {
+            Some(name) => name.clone(),
+            None => endpoint_host(endpoint).to_string(),
+        };
+        let server_name = ServerName::try_from(host.clone()).map_err(|_| {
+            TracerError::InvalidConfig(format!("invalid TLS server name {:?}", host))
+        })?;
+
+        TlsConnector::from(Arc::new(self.client_config()?))
+            .connect(server_name, stream)
+            .await
+            .map_err(|e| TracerError::ConnectionFailed(format!("TLS handshake failed: {}", e)))
+    }
+
+    fn client_config(&self) -> Result<ClientConfig, TracerError> {
+        let provider = Arc::new(crypto::ring::default_provider());
+        let builder = ClientConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()
+            .map_err(|e| TracerError::InvalidConfig(format!("TLS setup failed: {}", e)))?;
+
+        let config = if self.pinned_certs.is_empty() {
+            let mut roots = RootCertStore::empty();
+            roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+            for cert in &self.ca_certs {
+                roots.add(cert.clone()).map_err(|e| {
+                    TracerError::InvalidConfig(format!("invalid CA certificate: {}", e))
+                })?;
+            }
+            builder.with_root_certificates(roots).with_no_client_auth()
+        } else {
+            builder
+                .dangerous()
+                .with_custom_certificate_verifier(Arc::new(PinnedCertVerifier {
+                    pinned: self.pinned_certs.clone(),
+                    provider,
+                }))
+                .with_no_client_auth()
+        };
+        Ok(config)
+    }
+}
+
+/// Host part of `host:port` or `[v6]:port`
+fn endpoint_host(endpoint: &str) -> &str {
+    if let Some(rest) = endpoint.strip_prefix('[') {
+        return rest.split(']').next().unwrap_or(rest);
+    }
+    match endpoint.rsplit_once(':') {
+        Some((host, _port)) => host,
+        None => endpoint,
+    }
+}
+
+fn read_pem(path: &Path) -> Result<Vec<u8>, TracerError> {
+    std::fs::read(path).map_err(|e| {
+        TracerError::InvalidConfig(format!("failed to read {}: {}", path.display(), e))
+    })
+}
+
+fn parse_certs(pem: &[u8]) -> Result<Vec<CertificateDer<'static>>, TracerError> {
+    let certs = rustls_pemfile::certs(&mut &pem[..])
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| TracerError::InvalidConfig(format!("invalid PEM certificate: {}", e)))?;
+    if certs.is_empty() {
+        return Err(TracerError::InvalidConfig(
+            "no certificates found in PEM data".to_string(),
+        ));
+    }
+    Ok(certs)
+}
+
+/// Accepts exactly the pinned certificates, still checking handshake signatures
+#[derive(Debug)]
+struct PinnedCertVerifier {
+    pinned: Vec<CertificateDer<'static>>,
+    provider: Arc<CryptoProvider>,
+}
+
+impl ServerCertVerifier for PinnedCertVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        if self
+            .pinned
+            .iter()
+            .any(|pinned| pinned.as_ref() == end_entity.as_ref())
+        {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            Err(rustls::Error::InvalidCertificate(
+                rustls::CertificateError::UnknownIssuer,
+            ))
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
@@ -1,17 +1,88 @@
 use hindsight_protocol::*;
+#[cfg(feature = "client")]
 use rapace::{RpcSession, Transport};
+#[cfg(target_arch = "wasm32")]
+use std::cell::OnceCell;
+use std::collections::BTreeMap;
+use std::future::Future;
 use std::sync::Arc;
-use tokio::sync::mpsc;
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::OnceLock;
+
+#[cfg(feature = "client")]
+use crate::builder::TransportKind;
+use crate::builder::{TracerBuilder, TracerConfig};
+#[cfg(feature = "client")]
+use crate::context::untraced;
+use crate::context::Instrument;
+#[cfg(feature = "client")]
+use crate::exporter::{Exporter, FanOut, RapaceExporter};
+#[cfg(all(feature = "client", not(target_arch = "wasm32")))]
+use crate::file_exporter::FileExporter;
+use crate::open_spans::OpenSpans;
+use crate::processor::SpanProcessors;
+#[cfg(feature = "client")]
+use crate::runtime::{self, Interval};
+use crate::sampler::Sampler;
+use crate::span_builder::SpanBuilder;
+use crate::stats::{ConnectionState, ExporterCounters, StatsCounters, TracerStats};
+#[cfg(feature = "client")]
+use crate::stats::{DropRateMonitor, DROP_RATE_WINDOW};
+use crate::testing::InMemorySpans;
+
+#[cfg(not(target_arch = "wasm32"))]
+static GLOBAL_TRACER: OnceLock<Tracer> = OnceLock::new();
+
+// Browser tracers hold JS objects and can't live in a `static`; there is
+// only one thread anyway
+#[cfg(target_arch = "wasm32")]
+thread_local! {
+    static GLOBAL_TRACER: OnceCell<&'static Tracer> = const { OnceCell::new() };
+}
+
+/// Get the tracer installed with [`Tracer::install_global`], if any
+#[cfg(not(target_arch = "wasm32"))]
+pub fn global_tracer() -> Option<&'static Tracer> {
+    GLOBAL_TRACER.get()
+}
+
+/// Get the tracer installed with [`Tracer::install_global`], if any
+#[cfg(target_arch = "wasm32")]
+pub fn global_tracer() -> Option<&'static Tracer> {
+    GLOBAL_TRACER.with(|global| global.get().copied())
+}
 
 /// Main entry point for sending spans
+#[derive(Clone)]
 pub struct Tracer {
     inner: Arc<TracerInner>,
 }
 
 struct TracerInner {
     service_name: String,
-    span_tx: mpsc::UnboundedSender<Span>,
-    _session: Arc<dyn std::any::Any + Send + Sync>,
+    resource_attributes: BTreeMap<String, AttributeValue>,
+    sampler: Sampler,
+    processors: SpanProcessors,
+    /// Parent of spans started without one (see
+    /// [`TracerBuilder::parent_context`])
+    parent: Option<TraceContext>,
+    sink: SpanSink,
+    open_spans: OpenSpans,
+    /// Send in-flight records of open spans (see
+    /// [`TracerBuilder::heartbeat_interval`])
+    heartbeats: bool,
+    stats: Arc<StatsCounters>,
+}
+
+/// Where finished spans go
+enum SpanSink {
+    /// Dropped (see [`Tracer::noop`])
+    Noop,
+    /// Queued for each exporter's batching task
+    #[cfg(feature = "client")]
+    Batched(FanOut),
+    /// Recorded synchronously (see [`Tracer::in_memory`])
+    InMemory(InMemorySpans),
 }
 
 impl Tracer {
@@ -28,70 +99,102 @@ impl Tracer {
     /// # Ok(())
     /// # }
     /// ```
+    #[cfg(all(feature = "client", not(target_arch = "wasm32")))]
     pub async fn connect_http(addr: impl AsRef<str>) -> Result<Self, TracerError> {
-        use tokio::io::{AsyncReadExt, AsyncWriteExt};
-        use tokio::net::TcpStream;
-
-        let addr = addr.as_ref();
-
-        // Connect to server
-        let mut stream = TcpStream::connect(addr).await.map_err(|e| {
-            TracerError::ConnectionFailed(format!("Failed to connect to {}: {}", addr, e))
-        })?;
-
-        // Send HTTP upgrade request
-        let host = addr.split(':').next().unwrap_or("localhost");
-        let request = format!(
-            "GET / HTTP/1.1\r\n\
-             Host: {}\r\n\
-             Upgrade: rapace\r\n\
-             Connection: Upgrade\r\n\
-             \r\n",
-            host
-        );
-
-        stream.write_all(request.as_bytes()).await.map_err(|e| {
-            TracerError::ConnectionFailed(format!("Failed to send upgrade request: {}", e))
-        })?;
-
-        // Read response until we get \r\n\r\n
-        let mut response = Vec::new();
-        let mut buf = [0u8; 1];
+        TracerBuilder::new()
+            .transport(TransportKind::HttpUpgrade)
+            .endpoint(addr.as_ref())
+            .connect()
+            .await
+    }
 
-        loop {
-            stream.read_exact(&mut buf).await.map_err(|e| {
-                TracerError::ConnectionFailed(format!("Failed to read upgrade response: {}", e))
-            })?;
-            response.push(buf[0]);
-
-            // Check for \r\n\r\n
-            if response.len() >= 4 && response[response.len() - 4..] == [b'\r', b'\n', b'\r', b'\n']
-            {
-                break;
-            }
+    /// Connect to a Hindsight server through its Unix socket
+    ///
+    /// Useful where TCP ports are awkward to expose, such as dev containers.
+    /// Access is governed by the socket file's permissions.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # async fn example() -> Result<(), hindsight::TracerError> {
+    /// let tracer =
+    ///     hindsight::Tracer::connect_unix("/run/user/1000/hindsight/hindsight.sock").await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(all(feature = "client", not(target_arch = "wasm32")))]
+    pub async fn connect_unix(path: impl AsRef<std::path::Path>) -> Result<Self, TracerError> {
+        TracerBuilder::new()
+            .transport(TransportKind::Unix)
+            .endpoint(path.as_ref().to_string_lossy())
+            .connect()
+            .await
+    }
 
-            // Prevent infinite loop on malformed response
-            if response.len() > 8192 {
-                return Err(TracerError::ConnectionFailed(
-                    "HTTP upgrade response too large".to_string(),
-                ));
-            }
-        }
+    /// Connect to a Hindsight server on this host over shared memory
+    ///
+    /// A server started with `hindsight serve --shm` advertises a rendezvous
+    /// socket under `$XDG_RUNTIME_DIR/hindsight`; spans then travel through a
+    /// shared-memory segment instead of TCP, which suits high-volume
+    /// exporters such as picante.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # async fn example() -> Result<(), hindsight::TracerError> {
+    /// let tracer = hindsight::Tracer::connect_shm().await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(all(feature = "client", not(target_arch = "wasm32")))]
+    pub async fn connect_shm() -> Result<Self, TracerError> {
+        TracerBuilder::new()
+            .transport(TransportKind::Shm)
+            .connect()
+            .await
+    }
 
-        // Parse response - look for "HTTP/1.1 101"
-        let response_str = String::from_utf8_lossy(&response);
-        if !response_str.contains("101") && !response_str.contains("Switching Protocols") {
-            return Err(TracerError::ConnectionFailed(format!(
-                "HTTP upgrade failed: {}",
-                response_str.lines().next().unwrap_or("unknown error")
-            )));
-        }
+    /// Connect to a Hindsight server over WebSocket
+    ///
+    /// `endpoint` is `host:port` (in browsers, a `ws://` or `wss://` URL
+    /// works too). This is the transport available in browsers (`wasm32`),
+    /// where the spans of a front-end join the traces of the services it
+    /// calls.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # async fn example() -> Result<(), hindsight::TracerError> {
+    /// let tracer = hindsight::Tracer::connect_websocket("localhost:1990").await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "client")]
+    pub async fn connect_websocket(endpoint: impl AsRef<str>) -> Result<Self, TracerError> {
+        TracerBuilder::new()
+            .transport(TransportKind::WebSocket)
+            .endpoint(endpoint.as_ref())
+            .connect()
+            .await
+    }
 
-        // HTTP upgrade successful, switching to Rapace protocol
+    /// Connect using `HINDSIGHT_*` environment variables
+    ///
+    /// Shorthand for `Tracer::builder().with_env()?.connect()`; see
+    /// [`TracerBuilder`] for the variables and their defaults.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # async fn example() -> Result<(), hindsight::TracerError> {
+    /// let tracer = hindsight::Tracer::from_env().await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "client")]
+    pub async fn from_env() -> Result<Self, TracerError> {
+        TracerBuilder::new().with_env()?.connect().await
+    }
 
-        // Create transport from the upgraded stream
-        let transport = Transport::stream(stream);
-        Self::new(transport).await
+    /// Configure a tracer (batching, sampling, transport...)
+    pub fn builder() -> TracerBuilder {
+        TracerBuilder::new()
     }
 
     /// Connect to a Hindsight server via Rapace
@@ -105,90 +208,468 @@ impl Tracer {
     /// let transport = rapace::Transport::stream(stream);
     /// let tracer = Tracer::new(transport).await?;
     ///
-    /// // SHM transport (for same-machine communication)
-    /// // let (client, server) = rapace::Transport::shm_pair();
-    /// // let tracer = Tracer::new(client).await?;
+    /// // For a server on the same machine, see `Tracer::connect_shm`
     /// # Ok(())
     /// # }
     /// ```
+    #[cfg(feature = "client")]
     pub async fn new(transport: Transport) -> Result<Self, TracerError> {
-        // Detect service name (from env, or default)
-        let service_name =
-            std::env::var("HINDSIGHT_SERVICE_NAME").unwrap_or_else(|_| "unknown".to_string());
+        TracerBuilder::new().build(transport).await
+    }
 
+    #[cfg(feature = "client")]
+    pub(crate) async fn start(
+        transport: Transport,
+        config: TracerConfig,
+    ) -> Result<Self, TracerError> {
         // Create Rapace session
         // IMPORTANT: Do NOT attach a tracer to this session!
         // (Prevents infinite loop)
         let session = Arc::new(RpcSession::new(transport));
 
+        let exporter = RapaceExporter::new(session.clone());
+        let tracer = Self::with_exporters(config, Some(Arc::new(exporter)));
+
         // Spawn session runner
-        let session_clone = session.clone();
-        tokio::spawn(async move {
-            if let Err(e) = session_clone.run().await {
+        let stats = tracer.exporter_counters()[0].counters.clone();
+        runtime::spawn(untraced(async move {
+            if let Err(e) = session.run().await {
                 eprintln!("Hindsight client session error: {:?}", e);
             }
+            stats.disconnected();
+        }));
+
+        Ok(tracer)
+    }
+
+    /// Start a tracer that appends spans to a local file instead of a server
+    #[cfg(all(feature = "client", not(target_arch = "wasm32")))]
+    pub(crate) fn start_file(exporter: FileExporter, config: TracerConfig) -> Self {
+        Self::with_exporters(config, Some(Arc::new(exporter)))
+    }
+
+    /// Start a tracer exporting to `first` (if any), then to the
+    /// exporters added to the builder
+    ///
+    /// Each exporter gets its own batching task (see [`Exporter`]).
+    #[cfg(feature = "client")]
+    pub(crate) fn with_exporters(config: TracerConfig, first: Option<Arc<dyn Exporter>>) -> Self {
+        let stats = Arc::new(StatsCounters::new(ConnectionState::Connected));
+        let fan_out = FanOut::start(first, &config);
+
+        let inner = Arc::new(TracerInner {
+            service_name: config.service_name,
+            resource_attributes: config.resource_attributes,
+            sampler: config.sampler,
+            processors: config.processors,
+            parent: config.parent,
+            sink: SpanSink::Batched(fan_out),
+            open_spans: OpenSpans::default(),
+            heartbeats: config.heartbeat_interval.is_some(),
+            stats,
         });
 
-        // Create Rapace client
-        let client = HindsightServiceClient::new(session.clone());
-
-        // Channel for buffering spans before sending
-        let (span_tx, mut span_rx) = mpsc::unbounded_channel();
-
-        // Background task to batch and send spans
-        tokio::spawn(async move {
-            let mut batch = Vec::new();
-            let mut interval = tokio::time::interval(std::time::Duration::from_millis(100));
-
-            loop {
-                tokio::select! {
-                    _ = interval.tick() => {
-                        if !batch.is_empty() {
-                            let spans = std::mem::take(&mut batch);
-                            let _ = client.ingest_spans(spans).await;
-                        }
-                    }
-                    Some(span) = span_rx.recv() => {
-                        batch.push(span);
-                        if batch.len() >= 100 {
-                            let spans = std::mem::take(&mut batch);
-                            let _ = client.ingest_spans(spans).await;
-                        }
-                    }
-                    else => break,
+        if let Some(period) = config.heartbeat_interval {
+            inner.open_spans.enable();
+            spawn_heartbeats(Arc::downgrade(&inner), period);
+        }
+        if let Some(callback) = config.on_drop_rate {
+            spawn_drop_monitor(Arc::downgrade(&inner), DropRateMonitor::new(callback));
+        }
+
+        Self { inner }
+    }
+
+    /// A tracer that records and sends nothing
+    ///
+    /// Spans from a no-op tracer skip attribute and event collection and are
+    /// never exported; they still pass their parent's context through, so
+    /// propagation across a no-op library keeps working. Needs no server and
+    /// no Tokio runtime, which makes it a good default for libraries:
+    ///
+    /// ```
+    /// use hindsight::Tracer;
+    ///
+    /// let tracer = Tracer::noop();
+    /// let span = tracer.span("parse").with_attribute("bytes", 1024).start();
+    /// span.end(); // nothing is sent
+    /// assert!(tracer.is_noop());
+    /// ```
+    ///
+    /// With the `noop` cargo feature, every tracer is a no-op tracer.
+    pub fn noop() -> Self {
+        Self {
+            inner: Arc::new(TracerInner {
+                service_name: String::new(),
+                resource_attributes: BTreeMap::new(),
+                sampler: Sampler::AlwaysOff,
+                processors: SpanProcessors::default(),
+                parent: None,
+                sink: SpanSink::Noop,
+                open_spans: OpenSpans::default(),
+                heartbeats: false,
+                stats: Arc::new(StatsCounters::new(ConnectionState::Local)),
+            }),
+        }
+    }
+
+    /// A tracer that records finished spans in memory, for tests
+    ///
+    /// Spans are available from the returned [`InMemorySpans`] as soon as
+    /// they end. Needs no server and no Tokio runtime. See
+    /// [`InMemorySpans`] for the assertion helpers.
+    ///
+    /// With the `noop` feature enabled, nothing is recorded.
+    pub fn in_memory() -> (Self, InMemorySpans) {
+        TracerBuilder::new().build_in_memory()
+    }
+
+    pub(crate) fn start_in_memory(config: TracerConfig) -> (Self, InMemorySpans) {
+        let spans = InMemorySpans::default();
+        let tracer = Self {
+            inner: Arc::new(TracerInner {
+                service_name: config.service_name,
+                resource_attributes: config.resource_attributes,
+                sampler: config.sampler,
+                processors: config.processors,
+                parent: config.parent,
+                sink: SpanSink::InMemory(spans.clone()),
+                open_spans: OpenSpans::default(),
+                heartbeats: false,
+                stats: Arc::new(StatsCounters::new(ConnectionState::Local)),
+            }),
+        };
+        (tracer, spans)
+    }
+
+    /// Whether this tracer discards everything (see [`Tracer::noop`])
+    #[cfg(not(feature = "noop"))]
+    #[inline]
+    pub fn is_noop(&self) -> bool {
+        matches!(self.inner.sink, SpanSink::Noop)
+    }
+
+    /// Whether this tracer discards everything: always, with the `noop`
+    /// feature
+    #[cfg(feature = "noop")]
+    #[inline]
+    pub fn is_noop(&self) -> bool {
+        true
+    }
+
+    /// Make this the process-wide tracer used by `#[instrument]`
+    ///
+    /// Returns false (and leaves the existing one in place) if a global
+    /// tracer was already installed.
+    pub fn install_global(&self) -> bool {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            GLOBAL_TRACER.set(self.clone()).is_ok()
+        }
+        #[cfg(target_arch = "wasm32")]
+        {
+            GLOBAL_TRACER.with(|global| {
+                global.get().is_none() && global.set(Box::leak(Box::new(self.clone()))).is_ok()
+            })
+        }
+    }
+
+    /// Start building a new span
+    pub fn span(&self, name: impl Into<String>) -> SpanBuilder {
+        SpanBuilder::new(name, self)
+    }
+
+    /// Submit a fully formed span, e.g. one parsed from a build log
+    ///
+    /// The span goes through the same pipeline as spans ended with
+    /// `ActiveSpan::end`. Its IDs and timestamps are kept as-is; an empty
+    /// `service_name` is filled in with this tracer's.
+    pub fn submit(&self, mut span: Span) {
+        if self.is_noop() || !self.inner.sampler.should_sample(span.trace_id) {
+            return;
+        }
+        if span.service_name.is_empty() {
+            span.service_name = self.inner.service_name.clone();
+        }
+        if let Some(end) = span.end_time {
+            span.end_time = Some(Timestamp(end.0.max(span.start_time.0)));
+        }
+        self.export(span);
+    }
+
+    /// Service name attached to spans from this tracer
+    pub fn service_name(&self) -> &str {
+        &self.inner.service_name
+    }
+
+    #[cfg_attr(feature = "noop", allow(dead_code))]
+    pub(crate) fn sampler(&self) -> Sampler {
+        self.inner.sampler
+    }
+
+    #[cfg_attr(feature = "noop", allow(dead_code))]
+    pub(crate) fn parent_context(&self) -> Option<&TraceContext> {
+        self.inner.parent.as_ref()
+    }
+
+    /// Hand a finished (and sampled) span to the batching task
+    ///
+    /// Runs the span processors first. Drops the span if the queue is full.
+    pub(crate) fn export(&self, span: Span) {
+        if let Some(span) = self.process(span) {
+            self.send(span);
+        }
+    }
+
+    /// Export an in-flight record of an open span, remembering what was
+    /// sent (see [`Tracer::export_finished`])
+    #[cfg_attr(feature = "noop", allow(dead_code))]
+    pub(crate) fn export_in_flight(&self, span: Span) {
+        if let Some(record) = self.process(span) {
+            if self.open_spans().in_flight_sent(&record) {
+                self.send(record);
+            }
+        }
+    }
+
+    /// Export a span that ended, given the last in-flight record sent for it
+    ///
+    /// If the processors drop the finished span after in-flight records of
+    /// it went out, that record is sent again, ended, so the span doesn't
+    /// look like it runs forever.
+    #[cfg_attr(feature = "noop", allow(dead_code))]
+    pub(crate) fn export_finished(&self, span: Span, in_flight: Option<Span>) {
+        let end_time = span.end_time;
+        match self.process(span) {
+            Some(span) => self.send(span),
+            None => {
+                if let Some(mut record) = in_flight {
+                    record.end_time = end_time;
+                    self.send(record);
                 }
             }
+        }
+    }
 
-            // Flush remaining spans on shutdown
-            if !batch.is_empty() {
-                let _ = client.ingest_spans(batch).await;
+    /// Add resource attributes and run the processors; None if they drop it
+    fn process(&self, mut span: Span) -> Option<Span> {
+        if self.is_noop() {
+            return None;
+        }
+        for (key, value) in &self.inner.resource_attributes {
+            span.attributes
+                .entry(key.clone())
+                .or_insert_with(|| value.clone());
+        }
+        if !self.inner.processors.process(&mut span) {
+            if !span.is_running() {
+                self.inner.stats.span_filtered();
             }
-        });
+            return None;
+        }
+        Some(span)
+    }
 
-        let inner = Arc::new(TracerInner {
-            service_name,
-            span_tx,
-            _session: session,
-        });
+    fn send(&self, span: Span) {
+        let stats = &self.inner.stats;
+        match &self.inner.sink {
+            SpanSink::Noop => {}
+            #[cfg(feature = "client")]
+            SpanSink::Batched(fan_out) => fan_out.send(span),
+            SpanSink::InMemory(spans) => {
+                if !span.is_running() {
+                    stats.span_finished();
+                }
+                spans.record(span);
+                stats.spans_recorded(1);
+            }
+        }
+    }
+
+    /// Counters describing the tracer's own health
+    ///
+    /// # Example
+    /// ```no_run
+    /// # fn example(tracer: &hindsight::Tracer) {
+    /// let stats = tracer.stats();
+    /// if stats.spans_dropped > 0 {
+    ///     eprintln!("lost {} spans ({:?})", stats.spans_dropped, stats.connection);
+    /// }
+    /// # }
+    /// ```
+    pub fn stats(&self) -> TracerStats {
+        self.inner.stats.snapshot(self.exporter_counters())
+    }
 
-        Ok(Self { inner })
+    /// Each exporter's counters (none for no-op and in-memory tracers)
+    fn exporter_counters(&self) -> &[ExporterCounters] {
+        match &self.inner.sink {
+            #[cfg(feature = "client")]
+            SpanSink::Batched(fan_out) => fan_out.counters(),
+            _ => &[],
+        }
     }
 
-    /// Start building a new span
-    pub fn span(&self, name: impl Into<String>) -> crate::span_builder::SpanBuilder {
-        crate::span_builder::SpanBuilder::new(
-            name.into(),
-            self.inner.service_name.clone(),
-            self.inner.span_tx.clone(),
-        )
+    #[cfg_attr(feature = "noop", allow(dead_code))]
+    pub(crate) fn stats_counters(&self) -> &StatsCounters {
+        &self.inner.stats
+    }
+
+    /// Send every queued span now, blocking until done or `timeout` passes
+    ///
+    /// Returns whether the flush finished in time. Meant for moments when
+    /// the process is about to go away (panics, `exit`). Avoid calling it
+    /// from a single-threaded runtime's thread: the batching task can't run
+    /// while it blocks, so it always waits for the full timeout there.
+    ///
+    /// Browsers can't block: on `wasm32` the flush is only requested, and
+    /// this returns false.
+    pub fn flush_blocking(&self, timeout: std::time::Duration) -> bool {
+        match &self.inner.sink {
+            SpanSink::Noop | SpanSink::InMemory(_) => true,
+            #[cfg(feature = "client")]
+            SpanSink::Batched(fan_out) => fan_out.flush_blocking(timeout),
+        }
+    }
+
+    pub(crate) fn open_spans(&self) -> &OpenSpans {
+        &self.inner.open_spans
+    }
+
+    /// Whether spans should be reported when they start
+    #[cfg_attr(feature = "noop", allow(dead_code))]
+    pub(crate) fn reports_in_flight(&self) -> bool {
+        self.inner.heartbeats
+    }
+
+    /// Run a future inside a new span
+    ///
+    /// The span is current while the future runs, so spans started inside it
+    /// become its children. It ends when the future completes.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # async fn example(tracer: hindsight::Tracer) {
+    /// let user = tracer
+    ///     .in_span("load_user", async {
+    ///         // Spans started here are children of "load_user"
+    ///         42
+    ///     })
+    ///     .await;
+    /// # }
+    /// ```
+    pub async fn in_span<F: Future>(&self, name: impl Into<String>, future: F) -> F::Output {
+        let span = self.span(name).start();
+        let output = future.instrument(span.context().clone()).await;
+        span.end();
+        output
     }
 }
 
+/// Periodically re-send every span that has been open for a full `period`
+///
+/// Stops once the tracer is dropped.
+#[cfg(feature = "client")]
+fn spawn_heartbeats(inner: std::sync::Weak<TracerInner>, period: std::time::Duration) {
+    runtime::spawn(untraced(async move {
+        let mut interval = Interval::new(period);
+        loop {
+            interval.tick().await;
+            let Some(inner) = inner.upgrade() else {
+                break;
+            };
+            let tracer = Tracer { inner };
+            let cutoff = Timestamp(Timestamp::now().0.saturating_sub(period.as_nanos() as u64));
+            for span in tracer.open_spans().snapshot(cutoff) {
+                tracer.export_in_flight(span);
+            }
+        }
+    }));
+}
+
+/// Check the drop rate every window and call the callback when it's high
+///
+/// Stops once the tracer is dropped.
+#[cfg(feature = "client")]
+fn spawn_drop_monitor(inner: std::sync::Weak<TracerInner>, mut monitor: DropRateMonitor) {
+    runtime::spawn(untraced(async move {
+        let mut interval = Interval::new(DROP_RATE_WINDOW);
+        loop {
+            interval.tick().await;
+            let Some(inner) = inner.upgrade() else {
+                break;
+            };
+            let tracer = Tracer { inner };
+            monitor.check(&tracer.inner.stats, tracer.exporter_counters());
+        }
+    }));
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum TracerError {
     #[error("failed to connect to server: {0}")]
     ConnectionFailed(String),
 
+    #[cfg(feature = "client")]
     #[error("transport error: {0}")]
     TransportError(#[from] rapace::TransportError),
+
+    #[error("invalid tracer configuration: {0}")]
+    InvalidConfig(String),
+}
+
+/// Create a shared-memory segment and hand it to the server listening on
+/// the rendezvous socket at `socket_path`
+#[cfg(all(feature = "client", unix))]
+pub(crate) async fn shm_connect(socket_path: &str) -> Result<Transport, TracerError> {
+    use rapace::transport::shm::{ShmSession, ShmSessionConfig};
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    static SEGMENTS: AtomicU64 = AtomicU64::new(0);
+
+    let mut stream = tokio::net::UnixStream::connect(socket_path)
+        .await
+        .map_err(|e| {
+            TracerError::ConnectionFailed(format!("Failed to connect to {}: {}", socket_path, e))
+        })?;
+
+    // The segment lives next to the socket, in the user's private runtime dir
+    let segment = std::path::Path::new(socket_path).with_file_name(format!(
+        "shm-{}-{}",
+        std::process::id(),
+        SEGMENTS.fetch_add(1, Ordering::Relaxed)
+    ));
+    let session = ShmSession::create_file(&segment, ShmSessionConfig::default()).map_err(|e| {
+        TracerError::ConnectionFailed(format!(
+            "Failed to create shared-memory segment {}: {:?}",
+            segment.display(),
+            e
+        ))
+    })?;
+
+    let handshake = async {
+        stream
+            .write_all(format!("{}\n", segment.display()).as_bytes())
+            .await?;
+        let mut reply = String::new();
+        BufReader::new(&mut stream).read_line(&mut reply).await?;
+        Ok::<_, std::io::Error>(reply)
+    }
+    .await;
+
+    // Both sides have it mapped (or the handshake failed); the file can go
+    let _ = std::fs::remove_file(&segment);
+
+    match handshake {
+        Ok(reply) if reply.trim_end() == "ok" => Ok(Transport::shm(session)),
+        Ok(reply) => Err(TracerError::ConnectionFailed(format!(
+            "Server rejected shared-memory segment: {}",
+            reply.trim_end()
+        ))),
+        Err(e) => Err(TracerError::ConnectionFailed(format!(
+            "Shared-memory handshake failed: {}",
+            e
+        ))),
+    }
 }
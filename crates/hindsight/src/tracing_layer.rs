@@ -0,0 +1,208 @@
+//! Bridge from the `tracing` crate to hindsight.
+
+use hindsight_protocol::*;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::context::{is_untraced, replace_current};
+use crate::span_builder::{ActiveSpan, IntoAttributeValue};
+use crate::tracer::Tracer;
+
+thread_local! {
+    /// Hindsight contexts displaced by entering `tracing` spans, innermost last
+    static ENTERED: RefCell<Vec<Option<TraceContext>>> = const { RefCell::new(Vec::new()) };
+}
+
+/// `tracing_subscriber` layer that exports `tracing` spans to hindsight
+///
+/// Each `tracing` span becomes a hindsight [`Span`], its fields become
+/// attributes, and events recorded inside it become [`SpanEvent`]s. Entering
+/// a `tracing` span also makes it the current hindsight span, so the two APIs
+/// nest under each other.
+///
+/// Anything emitted while hindsight itself is exporting (see
+/// [`is_untraced`](crate::is_untraced)) is ignored.
+///
+/// # Example
+/// ```no_run
+/// # async fn example() -> Result<(), hindsight::TracerError> {
+/// use tracing_subscriber::prelude::*;
+///
+/// let tracer = hindsight::Tracer::connect_http("localhost:1990").await?;
+/// tracing_subscriber::registry()
+///     .with(hindsight::HindsightLayer::new(tracer))
+///     .init();
+/// # Ok(())
+/// # }
+/// ```
+pub struct HindsightLayer {
+    tracer: Tracer,
+}
+
+impl HindsightLayer {
+    /// Create a layer that sends spans through `tracer`
+    pub fn new(tracer: Tracer) -> Self {
+        Self { tracer }
+    }
+}
+
+impl<S> Layer<S> for HindsightLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if is_untraced() {
+            return;
+        }
+        let Some(span_ref) = ctx.span(id) else {
+            return;
+        };
+
+        // Prefer the `tracing` parent, fall back to the ambient hindsight span
+        let parent = if attrs.is_root() {
+            None
+        } else {
+            span_ref.parent().and_then(|parent| {
+                let extensions = parent.extensions();
+                let context = extensions
+                    .get::<ActiveSpan>()
+                    .map(|span| span.context().clone());
+                context
+            })
+        };
+
+        let mut builder = self.tracer.span(attrs.metadata().name());
+        builder = match parent {
+            Some(parent) => builder.with_parent(parent),
+            None if attrs.is_root() => builder.root(),
+            None => builder,
+        };
+
+        let mut span = builder.start();
+        if let Some(recorded) = span.span_mut() {
+            attrs.record(&mut AttributeVisitor(&mut recorded.attributes));
+        }
+        span_ref.extensions_mut().insert(span);
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        if is_untraced() {
+            return;
+        }
+        let Some(span_ref) = ctx.span(id) else {
+            return;
+        };
+        if let Some(span) = span_ref.extensions_mut().get_mut::<ActiveSpan>() {
+            if let Some(recorded) = span.span_mut() {
+                values.record(&mut AttributeVisitor(&mut recorded.attributes));
+            }
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        if is_untraced() {
+            return;
+        }
+        let Some(span_ref) = ctx.event_span(event) else {
+            return;
+        };
+        let mut extensions = span_ref.extensions_mut();
+        let Some(span) = extensions.get_mut::<ActiveSpan>() else {
+            return;
+        };
+
+        let mut attributes = BTreeMap::new();
+        event.record(&mut AttributeVisitor(&mut attributes));
+        attributes.insert(
+            "level".to_string(),
+            AttributeValue::String(event.metadata().level().to_string()),
+        );
+
+        // Use the message as the event name, like most `tracing` formatters do
+        let name = match attributes.remove("message") {
+            Some(AttributeValue::String(message)) => message,
+            _ => event.metadata().name().to_string(),
+        };
+
+        span.add_event_with_attributes(name, attributes);
+    }
+
+    fn on_enter(&self, id: &Id, ctx: Context<'_, S>) {
+        let Some(span_ref) = ctx.span(id) else {
+            return;
+        };
+        let context = span_ref
+            .extensions()
+            .get::<ActiveSpan>()
+            .map(|span| span.context().clone());
+        if let Some(context) = context {
+            let previous = replace_current(Some(context));
+            ENTERED.with(|entered| entered.borrow_mut().push(previous));
+        }
+    }
+
+    fn on_exit(&self, id: &Id, ctx: Context<'_, S>) {
+        let Some(span_ref) = ctx.span(id) else {
+            return;
+        };
+        if span_ref.extensions().get::<ActiveSpan>().is_some() {
+            if let Some(previous) = ENTERED.with(|entered| entered.borrow_mut().pop()) {
+                replace_current(previous);
+            }
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span_ref) = ctx.span(&id) else {
+            return;
+        };
+        if let Some(span) = span_ref.extensions_mut().remove::<ActiveSpan>() {
+            span.end();
+        }
+    }
+}
+
+/// Records `tracing` fields as hindsight attributes
+struct AttributeVisitor<'a>(&'a mut BTreeMap<String, AttributeValue>);
+
+impl AttributeVisitor<'_> {
+    fn insert(&mut self, field: &Field, value: AttributeValue) {
+        self.0.insert(field.name().to_string(), value);
+    }
+}
+
+impl Visit for AttributeVisitor<'_> {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.insert(field, AttributeValue::Float(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.insert(field, AttributeValue::Int(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.insert(field, value.into_attribute_value());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.insert(field, AttributeValue::Bool(value));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.insert(field, AttributeValue::String(value.to_string()));
+    }
+
+    fn record_error(&mut self, field: &Field, value: &(dyn std::error::Error + 'static)) {
+        self.insert(field, AttributeValue::String(value.to_string()));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.insert(field, AttributeValue::String(format!("{:?}", value)));
+    }
+}
@@ -0,0 +1,196 @@
+#![cfg(all(feature = "client", not(feature = "noop")))]
+
+use hindsight::{
+    encode_span_line, HindsightServiceClient, Span, SpanId, SpanStatus, Timestamp, TraceId, Tracer,
+};
+use hindsight_server::{ImportOptions, ServerOptions};
+use rapace::{RpcSession, Transport};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+const TOKEN: &str = "s3cret";
+
+/// Span file with one span, for `import_span_files`
+fn span_file(name: &str) -> (PathBuf, TraceId) {
+    let trace_id = TraceId::new();
+    let span = Span {
+        trace_id,
+        span_id: SpanId::new(),
+        parent_span_id: None,
+        name: name.into(),
+        start_time: Timestamp(1),
+        end_time: Some(Timestamp(2)),
+        attributes: BTreeMap::new(),
+        events: Vec::new(),
+        status: SpanStatus::Ok,
+        service_name: "import".into(),
+    };
+    let path =
+        std::env::temp_dir().join(format!("hindsight-{}-{}.jsonl", name, std::process::id()));
+    std::fs::write(&path, format!("{}\n", encode_span_line(&span))).unwrap();
+    (path, trace_id)
+}
+
+/// Query client over a WebSocket, passing the token like a browser would
+async fn websocket_client(
+    addr: &str,
+    token: &str,
+) -> Result<HindsightServiceClient, tokio_tungstenite::tungstenite::Error> {
+    let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+    let url = format!("ws://{}/?access_token={}", addr, token);
+    let (ws, _) = tokio_tungstenite::client_async(url, stream).await?;
+    let session = Arc::new(RpcSession::new(Transport::websocket(ws)));
+    let runner = session.clone();
+    tokio::spawn(async move { runner.run().await });
+    Ok(HindsightServiceClient::new(session))
+}
+
+async fn flush(tracer: &Tracer) {
+    let tracer = tracer.clone();
+    let flushed =
+        tokio::task::spawn_blocking(move || tracer.flush_blocking(Duration::from_secs(5)))
+            .await
+            .unwrap();
+    assert!(flushed);
+}
+
+/// Clients with the token get in, over every handshake; others are refused
+#[tokio::test(flavor = "multi_thread")]
+async fn test_auth_token() {
+    let addr = "127.0.0.1:19960";
+    let options = ServerOptions {
+        auth_token: Some(TOKEN.into()),
+        ..Default::default()
+    };
+    let server = tokio::spawn(async move {
+        hindsight_server::run_server("127.0.0.1", 19960, 19961, 3600, false, options).await
+    });
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    // HTTP upgrade with `Authorization: Bearer`
+    let tracer = Tracer::builder()
+        .endpoint(addr)
+        .bearer_token(TOKEN)
+        .connect()
+        .await
+        .unwrap();
+    let span = tracer.span("authorized").root().start();
+    let trace_id = span.context().trace_id;
+    span.end();
+    flush(&tracer).await;
+
+    let refused = Tracer::builder()
+        .endpoint(addr)
+        .bearer_token("wrong")
+        .connect()
+        .await;
+    assert!(refused.is_err());
+    assert!(Tracer::builder().endpoint(addr).connect().await.is_err());
+
+    // WebSocket with `?access_token=`
+    let client = websocket_client(addr, TOKEN).await.unwrap();
+    let trace = client.get_trace(trace_id).await.unwrap().unwrap();
+    assert_eq!(trace.spans[0].name, "authorized");
+    assert!(websocket_client(addr, "wrong").await.is_err());
+
+    // `hindsight import --auth-token`
+    let (path, trace_id) = span_file("auth-import");
+    let with_token = ImportOptions {
+        auth_token: Some(TOKEN.into()),
+        ..Default::default()
+    };
+    let accepted = hindsight_server::import_span_files(addr, &[&path], &with_token)
+        .await
+        .unwrap();
+    assert_eq!(accepted, 1);
+    assert!(client.get_trace(trace_id).await.unwrap().is_some());
+
+    let wrong_token = ImportOptions {
+        auth_token: Some("wrong".into()),
+        ..Default::default()
+    };
+    assert!(
+        hindsight_server::import_span_files(addr, &[&path], &wrong_token)
+            .await
+            .is_err()
+    );
+
+    server.abort();
+}
+
+/// The TLS port completes the handshake and serves clients and imports
+#[cfg(feature = "tls")]
+#[tokio::test(flavor = "multi_thread")]
+async fn test_tls() {
+    use hindsight::TlsConfig;
+    use rcgen::{BasicConstraints, CertificateParams, IsCa, KeyPair};
+
+    // A private CA and a certificate for localhost signed by it
+    let mut ca_params = CertificateParams::new(Vec::<String>::new()).unwrap();
+    ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+    let ca_key = KeyPair::generate().unwrap();
+    let ca = ca_params.self_signed(&ca_key).unwrap();
+    let server_key = KeyPair::generate().unwrap();
+    let server_cert = CertificateParams::new(vec!["localhost".to_string()])
+        .unwrap()
+        .signed_by(&server_key, &ca, &ca_key)
+        .unwrap();
+
+    let dir = std::env::temp_dir().join(format!("hindsight-tls-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let ca_path = dir.join("ca.pem");
+    let cert_path = dir.join("server.pem");
+    let key_path = dir.join("server.key");
+    std::fs::write(&ca_path, ca.pem()).unwrap();
+    std::fs::write(&cert_path, server_cert.pem()).unwrap();
+    std::fs::write(&key_path, server_key.serialize_pem()).unwrap();
+
+    let options = ServerOptions {
+        tls: Some(hindsight_server::TlsListener {
+            port: 19967,
+            cert_path,
+            key_path,
+        }),
+        auth_token: Some(TOKEN.into()),
+        ..Default::default()
+    };
+    let server = tokio::spawn(async move {
+        hindsight_server::run_server("127.0.0.1", 19965, 19966, 3600, false, options).await
+    });
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    let addr = "localhost:19967";
+    let tracer = Tracer::builder()
+        .endpoint(addr)
+        .tls(TlsConfig::new().add_ca_pem(ca.pem().as_bytes()).unwrap())
+        .bearer_token(TOKEN)
+        .connect()
+        .await
+        .unwrap();
+    tracer.span("over_tls").root().start().end();
+    flush(&tracer).await;
+
+    // Plain text can't talk to the TLS port
+    let plain = Tracer::builder()
+        .endpoint(addr)
+        .bearer_token(TOKEN)
+        .connect()
+        .await;
+    assert!(plain.is_err());
+
+    // `hindsight import --tls-ca-file --auth-token`
+    let (path, _) = span_file("tls-import");
+    let import = ImportOptions {
+        auth_token: Some(TOKEN.into()),
+        tls: true,
+        tls_ca_file: Some(ca_path),
+    };
+    let accepted = hindsight_server::import_span_files(addr, &[&path], &import)
+        .await
+        .unwrap();
+    assert_eq!(accepted, 1);
+
+    server.abort();
+}
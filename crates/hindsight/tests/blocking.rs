@@ -0,0 +1,36 @@
+#![cfg(all(feature = "client", not(feature = "noop")))]
+
+use hindsight::BlockingTracer;
+
+/// The blocking tracer works from plain `main`-style code, without a runtime
+#[test]
+fn test_blocking_tracer_without_runtime() {
+    // Any free port, so parallel test binaries can't collide
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.set_nonblocking(true).unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    // Run the server on its own runtime, away from the code under test
+    std::thread::spawn(move || {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let _ = runtime.block_on(async move {
+            let listener = tokio::net::TcpListener::from_std(listener).unwrap();
+            hindsight_server::run_server_on(listener, 3600, false, Default::default()).await
+        });
+    });
+
+    let tracer = BlockingTracer::connect(addr.to_string()).expect("Failed to connect");
+
+    let span = tracer
+        .span("test_blocking")
+        .with_attribute("test", "integration")
+        .start();
+    span.end();
+
+    assert!(tracer.flush());
+    let stats = tracer.tracer().stats();
+    assert_eq!(stats.spans_sent, 1);
+    assert_eq!(stats.queue_depth, 0);
+
+    tracer.shutdown();
+}
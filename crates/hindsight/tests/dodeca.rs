@@ -0,0 +1,112 @@
+#![cfg(all(feature = "dodeca", not(feature = "noop")))]
+
+use hindsight::dodeca::DodecaTracing;
+use hindsight::{current_context, SpanMatcher, TraceType, Tracer};
+
+/// A rebuild is one dodeca trace, with every pipeline step as a child
+#[tokio::test]
+async fn test_build_is_one_trace() {
+    let (tracer, spans) = Tracer::in_memory();
+    let dodeca = DodecaTracing::new(tracer.clone());
+
+    let build = dodeca.start_build(Some("content/index.md"));
+    build
+        .markdown_parse("content/index.md")
+        .run(async {
+            // Work inside a step runs with the step as the current span
+            tracer.span("highlight").start().end();
+        })
+        .await;
+    build.image_optimization("logo.png").end();
+    build
+        .template_render("index.html", "page.html")
+        .run(async {})
+        .await;
+    let mut plugin = build.plugin_call("search", "index");
+    plugin.set_error("plugin crashed");
+    plugin.end();
+    build.finish_with_pages(3);
+
+    assert_eq!(spans.len(), 6);
+    spans.assert_has(
+        SpanMatcher::named("dodeca build")
+            .with_attribute("dodeca.build", true)
+            .with_attribute("dodeca.build_number", 1)
+            .with_attribute("dodeca.trigger", "content/index.md")
+            .with_attribute("dodeca.page_count", 3)
+            .root()
+            .ok(),
+    );
+    spans.assert_has(
+        SpanMatcher::named("parse content/index.md")
+            .with_attribute("dodeca.step", "markdown_parse")
+            .with_attribute("dodeca.source", "content/index.md")
+            .with_parent("dodeca build"),
+    );
+    spans.assert_has(SpanMatcher::named("highlight").with_parent("parse content/index.md"));
+    spans.assert_has(
+        SpanMatcher::named("optimize logo.png")
+            .with_attribute("dodeca.step", "image_optimization")
+            .with_attribute("dodeca.image", "logo.png")
+            .with_parent("dodeca build"),
+    );
+    spans.assert_has(
+        SpanMatcher::named("render index.html")
+            .with_attribute("dodeca.step", "template_render")
+            .with_attribute("dodeca.page", "index.html")
+            .with_attribute("dodeca.template", "page.html")
+            .with_parent("dodeca build"),
+    );
+    spans.assert_has(
+        SpanMatcher::named("search.index")
+            .with_attribute("dodeca.step", "plugin_call")
+            .with_attribute("dodeca.plugin", "search")
+            .with_attribute("dodeca.plugin_method", "index")
+            .with_parent("dodeca build")
+            .errored(),
+    );
+
+    let traces = spans.traces();
+    assert_eq!(traces.len(), 1);
+    assert_eq!(traces[0].classify_type(), TraceType::Dodeca);
+}
+
+/// Successive builds are separate, numbered traces, even when started
+/// inside another span
+#[tokio::test]
+async fn test_builds_are_numbered_roots() {
+    let (tracer, spans) = Tracer::in_memory();
+    let dodeca = DodecaTracing::new(tracer.clone());
+
+    tracer
+        .in_span("watch", async {
+            let mut first = dodeca.start_build(None);
+            first
+                .run(async { assert!(current_context().is_some()) })
+                .await;
+            first.set_error("template error");
+            first.finish();
+            dodeca.start_build(Some("style.css")).finish();
+        })
+        .await;
+
+    spans.assert_has(
+        SpanMatcher::named("dodeca build")
+            .with_attribute("dodeca.build_number", 1)
+            .root()
+            .errored(),
+    );
+    spans.assert_has(
+        SpanMatcher::named("dodeca build")
+            .with_attribute("dodeca.build_number", 2)
+            .with_attribute("dodeca.trigger", "style.css")
+            .root()
+            .ok(),
+    );
+    assert!(!spans
+        .find("dodeca build")
+        .unwrap()
+        .attributes
+        .contains_key("dodeca.page_count"));
+    assert_eq!(spans.traces().len(), 3);
+}
@@ -0,0 +1,106 @@
+#![cfg(all(feature = "client", not(feature = "noop")))]
+
+use hindsight::{
+    ConnectionState, ExportError, ExportFuture, Exporter, FilterProcessor, Span, Tracer,
+};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Keeps every span it is sent
+#[derive(Clone, Default)]
+struct Collect(Arc<Mutex<Vec<Span>>>);
+
+impl Exporter for Collect {
+    fn export(&self, spans: Vec<Span>) -> ExportFuture<'_> {
+        Box::pin(async move {
+            self.0.lock().unwrap().extend(spans);
+            Ok(())
+        })
+    }
+}
+
+/// Fails every batch
+struct Fail;
+
+impl Exporter for Fail {
+    fn export(&self, _spans: Vec<Span>) -> ExportFuture<'_> {
+        Box::pin(async { Err(ExportError::new("unreachable")) })
+    }
+}
+
+/// Every exporter gets every span, and one failing doesn't affect the others
+#[tokio::test(flavor = "multi_thread")]
+async fn test_fan_out_isolates_failures() {
+    let first = Collect::default();
+    let second = Collect::default();
+    let tracer = Tracer::builder()
+        .exporter(first.clone())
+        .exporter(Fail)
+        .exporter(second.clone())
+        .build_exporters();
+
+    tracer.span("build").start().end();
+    // Waits out the failing exporter's retries too
+    assert!(tracer.flush_blocking(Duration::from_secs(5)));
+
+    for collect in [&first, &second] {
+        let spans = collect.0.lock().unwrap();
+        assert_eq!(spans.len(), 1);
+        assert_eq!(spans[0].name, "build");
+    }
+
+    let stats = tracer.stats();
+    assert_eq!(stats.spans_sent, 2);
+    assert_eq!(stats.spans_dropped, 1);
+    assert_eq!(stats.batches_failed, 1);
+
+    // Broken down per exporter, with the failing one on its own
+    let exporters: Vec<_> = stats
+        .exporters
+        .iter()
+        .map(|e| (e.name.as_str(), e.spans_sent, e.spans_dropped, e.connection))
+        .collect();
+    assert_eq!(
+        exporters,
+        [
+            ("Collect", 1, 0, ConnectionState::Connected),
+            ("Fail", 0, 1, ConnectionState::Failing),
+            ("Collect", 1, 0, ConnectionState::Connected),
+        ]
+    );
+    assert_eq!(stats.connection, ConnectionState::Connected);
+}
+
+/// A span dropped by a processor once finished doesn't stay running on the
+/// exporters that saw its in-flight record
+#[tokio::test(flavor = "multi_thread")]
+async fn test_filtered_span_ends_in_flight_record() {
+    let collect = Collect::default();
+    let tracer = Tracer::builder()
+        .exporter(collect.clone())
+        .heartbeat_interval(Duration::from_secs(60))
+        .processor(FilterProcessor::new(|span| {
+            span.name != "health_check" || span.is_running()
+        }))
+        .processor(|span: &mut Span| {
+            span.attributes.remove("token");
+            true
+        })
+        .build_exporters();
+
+    tracer
+        .span("health_check")
+        .with_attribute("token", "secret")
+        .start()
+        .end();
+    assert!(tracer.flush_blocking(Duration::from_secs(5)));
+
+    let spans = collect.0.lock().unwrap();
+    assert_eq!(spans.len(), 2);
+    assert!(spans[0].is_running());
+    // The in-flight record, as the processors left it, now ended
+    assert_eq!(spans[1].span_id, spans[0].span_id);
+    assert!(!spans[1].is_running());
+    assert!(!spans[1].attributes.contains_key("token"));
+    assert_eq!(tracer.stats().spans_filtered, 1);
+}
@@ -0,0 +1,139 @@
+#![cfg(all(feature = "client", not(feature = "noop")))]
+
+use hindsight::{
+    decode_span_line, FileExporter, HindsightServiceClient, Span, SpanId, SpanStatus, Timestamp,
+    TraceId, Tracer,
+};
+use rapace::{RpcSession, Transport};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Fresh directory for one test's span files
+fn test_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("hindsight-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+fn span(name: &str) -> Span {
+    Span {
+        trace_id: TraceId::new(),
+        span_id: SpanId::new(),
+        parent_span_id: None,
+        name: name.into(),
+        start_time: Timestamp(1),
+        end_time: Some(Timestamp(2)),
+        attributes: BTreeMap::new(),
+        events: Vec::new(),
+        status: SpanStatus::Ok,
+        service_name: "test".into(),
+    }
+}
+
+fn span_names(path: &Path) -> Vec<String> {
+    std::fs::read_to_string(path)
+        .unwrap()
+        .lines()
+        .map(|line| decode_span_line(line).unwrap().name)
+        .collect()
+}
+
+fn rotated(path: &Path, index: usize) -> PathBuf {
+    PathBuf::from(format!("{}.{}", path.display(), index))
+}
+
+/// Full files move to `.1`, `.2`, ... and only `max_files` are kept
+#[test]
+fn test_rotation() {
+    let path = test_dir("rotation").join("spans.jsonl");
+    let line_len = hindsight::encode_span_line(&span("batch0")).len() as u64 + 1;
+    let exporter = FileExporter::new(&path)
+        .max_file_size(line_len * 2)
+        .max_files(2);
+
+    for index in 0..7 {
+        exporter
+            .write_batch(&[span(&format!("batch{}", index))])
+            .unwrap();
+    }
+
+    // Two batches per file, newest in the live file
+    assert_eq!(span_names(&path), ["batch6"]);
+    assert_eq!(span_names(&rotated(&path, 1)), ["batch4", "batch5"]);
+    assert_eq!(span_names(&rotated(&path, 2)), ["batch2", "batch3"]);
+    assert!(!rotated(&path, 3).exists());
+}
+
+/// With `max_files(0)`, full files are deleted instead of rotated
+#[test]
+fn test_rotation_without_history() {
+    let path = test_dir("no-history").join("spans.jsonl");
+    let exporter = FileExporter::new(&path).max_file_size(1).max_files(0);
+
+    exporter.write_batch(&[span("first")]).unwrap();
+    exporter.write_batch(&[span("second")]).unwrap();
+
+    assert_eq!(span_names(&path), ["second"]);
+    assert!(!rotated(&path, 1).exists());
+}
+
+/// Spans written by a file tracer come back unchanged through `hindsight import`
+#[tokio::test(flavor = "multi_thread")]
+async fn test_export_import_round_trip() {
+    let path = test_dir("round-trip").join("spans.jsonl");
+    let tracer = Tracer::builder()
+        .service_name("ci")
+        .build_file(FileExporter::new(&path));
+
+    let mut build = tracer.span("build").with_attribute("target", "x86").start();
+    let trace_id = build.context().trace_id;
+    tracer
+        .span("compile")
+        .with_parent(build.context().clone())
+        .start_at(Timestamp(1_000))
+        .end_at(Timestamp(2_000));
+    build.set_error("link failed");
+    build.end();
+
+    let flusher = tracer.clone();
+    let flushed =
+        tokio::task::spawn_blocking(move || flusher.flush_blocking(Duration::from_secs(5)))
+            .await
+            .unwrap();
+    assert!(flushed);
+
+    let server = tokio::spawn(async {
+        hindsight_server::run_server("127.0.0.1", 19940, 19941, 3600, false, Default::default())
+            .await
+    });
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    let accepted =
+        hindsight_server::import_span_files("127.0.0.1:19940", &[&path], &Default::default())
+            .await
+            .unwrap();
+    assert_eq!(accepted, 2);
+
+    let stream = tokio::net::TcpStream::connect("127.0.0.1:19940")
+        .await
+        .unwrap();
+    let session = Arc::new(RpcSession::new(Transport::stream(stream)));
+    let runner = session.clone();
+    tokio::spawn(async move { runner.run().await });
+    let client = HindsightServiceClient::new(session);
+
+    let trace = client.get_trace(trace_id).await.unwrap().unwrap();
+    assert_eq!(trace.spans.len(), 2);
+    let build = trace.spans.iter().find(|s| s.name == "build").unwrap();
+    assert_eq!(build.service_name, "ci");
+    assert!(build.parent_span_id.is_none());
+    assert!(matches!(build.status, SpanStatus::Error { .. }));
+    let compile = trace.spans.iter().find(|s| s.name == "compile").unwrap();
+    assert_eq!(compile.parent_span_id, Some(build.span_id));
+    assert_eq!(compile.start_time, Timestamp(1_000));
+    assert_eq!(compile.end_time, Some(Timestamp(2_000)));
+
+    server.abort();
+}
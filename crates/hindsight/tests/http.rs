@@ -0,0 +1,212 @@
+#![cfg(feature = "http")]
+
+use hindsight::http::{extract_headers, inject_headers, TRACEPARENT, TRACESTATE};
+use hindsight::TraceContext;
+use http::{HeaderMap, HeaderValue};
+
+/// `traceparent` and `tracestate` survive an inject/extract round trip
+#[test]
+fn test_header_round_trip() {
+    let mut context = TraceContext::new_root();
+    context.tracestate = Some("vendor=abc,other=def".into());
+
+    let mut headers = HeaderMap::new();
+    inject_headers(&context, &mut headers);
+    assert_eq!(headers[TRACEPARENT], context.to_traceparent().as_str());
+    assert_eq!(headers[TRACESTATE], "vendor=abc,other=def");
+
+    let extracted = extract_headers(&headers).unwrap();
+    assert_eq!(extracted.trace_id, context.trace_id);
+    assert_eq!(extracted.span_id, context.span_id);
+    assert_eq!(extracted.flags, context.flags);
+    assert_eq!(
+        extracted.tracestate.as_deref(),
+        Some("vendor=abc,other=def")
+    );
+}
+
+/// Several `tracestate` headers are joined; bad or missing `traceparent` is None
+#[test]
+fn test_extract_headers_edge_cases() {
+    let context = TraceContext::new_root();
+    let mut headers = HeaderMap::new();
+    inject_headers(&context, &mut headers);
+    headers.append(TRACESTATE, HeaderValue::from_static("a=1"));
+    headers.append(TRACESTATE, HeaderValue::from_static("b=2"));
+    let extracted = extract_headers(&headers).unwrap();
+    assert_eq!(extracted.tracestate.as_deref(), Some("a=1,b=2"));
+
+    assert!(extract_headers(&HeaderMap::new()).is_none());
+
+    let mut headers = HeaderMap::new();
+    headers.insert(TRACEPARENT, HeaderValue::from_static("not-a-traceparent"));
+    assert!(extract_headers(&headers).is_none());
+}
+
+#[cfg(all(feature = "tower", not(feature = "noop")))]
+mod tower_layer {
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use axum::routing::get;
+    use axum::Router;
+    use hindsight::http::TraceLayer;
+    use hindsight::{current_context, SpanMatcher, TraceContext, Tracer};
+    use tower::ServiceExt;
+
+    fn app(tracer: &Tracer) -> Router {
+        let inner = tracer.clone();
+        Router::new()
+            .route(
+                "/users",
+                get(move || async move {
+                    // The request span is current inside the handler
+                    assert!(current_context().is_some());
+                    inner.span("load_users").start().end();
+                    "users"
+                }),
+            )
+            .route("/fail", get(|| async { StatusCode::INTERNAL_SERVER_ERROR }))
+            .layer(TraceLayer::new(tracer.clone()))
+    }
+
+    /// Requests get a server span that joins the caller's trace
+    #[tokio::test]
+    async fn test_trace_layer_request_response() {
+        let (tracer, spans) = Tracer::in_memory();
+        let parent = TraceContext::new_root();
+
+        let response = app(&tracer)
+            .oneshot(
+                Request::builder()
+                    .uri("/users")
+                    .header("traceparent", parent.to_traceparent())
+                    .header("user-agent", "tests")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        assert_eq!(spans.len(), 2);
+        spans.assert_has(
+            SpanMatcher::named("GET /users")
+                .with_attribute("span.kind", "server")
+                .with_attribute("http.method", "GET")
+                .with_attribute("http.target", "/users")
+                .with_attribute("http.user_agent", "tests")
+                .with_attribute("http.status_code", 200)
+                .ok(),
+        );
+        spans.assert_has(SpanMatcher::named("load_users").with_parent("GET /users"));
+
+        let request = spans.find("GET /users").unwrap();
+        assert_eq!(request.trace_id, parent.trace_id);
+        assert_eq!(request.parent_span_id, Some(parent.span_id));
+    }
+
+    /// Requests without `traceparent` start a new trace; 5xx marks an error
+    #[tokio::test]
+    async fn test_trace_layer_server_error() {
+        let (tracer, spans) = Tracer::in_memory();
+
+        let response = app(&tracer)
+            .oneshot(Request::builder().uri("/fail").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        spans.assert_has(
+            SpanMatcher::named("GET /fail")
+                .with_attribute("http.status_code", 500)
+                .root()
+                .errored(),
+        );
+    }
+}
+
+#[cfg(all(feature = "reqwest", not(feature = "noop")))]
+mod reqwest_client {
+    use axum::http::{HeaderMap, StatusCode};
+    use axum::routing::get;
+    use axum::Router;
+    use hindsight::http::RequestBuilderExt;
+    use hindsight::{SpanMatcher, TraceContext, Tracer};
+
+    /// Serve an app echoing the request's `traceparent` on a free port
+    async fn echo_server() -> String {
+        let app = Router::new()
+            .route(
+                "/echo",
+                get(|headers: HeaderMap| async move {
+                    headers
+                        .get("traceparent")
+                        .and_then(|value| value.to_str().ok())
+                        .unwrap_or_default()
+                        .to_string()
+                }),
+            )
+            .route("/fail", get(|| async { StatusCode::SERVICE_UNAVAILABLE }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        format!("http://{}", addr)
+    }
+
+    /// `send_traced` opens a client span and sends its context downstream
+    #[tokio::test]
+    async fn test_send_traced() {
+        let (tracer, spans) = Tracer::in_memory();
+        let base = echo_server().await;
+        let client = reqwest::Client::new();
+
+        let response = client
+            .get(format!("{}/echo", base))
+            .send_traced(&tracer)
+            .await
+            .unwrap();
+        let traceparent = response.text().await.unwrap();
+
+        let span = spans.find("GET /echo").unwrap();
+        let sent = TraceContext::from_traceparent(&traceparent).unwrap();
+        assert_eq!(sent.trace_id, span.trace_id);
+        assert_eq!(sent.span_id, span.span_id);
+        spans.assert_has(
+            SpanMatcher::named("GET /echo")
+                .with_attribute("span.kind", "client")
+                .with_attribute("http.url", format!("{}/echo", base))
+                .with_attribute("http.status_code", 200)
+                .ok(),
+        );
+
+        client
+            .get(format!("{}/fail", base))
+            .send_traced(&tracer)
+            .await
+            .unwrap();
+        spans.assert_has(
+            SpanMatcher::named("GET /fail")
+                .with_attribute("http.status_code", 503)
+                .errored(),
+        );
+    }
+
+    /// `with_trace_context` sends the given context
+    #[tokio::test]
+    async fn test_with_trace_context() {
+        let base = echo_server().await;
+        let context = TraceContext::new_root();
+
+        let traceparent = reqwest::Client::new()
+            .get(format!("{}/echo", base))
+            .with_trace_context(&context)
+            .send()
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+
+        assert_eq!(traceparent, context.to_traceparent());
+    }
+}
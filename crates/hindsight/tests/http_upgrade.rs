@@ -1,3 +1,5 @@
+#![cfg(all(feature = "client", not(feature = "noop")))]
+
 use hindsight::Tracer;
 use std::time::Duration;
 use tokio::time::timeout;
@@ -7,7 +9,8 @@ use tokio::time::timeout;
 async fn test_http_upgrade_connect() {
     // Start a Hindsight server in the background
     let server_handle = tokio::spawn(async {
-        hindsight_server::run_server("127.0.0.1", 19900, 19901, 3600, false).await
+        hindsight_server::run_server("127.0.0.1", 19900, 19901, 3600, false, Default::default())
+            .await
     });
 
     // Give the server time to start
@@ -47,7 +50,8 @@ async fn test_raw_tcp_still_works() {
 
     // Start a Hindsight server in the background
     let server_handle = tokio::spawn(async {
-        hindsight_server::run_server("127.0.0.1", 19910, 19911, 3600, false).await
+        hindsight_server::run_server("127.0.0.1", 19910, 19911, 3600, false, Default::default())
+            .await
     });
 
     // Give the server time to start
@@ -0,0 +1,254 @@
+#![cfg(not(feature = "noop"))]
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use hindsight::{
+    AttributeLimitProcessor, AttributeValue, ConnectionState, EnrichProcessor, FilterProcessor,
+    RedactProcessor, Sampler, Span, SpanId, SpanMatcher, SpanStatus, Timestamp, TraceId, Tracer,
+};
+
+/// Spans started inside `in_span` are recorded as its children
+#[tokio::test]
+async fn test_in_memory_records_nested_spans() {
+    let (tracer, spans) = Tracer::in_memory();
+
+    tracer
+        .in_span("handle_request", async {
+            let mut span = tracer
+                .span("load_user")
+                .with_attribute("user_id", 42)
+                .start();
+            span.set_error("not found");
+            span.end();
+        })
+        .await;
+
+    assert_eq!(spans.len(), 2);
+    spans.assert_has(SpanMatcher::named("handle_request").root().ok());
+    spans.assert_has(
+        SpanMatcher::named("load_user")
+            .with_attribute("user_id", 42)
+            .with_parent("handle_request")
+            .errored(),
+    );
+    spans.assert_none(SpanMatcher::named("load_user").root());
+
+    let traces = spans.traces();
+    assert_eq!(traces.len(), 1);
+    assert_eq!(traces[0].spans.len(), 2);
+}
+
+/// Stats count recorded spans, with nothing queued or dropped
+#[test]
+fn test_in_memory_stats() {
+    let (tracer, _spans) = Tracer::in_memory();
+
+    tracer.span("first").start().end();
+    tracer.span("second").start().end();
+
+    let stats = tracer.stats();
+    assert_eq!(stats.spans_created, 2);
+    assert_eq!(stats.spans_sampled, 2);
+    assert_eq!(stats.spans_sent, 2);
+    assert_eq!(stats.spans_dropped, 0);
+    assert_eq!(stats.queue_depth, 0);
+    assert_eq!(stats.connection, ConnectionState::Local);
+}
+
+/// Processors run in order on every span before it is recorded
+#[test]
+fn test_in_memory_processors() {
+    let (tracer, spans) = Tracer::builder()
+        .processor(FilterProcessor::new(|span| span.name != "GET /health"))
+        .processor(EnrichProcessor::new().with_attribute("deployment.environment", "ci"))
+        .processor(RedactProcessor::new(["*token*", "user.email"]))
+        .processor(AttributeLimitProcessor::new().max_value_len(4))
+        .build_in_memory();
+
+    tracer.span("GET /health").start().end();
+    tracer
+        .span("GET /users")
+        .with_attribute("auth.Token", "secret")
+        .with_attribute("user.email", "someone@example.com")
+        .with_attribute("path", "/users/42")
+        .start()
+        .end();
+
+    assert_eq!(spans.len(), 1);
+    let span = spans.find("GET /users").unwrap();
+    let attribute = |key: &str| span.attributes.get(key).cloned();
+    // Limits run last, so they also cut the redaction marker and enrichment
+    assert_eq!(
+        attribute("auth.Token"),
+        Some(AttributeValue::String("[RED".into()))
+    );
+    assert_eq!(
+        attribute("user.email"),
+        Some(AttributeValue::String("[RED".into()))
+    );
+    assert_eq!(
+        attribute("path"),
+        Some(AttributeValue::String("/use".into()))
+    );
+    assert_eq!(
+        attribute("deployment.environment"),
+        Some(AttributeValue::String("ci".into()))
+    );
+
+    let stats = tracer.stats();
+    assert_eq!(stats.spans_filtered, 1);
+    assert_eq!(stats.spans_dropped, 0);
+}
+
+/// Attributes, events, name and status can change after `start()`
+#[test]
+fn test_active_span_mutation() {
+    let (tracer, spans) = Tracer::in_memory();
+
+    let mut span = tracer
+        .span("GET")
+        .with_attribute("http.method", "GET")
+        .start();
+    span.update_name("GET /users/:id");
+    span.set_attribute("http.status_code", 404u16);
+    span.set_attribute("http.method", "HEAD");
+    span.add_event("cache_lookup");
+    span.add_event_with_attributes("cache_miss", [("key", "user:42")]);
+    span.set_status(SpanStatus::Error {
+        message: "not found".into(),
+    });
+    span.set_status(SpanStatus::Ok);
+    span.end();
+
+    assert_eq!(spans.len(), 1);
+    spans.assert_has(
+        SpanMatcher::named("GET /users/:id")
+            .with_attribute("http.method", "HEAD")
+            .with_attribute("http.status_code", 404)
+            .ok(),
+    );
+    assert!(spans.find("GET").is_none());
+
+    let span = spans.find("GET /users/:id").unwrap();
+    let events: Vec<_> = span.events.iter().map(|e| e.name.as_str()).collect();
+    assert_eq!(events, ["cache_lookup", "cache_miss"]);
+    assert!(span.events[0].attributes.is_empty());
+    assert_eq!(
+        span.events[1].attributes.get("key"),
+        Some(&AttributeValue::String("user:42".into()))
+    );
+}
+
+/// `record_duration_from` moves the start back to when timing began
+#[test]
+fn test_record_duration_from() {
+    let (tracer, spans) = Tracer::in_memory();
+
+    let began = Timestamp::now();
+    std::thread::sleep(Duration::from_millis(20));
+    let mut span = tracer.span("slow").start();
+    span.record_duration_from(began);
+    span.end();
+
+    let span = spans.find("slow").unwrap();
+    assert!(span.duration_nanos().unwrap() >= 20_000_000);
+}
+
+/// `start_at` and `end_at` keep the given timestamps
+#[test]
+fn test_explicit_timestamps() {
+    let (tracer, spans) = Tracer::in_memory();
+
+    tracer
+        .span("compile")
+        .start_at(Timestamp(1_000))
+        .end_at(Timestamp(5_000));
+
+    let span = spans.find("compile").unwrap();
+    assert_eq!(span.start_time, Timestamp(1_000));
+    assert_eq!(span.end_time, Some(Timestamp(5_000)));
+    assert_eq!(span.duration_nanos(), Some(4_000));
+}
+
+/// An end before the start is moved up to the start
+#[test]
+fn test_end_before_start() {
+    let (tracer, spans) = Tracer::in_memory();
+
+    tracer
+        .span("backfilled")
+        .start_at(Timestamp(5_000))
+        .end_at(Timestamp(1_000));
+    let mut imported = imported_span("imported", TraceId::new());
+    imported.end_time = Some(Timestamp(5));
+    tracer.submit(imported);
+
+    let span = spans.find("backfilled").unwrap();
+    assert_eq!(span.end_time, Some(Timestamp(5_000)));
+    assert_eq!(span.duration_nanos(), Some(0));
+    let mut span = spans.find("imported").unwrap();
+    assert_eq!(span.end_time, Some(Timestamp(10)));
+    assert_eq!(span.duration_nanos(), Some(0));
+
+    // Inverted spans from elsewhere (e.g. a server import) don't underflow
+    span.end_time = Some(Timestamp(0));
+    assert_eq!(span.duration_nanos(), Some(0));
+}
+
+fn imported_span(name: &str, trace_id: TraceId) -> Span {
+    Span {
+        trace_id,
+        span_id: SpanId::new(),
+        parent_span_id: None,
+        name: name.into(),
+        start_time: Timestamp(10),
+        end_time: Some(Timestamp(20)),
+        attributes: BTreeMap::new(),
+        events: Vec::new(),
+        status: SpanStatus::Ok,
+        service_name: String::new(),
+    }
+}
+
+/// Submitted spans keep their IDs and timings and go through processors
+#[test]
+fn test_submit() {
+    let (tracer, spans) = Tracer::builder()
+        .service_name("importer")
+        .processor(EnrichProcessor::new().with_attribute("imported", true))
+        .processor(FilterProcessor::new(|span| span.name != "skipped"))
+        .build_in_memory();
+
+    let trace_id = TraceId::new();
+    let mut link = imported_span("link", trace_id);
+    link.service_name = "linker".into();
+    let compile = imported_span("compile", trace_id);
+    let span_id = compile.span_id;
+    tracer.submit(compile);
+    tracer.submit(link);
+    tracer.submit(imported_span("skipped", trace_id));
+
+    assert_eq!(spans.len(), 2);
+    let compile = spans.find("compile").unwrap();
+    assert_eq!(compile.trace_id, trace_id);
+    assert_eq!(compile.span_id, span_id);
+    assert_eq!(compile.start_time, Timestamp(10));
+    assert_eq!(compile.end_time, Some(Timestamp(20)));
+    assert_eq!(compile.service_name, "importer");
+    assert_eq!(spans.find("link").unwrap().service_name, "linker");
+    spans.assert_has(SpanMatcher::named("compile").with_attribute("imported", true));
+    assert_eq!(tracer.stats().spans_filtered, 1);
+}
+
+/// Submitted spans are sampled like started ones
+#[test]
+fn test_submit_respects_sampler() {
+    let (tracer, spans) = Tracer::builder()
+        .sampler(Sampler::AlwaysOff)
+        .build_in_memory();
+
+    tracer.submit(imported_span("compile", TraceId::new()));
+
+    assert!(spans.is_empty());
+}
@@ -0,0 +1,228 @@
+#![cfg(all(feature = "macros", not(feature = "noop")))]
+
+use hindsight::{AttributeValue, SpanMatcher, SpanStatus, Tracer};
+
+struct Db;
+
+#[derive(Debug)]
+struct Query {
+    table: &'static str,
+}
+
+#[hindsight::instrument(tracer = tracer, skip(tracer))]
+fn add(tracer: &Tracer, a: i64, b: i64) -> i64 {
+    a + b
+}
+
+#[hindsight::instrument(tracer = tracer, skip(tracer, db), rename(id = "user.id"))]
+fn load_user(tracer: &Tracer, db: &Db, id: i64) -> Result<String, String> {
+    let _ = db;
+    if id < 0 {
+        return Err(format!("no user {}", id));
+    }
+    Ok(format!("user {}", id))
+}
+
+#[hindsight::instrument(name = "db.query", tracer = tracer, skip_all)]
+fn query(tracer: &Tracer, sql: &str) -> usize {
+    sql.len()
+}
+
+#[hindsight::instrument(tracer = tracer, skip(tracer))]
+fn run_query(tracer: &Tracer, query: Query) -> &'static str {
+    query.table
+}
+
+#[derive(Debug)]
+struct NotFound {
+    id: i64,
+}
+
+#[hindsight::instrument(tracer = tracer, skip(tracer))]
+fn find(tracer: &Tracer, id: i64) -> Result<i64, NotFound> {
+    Err(NotFound { id })
+}
+
+#[hindsight::instrument(tracer = tracer, skip(tracer))]
+fn check(tracer: &Tracer) -> Result<(), ()> {
+    Err(())
+}
+
+struct Counter {
+    tracer: Tracer,
+    count: i64,
+}
+
+impl Counter {
+    #[hindsight::instrument(tracer = self.tracer)]
+    fn count_mut(&mut self) -> &mut i64 {
+        &mut self.count
+    }
+
+    #[hindsight::instrument(tracer = self.tracer)]
+    fn try_count_mut(&mut self, limit: i64) -> Result<&mut i64, String> {
+        if self.count >= limit {
+            return Err(format!("over {}", limit));
+        }
+        Ok(&mut self.count)
+    }
+}
+
+#[hindsight::instrument(tracer = tracer, skip(tracer))]
+async fn handle_request(tracer: &Tracer, path: &str) -> Result<usize, String> {
+    tokio::task::yield_now().await;
+    load_user(tracer, &Db, 42)?;
+    Ok(query(tracer, path))
+}
+
+#[hindsight::instrument(tracer = tracer, skip(tracer))]
+async fn failing_request(tracer: &Tracer, id: i64) -> Result<String, String> {
+    load_user(tracer, &Db, id)
+}
+
+/// Sync functions record their arguments and return the body's value
+#[test]
+fn test_instrument_sync() {
+    let (tracer, spans) = Tracer::in_memory();
+
+    assert_eq!(add(&tracer, 1, 2), 3);
+
+    assert_eq!(spans.len(), 1);
+    spans.assert_has(
+        SpanMatcher::named("add")
+            .with_attribute("a", 1)
+            .with_attribute("b", 2)
+            .root()
+            .ok(),
+    );
+    assert!(!spans.find("add").unwrap().attributes.contains_key("tracer"));
+}
+
+/// `skip` leaves arguments out, `rename` changes their key
+#[test]
+fn test_instrument_skip_and_rename() {
+    let (tracer, spans) = Tracer::in_memory();
+
+    load_user(&tracer, &Db, 7).unwrap();
+
+    let span = spans.find("load_user").unwrap();
+    assert_eq!(span.attributes.len(), 1);
+    assert_eq!(
+        span.attributes.get("user.id"),
+        Some(&AttributeValue::Int(7))
+    );
+}
+
+/// `skip_all` records nothing; `name` overrides the span name
+#[test]
+fn test_instrument_skip_all_and_name() {
+    let (tracer, spans) = Tracer::in_memory();
+
+    assert_eq!(query(&tracer, "SELECT 1"), 8);
+
+    assert!(spans.find("query").is_none());
+    let span = spans.find("db.query").unwrap();
+    assert!(span.attributes.is_empty());
+}
+
+/// Arguments without `IntoAttributeValue` are recorded with `Debug`
+#[test]
+fn test_instrument_debug_arguments() {
+    let (tracer, spans) = Tracer::in_memory();
+
+    run_query(&tracer, Query { table: "users" });
+
+    spans.assert_has(
+        SpanMatcher::named("run_query").with_attribute("query", "Query { table: \"users\" }"),
+    );
+}
+
+/// An `Err` return marks the span as errored with the error's message
+#[test]
+fn test_instrument_error() {
+    let (tracer, spans) = Tracer::in_memory();
+
+    assert!(load_user(&tracer, &Db, -1).is_err());
+
+    let span = spans.find("load_user").unwrap();
+    assert!(matches!(
+        span.status,
+        SpanStatus::Error { ref message } if message == "no user -1"
+    ));
+}
+
+/// Errors without `Display` are described with `Debug`
+#[test]
+fn test_instrument_debug_error() {
+    let (tracer, spans) = Tracer::in_memory();
+
+    assert!(find(&tracer, 9).is_err());
+    assert!(check(&tracer).is_err());
+
+    let span = spans.find("find").unwrap();
+    assert!(matches!(
+        span.status,
+        SpanStatus::Error { ref message } if message == "NotFound { id: 9 }"
+    ));
+    let span = spans.find("check").unwrap();
+    assert!(matches!(
+        span.status,
+        SpanStatus::Error { ref message } if message == "()"
+    ));
+}
+
+/// Methods can return borrows of `&mut self`
+#[test]
+fn test_instrument_mut_self_borrow() {
+    let (tracer, spans) = Tracer::in_memory();
+    let mut counter = Counter { tracer, count: 0 };
+
+    *counter.count_mut() += 1;
+    *counter.try_count_mut(5).unwrap() += 1;
+    assert!(counter.try_count_mut(2).is_err());
+    assert_eq!(counter.count, 2);
+
+    assert_eq!(spans.matching(&SpanMatcher::named("count_mut")).len(), 1);
+    spans.assert_has(
+        SpanMatcher::named("try_count_mut")
+            .with_attribute("limit", 2)
+            .errored(),
+    );
+}
+
+/// Async functions are spans across awaits, with nested calls as children
+#[tokio::test]
+async fn test_instrument_async() {
+    let (tracer, spans) = Tracer::in_memory();
+
+    assert_eq!(handle_request(&tracer, "/users").await, Ok(6));
+
+    assert_eq!(spans.len(), 3);
+    spans.assert_has(
+        SpanMatcher::named("handle_request")
+            .with_attribute("path", "/users")
+            .root()
+            .ok(),
+    );
+    spans.assert_has(SpanMatcher::named("load_user").with_parent("handle_request"));
+    spans.assert_has(SpanMatcher::named("db.query").with_parent("handle_request"));
+}
+
+/// Errors propagate from nested spans up through async callers
+#[tokio::test]
+async fn test_instrument_async_error() {
+    let (tracer, spans) = Tracer::in_memory();
+
+    assert!(failing_request(&tracer, -3).await.is_err());
+
+    spans.assert_has(
+        SpanMatcher::named("failing_request")
+            .with_attribute("id", -3)
+            .errored(),
+    );
+    spans.assert_has(
+        SpanMatcher::named("load_user")
+            .with_parent("failing_request")
+            .errored(),
+    );
+}
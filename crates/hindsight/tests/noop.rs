@@ -0,0 +1,47 @@
+#![cfg(feature = "noop")]
+
+use hindsight::{current_context, Timestamp, TraceContext, Tracer};
+
+/// Every tracer is a no-op tracer, including in-memory ones
+#[test]
+fn test_tracers_are_noop() {
+    assert!(Tracer::noop().is_noop());
+
+    let (tracer, spans) = Tracer::in_memory();
+    assert!(tracer.is_noop());
+
+    let mut span = tracer.span("work").with_attribute("bytes", 1024).start();
+    span.set_attribute("key", "value");
+    span.add_event("event");
+    span.update_name("renamed");
+    span.set_error("failed");
+    span.record_duration_from(Timestamp::now());
+    span.end();
+
+    assert_eq!(spans.len(), 0);
+    assert_eq!(tracer.stats().spans_created, 0);
+}
+
+/// Spans pass their parent's context through, so propagation keeps working
+#[test]
+fn test_context_passes_through() {
+    let tracer = Tracer::noop();
+    let parent = TraceContext::new_root();
+
+    let span = tracer.span("child").with_parent(parent.clone()).start();
+    assert_eq!(span.context().trace_id, parent.trace_id);
+    assert_eq!(span.context().span_id, parent.span_id);
+    assert_eq!(span.is_sampled(), parent.flags & 0x01 != 0);
+
+    {
+        let _guard = span.enter();
+        let nested = tracer.span("nested").start();
+        assert_eq!(nested.context().span_id, parent.span_id);
+        assert_eq!(current_context().unwrap().span_id, parent.span_id);
+    }
+    assert!(current_context().is_none());
+
+    // Without a parent there is nothing to propagate
+    let root = tracer.span("root").start();
+    assert!(!root.is_sampled());
+}
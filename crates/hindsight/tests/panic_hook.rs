@@ -0,0 +1,33 @@
+#![cfg(not(feature = "noop"))]
+
+use std::panic::AssertUnwindSafe;
+use std::time::Duration;
+
+use hindsight::{SpanMatcher, SpanStatus, Tracer};
+
+/// A panic inside a span reports it once, errored, with one exception event
+#[test]
+fn test_panic_reports_span_once() {
+    let (tracer, spans) = Tracer::in_memory();
+    hindsight::install_panic_hook_with_timeout(&tracer, Duration::from_millis(100));
+
+    let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+        let span = tracer.span("work").start();
+        let _guard = span.enter();
+        panic!("boom");
+    }));
+    assert!(result.is_err());
+
+    let recorded = spans.spans();
+    assert_eq!(recorded.len(), 1);
+    let span = &recorded[0];
+    assert_eq!(span.name, "work");
+    assert!(matches!(&span.status, SpanStatus::Error { message } if message == "panicked: boom"));
+    let exceptions: Vec<_> = span
+        .events
+        .iter()
+        .filter(|event| event.name == "exception")
+        .collect();
+    assert_eq!(exceptions.len(), 1);
+    spans.assert_none(SpanMatcher::named("panic"));
+}
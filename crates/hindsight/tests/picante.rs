@@ -0,0 +1,64 @@
+#![cfg(all(feature = "picante", not(feature = "noop")))]
+
+use hindsight::picante::RuntimeExt;
+use hindsight::{SpanMatcher, Tracer};
+use picante::{HasRuntime, PicanteResult};
+
+#[picante::input]
+pub struct SourceFile {
+    #[key]
+    pub path: String,
+    pub text: String,
+}
+
+#[picante::tracked]
+pub async fn line_count<DB: DatabaseTrait>(db: &DB, file: SourceFile) -> PicanteResult<usize> {
+    Ok(file.text(db)?.lines().count())
+}
+
+#[picante::tracked]
+pub async fn summary<DB: DatabaseTrait>(db: &DB, file: SourceFile) -> PicanteResult<String> {
+    let lines = line_count(db, file).await?;
+    Ok(format!("{}: {} lines", file.path(db)?, lines))
+}
+
+#[picante::db(inputs(SourceFile), tracked(line_count, summary))]
+pub struct Database {}
+
+/// Running a query emits its span, with its dependency as a child, and
+/// reusing memoized results is reported as a hit
+#[tokio::test]
+async fn test_queries_emit_spans() {
+    let (tracer, spans) = Tracer::in_memory();
+    let db = Database::new();
+    db.runtime().set_tracer(tracer);
+
+    let file = SourceFile::new(&db, "src/main.rs".into(), "fn main() {\n}\n".into()).unwrap();
+    assert_eq!(summary(&db, file).await.unwrap(), "src/main.rs: 2 lines");
+
+    assert_eq!(spans.len(), 2);
+    spans.assert_has(
+        SpanMatcher::named("summary")
+            .with_attribute("picante.query", true)
+            .with_attribute("picante.query_kind", "summary")
+            .with_attribute("picante.cache_status", "miss")
+            .with_attribute("picante.dependency_count", 1)
+            .root()
+            .ok(),
+    );
+    spans.assert_has(
+        SpanMatcher::named("line_count")
+            .with_attribute("picante.cache_status", "miss")
+            .with_parent("summary"),
+    );
+    let summary_span = spans.find("summary").unwrap();
+    assert!(summary_span.attributes.contains_key("picante.query_key"));
+    assert!(summary_span.attributes.contains_key("picante.revision"));
+
+    // Nothing changed, so the memoized summary is reused without running
+    // its dependency
+    spans.clear();
+    summary(&db, file).await.unwrap();
+    assert_eq!(spans.len(), 1);
+    spans.assert_has(SpanMatcher::named("summary").with_attribute("picante.cache_status", "hit"));
+}
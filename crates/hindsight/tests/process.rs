@@ -0,0 +1,130 @@
+use hindsight::process::{CommandExt, BAGGAGE, HINDSIGHT_PARENT, TRACEPARENT, TRACESTATE};
+use hindsight::TraceContext;
+use std::ffi::OsStr;
+use std::process::Command;
+
+fn env<'a>(command: &'a Command, key: &str) -> Option<Option<&'a OsStr>> {
+    command
+        .get_envs()
+        .find(|(name, _)| *name == OsStr::new(key))
+        .map(|(_, value)| value)
+}
+
+/// The context is written to the child's environment
+#[test]
+fn test_command_gets_trace_context() {
+    let mut context = TraceContext::new_root();
+    context.baggage = Some("user_id=42".to_string());
+
+    let mut command = Command::new("cargo");
+    command.with_trace_context(&context);
+
+    assert_eq!(
+        env(&command, TRACEPARENT),
+        Some(Some(OsStr::new(&context.to_traceparent())))
+    );
+    // An inherited tracestate would belong to another trace
+    assert_eq!(env(&command, TRACESTATE), Some(None));
+    assert_eq!(env(&command, BAGGAGE), Some(Some(OsStr::new("user_id=42"))));
+    assert_eq!(env(&command, HINDSIGHT_PARENT), Some(Some(OsStr::new("1"))));
+}
+
+/// Spans started without a parent attach under the tracer's parent context
+#[cfg(not(feature = "noop"))]
+#[test]
+fn test_tracer_parent_context() {
+    use hindsight::{SpanMatcher, Tracer};
+
+    let parent = TraceContext::new_root();
+    let (tracer, spans) = Tracer::builder()
+        .parent_context(parent.clone())
+        .build_in_memory();
+
+    tracer.span("plugin_main").start().end();
+    tracer.span("fresh").root().start().end();
+
+    let plugin_main = spans.find("plugin_main").unwrap();
+    assert_eq!(plugin_main.trace_id, parent.trace_id);
+    assert_eq!(plugin_main.parent_span_id, Some(parent.span_id));
+    spans.assert_has(SpanMatcher::named("fresh").root());
+}
+
+/// Set in the child process this test binary spawns: whether its tracer
+/// should join the inherited trace (`join`) or ignore it (`ignore`)
+#[cfg(all(feature = "client", not(feature = "noop")))]
+const CHILD_MODE: &str = "HINDSIGHT_TEST_CHILD";
+
+/// Run `child_reads_trace_context` in a new process of this test binary
+#[cfg(all(feature = "client", not(feature = "noop")))]
+fn run_child(command: &mut Command) {
+    let output = command
+        .args(["child_reads_trace_context", "--exact", "--nocapture"])
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "child failed:\n{}{}",
+        stdout,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(
+        stdout.contains("1 passed"),
+        "child test didn't run:\n{}",
+        stdout
+    );
+}
+
+/// A process spawned with `with_trace_context` joins the trace; one that
+/// only inherits a foreign `TRACEPARENT` starts its own
+#[cfg(all(feature = "client", not(feature = "noop")))]
+#[test]
+fn test_child_process_env() {
+    let exe = std::env::current_exe().unwrap();
+    let parent = TraceContext::new_root();
+
+    run_child(
+        Command::new(&exe)
+            .env(CHILD_MODE, "join")
+            .with_trace_context(&parent),
+    );
+    run_child(
+        Command::new(&exe)
+            .env(CHILD_MODE, "ignore")
+            .env(TRACEPARENT, parent.to_traceparent())
+            .env_remove(HINDSIGHT_PARENT),
+    );
+}
+
+/// The child side of `test_child_process_env`; does nothing on its own
+#[cfg(all(feature = "client", not(feature = "noop")))]
+#[tokio::test]
+async fn child_reads_trace_context() {
+    use hindsight::{ExportFuture, Exporter, Span, Tracer};
+
+    struct Discard;
+
+    impl Exporter for Discard {
+        fn export(&self, _spans: Vec<Span>) -> ExportFuture<'_> {
+            Box::pin(async { Ok(()) })
+        }
+    }
+
+    let Ok(mode) = std::env::var(CHILD_MODE) else {
+        return;
+    };
+    let inherited = hindsight::process::extract_env().expect("TRACEPARENT is set");
+
+    let tracer = Tracer::builder().exporter(Discard).build_exporters();
+    let span = tracer.span("child_main").start();
+    let context = span.context().clone();
+    span.end();
+
+    if mode == "join" {
+        assert_eq!(context.trace_id, inherited.trace_id);
+        assert_eq!(context.parent_span_id, Some(inherited.span_id));
+    } else {
+        assert_ne!(context.trace_id, inherited.trace_id);
+        assert_eq!(context.parent_span_id, None);
+    }
+}
@@ -0,0 +1,131 @@
+#![cfg(all(feature = "rpc", not(feature = "noop")))]
+
+use hindsight::rpc::RpcSessionExt;
+use hindsight::{HindsightServiceClient, Instrument, SpanMatcher, Tracer};
+use rapace::{RpcSession, Transport};
+use std::sync::Arc;
+
+#[allow(async_fn_in_trait)]
+#[rapace::service]
+trait Calculator {
+    async fn add(&self, a: i32, b: i32) -> i32;
+}
+
+#[derive(Clone)]
+struct CalculatorImpl {
+    tracer: Tracer,
+}
+
+impl Calculator for CalculatorImpl {
+    async fn add(&self, a: i32, b: i32) -> i32 {
+        // Work in the handler nests under the server span
+        self.tracer.span("compute").start().end();
+        a + b
+    }
+}
+
+/// Serve `Calculator` over an in-process pipe and return a client for it
+///
+/// Each end reports to its own tracer, like two processes would.
+fn connect(client_tracer: Tracer, server_tracer: Tracer) -> CalculatorClient {
+    let (client_stream, server_stream) = tokio::io::duplex(8192);
+
+    let session = Arc::new(
+        RpcSession::new(Transport::stream(server_stream)).with_tracer(server_tracer.clone()),
+    );
+    session.set_dispatcher({
+        let session = session.clone();
+        move |frame| {
+            let service = CalculatorImpl {
+                tracer: server_tracer.clone(),
+            };
+            let session = session.clone();
+            Box::pin(async move {
+                CalculatorServer::new(service)
+                    .dispatch(frame.desc.method_id, &frame, session.buffer_pool())
+                    .await
+            })
+        }
+    });
+    tokio::spawn(async move { session.run().await });
+
+    let session =
+        Arc::new(RpcSession::new(Transport::stream(client_stream)).with_tracer(client_tracer));
+    let runner = session.clone();
+    tokio::spawn(async move { runner.run().await });
+    CalculatorClient::new(session)
+}
+
+/// Calls get a client span, and dispatch a server span parented to it
+/// through the frame metadata, with no instrumentation in the service
+#[tokio::test]
+async fn test_calls_are_traced_across_sessions() {
+    let (client_tracer, client_spans) = Tracer::in_memory();
+    let (server_tracer, server_spans) = Tracer::in_memory();
+
+    let client = connect(client_tracer.clone(), server_tracer);
+
+    let parent = client_tracer.span("checkout").start();
+    let sum = client
+        .add(1, 2)
+        .instrument(parent.context().clone())
+        .await
+        .unwrap();
+    parent.end();
+    assert_eq!(sum, 3);
+
+    client_spans.assert_has(
+        SpanMatcher::named("Calculator.add")
+            .with_attribute("span.kind", "client")
+            .with_attribute("rpc.system", "rapace")
+            .with_attribute("rpc.service", "Calculator")
+            .with_attribute("rpc.method", "add")
+            .with_parent("checkout")
+            .ok(),
+    );
+    server_spans.assert_has(
+        SpanMatcher::named("Calculator.add")
+            .with_attribute("span.kind", "server")
+            .with_attribute("rpc.system", "rapace")
+            .ok(),
+    );
+    server_spans.assert_has(SpanMatcher::named("compute").with_parent("Calculator.add"));
+
+    let client_span = client_spans.find("Calculator.add").unwrap();
+    let server_span = server_spans.find("Calculator.add").unwrap();
+    assert_eq!(server_span.trace_id, client_span.trace_id);
+    assert_eq!(server_span.parent_span_id, Some(client_span.span_id));
+}
+
+/// Calls to hindsight's own service never produce spans
+#[tokio::test(flavor = "multi_thread")]
+async fn test_hindsight_service_is_untraced() {
+    let server = tokio::spawn(async {
+        hindsight_server::run_server("127.0.0.1", 19970, 19971, 3600, false, Default::default())
+            .await
+    });
+    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+
+    let (tracer, spans) = Tracer::in_memory();
+    let stream = tokio::net::TcpStream::connect("127.0.0.1:19970")
+        .await
+        .unwrap();
+    let session = Arc::new(RpcSession::new(Transport::stream(stream)).with_tracer(tracer.clone()));
+    let runner = session.clone();
+    tokio::spawn(async move { runner.run().await });
+    let client = HindsightServiceClient::new(session);
+
+    let span = tracer.span("health").start();
+    let pong = client
+        .ping()
+        .instrument(span.context().clone())
+        .await
+        .unwrap();
+    span.end();
+    assert!(!pong.is_empty());
+
+    assert_eq!(spans.len(), 1);
+    spans.assert_none(SpanMatcher::named("HindsightService.ping"));
+
+    server.abort();
+}
@@ -0,0 +1,76 @@
+#![cfg(all(feature = "tracing", not(feature = "noop")))]
+
+use hindsight::{untraced, AttributeValue, HindsightLayer, SpanMatcher, Tracer};
+use tracing_subscriber::prelude::*;
+
+/// `tracing` spans become hindsight spans, with fields as attributes and
+/// events as span events, nesting with hindsight's own spans
+#[test]
+fn test_layer_exports_tracing_spans() {
+    let (tracer, spans) = Tracer::in_memory();
+    let subscriber = tracing_subscriber::registry().with(HindsightLayer::new(tracer.clone()));
+
+    tracing::subscriber::with_default(subscriber, || {
+        let request = tracing::info_span!(
+            "handle_request",
+            user_id = 42,
+            path = "/users",
+            status = tracing::field::Empty
+        );
+        let _entered = request.enter();
+        tracing::info!(cache = "miss", "loading user");
+        tracing::info_span!("load_user").in_scope(|| {
+            // Entered `tracing` spans are current for hindsight spans too
+            tracer.span("query").start().end();
+        });
+        request.record("status", 200);
+    });
+
+    assert_eq!(spans.len(), 3);
+    spans.assert_has(
+        SpanMatcher::named("handle_request")
+            .with_attribute("user_id", 42)
+            .with_attribute("path", "/users")
+            .with_attribute("status", 200)
+            .root(),
+    );
+    spans.assert_has(SpanMatcher::named("load_user").with_parent("handle_request"));
+    spans.assert_has(SpanMatcher::named("query").with_parent("load_user"));
+
+    let request = spans.find("handle_request").unwrap();
+    assert_eq!(request.events.len(), 1);
+    let event = &request.events[0];
+    assert_eq!(event.name, "loading user");
+    assert_eq!(
+        event.attributes.get("cache"),
+        Some(&AttributeValue::String("miss".into()))
+    );
+    assert_eq!(
+        event.attributes.get("level"),
+        Some(&AttributeValue::String("INFO".into()))
+    );
+}
+
+/// Nothing emitted from hindsight's own export tasks is traced
+#[tokio::test]
+async fn test_layer_ignores_untraced_tasks() {
+    let (tracer, spans) = Tracer::in_memory();
+    let subscriber = tracing_subscriber::registry().with(HindsightLayer::new(tracer));
+    let _default = tracing::subscriber::set_default(subscriber);
+
+    let app = tracing::info_span!("app", batch = tracing::field::Empty);
+    untraced(async {
+        tracing::info_span!("ingest_spans").in_scope(|| {
+            tracing::info!("sending batch");
+        });
+        app.record("batch", 1);
+        app.in_scope(|| tracing::info!("sent"));
+    })
+    .await;
+    drop(app);
+
+    assert_eq!(spans.len(), 1);
+    let app = spans.find("app").unwrap();
+    assert!(app.attributes.is_empty());
+    assert!(app.events.is_empty());
+}
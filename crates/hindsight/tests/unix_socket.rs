@@ -0,0 +1,54 @@
+#![cfg(all(unix, feature = "client", not(feature = "noop")))]
+
+use hindsight::{HindsightServiceClient, Tracer};
+use rapace::{RpcSession, Transport};
+use std::os::unix::fs::PermissionsExt;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Spans sent through the server's Unix socket can be read back through it
+#[tokio::test(flavor = "multi_thread")]
+async fn test_unix_socket_round_trip() {
+    let dir = std::env::temp_dir().join(format!("hindsight-unix-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let path = dir.join("hindsight.sock");
+
+    let options = hindsight_server::ServerOptions {
+        unix_socket: Some(hindsight_server::UnixSocket {
+            path: path.clone(),
+            mode: 0o600,
+        }),
+        ..Default::default()
+    };
+    let server = tokio::spawn(async move {
+        hindsight_server::run_server("127.0.0.1", 19950, 19951, 3600, false, options).await
+    });
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+
+    let tracer = Tracer::connect_unix(&path).await.unwrap();
+    let span = tracer.span("over_unix").root().start();
+    let trace_id = span.context().trace_id;
+    span.end();
+    let flusher = tracer.clone();
+    let flushed =
+        tokio::task::spawn_blocking(move || flusher.flush_blocking(Duration::from_secs(5)))
+            .await
+            .unwrap();
+    assert!(flushed);
+
+    // The socket also speaks raw rapace
+    let stream = tokio::net::UnixStream::connect(&path).await.unwrap();
+    let session = Arc::new(RpcSession::new(Transport::stream(stream)));
+    let runner = session.clone();
+    tokio::spawn(async move { runner.run().await });
+    let client = HindsightServiceClient::new(session);
+
+    let trace = client.get_trace(trace_id).await.unwrap().unwrap();
+    assert_eq!(trace.spans.len(), 1);
+    assert_eq!(trace.spans[0].name, "over_unix");
+
+    server.abort();
+}